@@ -0,0 +1,96 @@
+//! A square-wave beeper for the sound timer.
+//!
+//! CHIP-8 has a single sound: a buzzer that plays while the sound timer is non-zero. `Beeper`
+//! renders it as PCM samples, so a frontend without a synthesiser only has to queue buffers.
+
+use crate::machine::Machine;
+
+/// This constant defines the default beeper tone in hertz.
+pub const DEFAULT_FREQUENCY: u32 = 440;
+
+/// This struct renders the sound timer as a square wave.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Beeper {
+    /// This field stores the tone in hertz.
+    pub frequency: u32,
+    /// This field stores the amplitude, from 0.0 to 1.0.
+    pub volume: f32,
+    sample_rate: u32,
+    // position in the current period, counted in sample_rate steps of frequency
+    phase: u32,
+}
+
+impl Beeper {
+    /// This function builds a beeper at the default tone and half volume.
+    /// Arguments:
+    /// - sample_rate: The output sample rate in hertz.
+    ///
+    /// Returns: The beeper.
+    pub fn new(sample_rate: u32) -> Beeper {
+        Beeper {
+            frequency: DEFAULT_FREQUENCY,
+            volume: 0.5,
+            sample_rate: sample_rate.max(1),
+            phase: 0,
+        }
+    }
+
+    /// This function returns the output sample rate.
+    /// Arguments: none.
+    /// Returns: The sample rate in hertz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// This function fills a buffer with the beeper's output. Call it once per buffer after the
+    /// frame that the buffer plays during; the tone restarts each time the sound timer does.
+    /// Arguments:
+    /// - machine: The machine whose sound timer drives the beeper.
+    /// - samples: The mono samples to fill, from -volume to volume.
+    ///
+    /// Returns: none.
+    pub fn render(&mut self, machine: &impl Machine, samples: &mut [f32]) {
+        if !machine.audio().is_playing {
+            self.phase = 0;
+            samples.fill(0.0);
+            return;
+        }
+        let frequency = self.frequency.min(self.sample_rate / 2);
+        for sample in samples {
+            *sample = if self.phase < self.sample_rate / 2 {
+                self.volume
+            } else {
+                -self.volume
+            };
+            self.phase = (self.phase + frequency) % self.sample_rate;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chip8::Chip8Sys;
+
+    #[test]
+    // Tests that the beeper is silent until the sound timer runs and then plays a square wave
+    fn test_square_wave() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        let mut beeper = Beeper::new(8000);
+        beeper.frequency = 1000;
+        beeper.volume = 1.0;
+        let mut samples = [1.0; 12];
+        beeper.render(&chip8, &mut samples);
+        assert_eq!(samples, [0.0; 12]);
+
+        chip8.sound_timer = 2;
+        chip8.is_playing_sound = true;
+        beeper.render(&chip8, &mut samples);
+        assert_eq!(
+            samples,
+            [
+                1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 1.0
+            ]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Headless batch runs for ROM regression sweeps.
+//!
+//! Every ROM is run under every profile on its own `Chip8Sys`, spread over a pool of threads.
+//! Runs are seeded so a sweep gives the same report every time, which can be written as JSON or
+//! CSV for CI.
+
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::chip8::{Chip8Quirks, Chip8Sys, TimerMode};
+use crate::halt::RunStatus;
+use crate::platform::Platform;
+use crate::rom_db::{platform_quirks, sha1_hex};
+
+/// This struct names a platform and quirk combination to run ROMs under.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// This field stores the name used in reports.
+    pub name: String,
+    /// This field stores the platform to emulate.
+    pub platform: Platform,
+    /// This field stores the quirks to apply.
+    pub quirks: Chip8Quirks,
+}
+
+impl Profile {
+    /// This function returns the built-in profiles.
+    /// Arguments: none.
+    /// Returns: Profiles for VIP CHIP-8, modern CHIP-8, hi-res CHIP-8 and CHIP-8X.
+    pub fn defaults() -> Vec<Profile> {
+        ["chip8", "modern", "hires", "chip8x"]
+            .into_iter()
+            .filter_map(Profile::from_name)
+            .collect()
+    }
+
+    /// This function looks up a built-in profile by name.
+    /// Arguments:
+    /// - name: One of `chip8`, `modern`, `hires` or `chip8x`.
+    ///
+    /// Returns: The profile, or None for an unknown name.
+    pub fn from_name(name: &str) -> Option<Profile> {
+        let (platform, platform_id) = match name {
+            "chip8" => (Platform::Chip8, "originalChip8"),
+            "modern" => (Platform::Chip8, "modernChip8"),
+            "hires" => (Platform::HiresChip8, "originalChip8"),
+            "chip8x" => (Platform::Chip8X, "chip8x"),
+            _ => return None,
+        };
+        Some(Profile {
+            name: name.to_owned(),
+            platform,
+            quirks: platform_quirks(platform_id)?.to_quirks(platform_id),
+        })
+    }
+}
+
+/// This struct configures a batch run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// This field stores the number of 60Hz frames each ROM runs for.
+    pub frames: u32,
+    /// This field stores the number of instructions executed per frame.
+    pub instructions_per_frame: u32,
+    /// This field stores the seed for CXNN, shared by every run.
+    pub seed: u64,
+    /// This field stores the number of worker threads, 0 uses the available parallelism.
+    pub threads: usize,
+    /// This field stores the profiles every ROM is run under.
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for BatchConfig {
+    /// This function returns a ten second run at roughly 700 instructions per second.
+    /// Arguments: none.
+    /// Returns: The default batch configuration.
+    fn default() -> Self {
+        Self {
+            frames: 600,
+            instructions_per_frame: 12,
+            seed: 0,
+            threads: 0,
+            profiles: Profile::defaults(),
+        }
+    }
+}
+
+/// This struct names a ROM to run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomJob {
+    /// This field stores the name used in reports, usually the file path.
+    pub name: String,
+    /// This field stores the ROM bytes.
+    pub bytes: Vec<u8>,
+}
+
+/// This enum lists how a run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Outcome {
+    /// The ROM ran for every frame and left something on screen.
+    Ok,
+    /// The ROM stopped with an error, holds the error's debug text.
+    Error(String),
+    /// The ROM halted, usually by jumping to itself, the way CHIP-8 programs end.
+    InfiniteLoop,
+    /// The screen was blank at the end of the run.
+    BlankScreen,
+}
+
+impl Outcome {
+    /// This function returns the outcome's name used in reports.
+    /// Arguments: none.
+    /// Returns: `ok`, `error`, `infinite_loop` or `blank_screen`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Error(_) => "error",
+            Outcome::InfiniteLoop => "infinite_loop",
+            Outcome::BlankScreen => "blank_screen",
+        }
+    }
+}
+
+/// This struct stores the result of running one ROM under one profile.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct RunReport {
+    /// This field stores the ROM name.
+    pub rom: String,
+    /// This field stores the profile name.
+    pub profile: String,
+    /// This field stores how the run ended.
+    pub outcome: Outcome,
+    /// This field stores the SHA-1 of the final packed framebuffer.
+    pub framebuffer_sha1: String,
+    /// This field stores the number of instructions executed, not counting FX0A waits.
+    pub instructions: u64,
+    /// This field stores the number of frames run.
+    pub frames: u32,
+}
+
+/// This function runs a single ROM under a single profile.
+/// Arguments:
+/// - rom: The ROM to run.
+/// - profile: The platform and quirks to use.
+/// - config: The frame count, speed and seed.
+///
+/// Returns: The run report.
+pub fn run_rom(rom: &RomJob, profile: &Profile, config: &BatchConfig) -> RunReport {
+    let mut chip8 = Chip8Sys::new_with_quirks(profile.quirks);
+    chip8
+        .set_timer_mode(TimerMode::External)
+        .set_random_seed(Some(config.seed))
+        .set_platform(profile.platform)
+        .load_rom_bytes(&rom.bytes);
+    let mut instructions = 0;
+    let mut frames = 0;
+    let mut outcome = Outcome::Ok;
+    'frames: while frames < config.frames {
+        frames += 1;
+        for _ in 0..config.instructions_per_frame {
+            let is_waiting = chip8.is_waiting_for_key();
+            if let Err(error) = chip8.run() {
+                outcome = Outcome::Error(format!("{error:?}"));
+                break 'frames;
+            }
+            if is_waiting {
+                continue;
+            }
+            instructions += 1;
+            if chip8.status() == RunStatus::Halted {
+                outcome = Outcome::InfiniteLoop;
+                break 'frames;
+            }
+        }
+        chip8.tick_timers(1);
+    }
+    let framebuffer = chip8.framebuffer_packed();
+    // a blank screen is more useful to flag than how the ROM ended, errors still win
+    let is_blank = framebuffer.iter().all(|&byte| byte == 0);
+    if is_blank && !matches!(outcome, Outcome::Error(_)) {
+        outcome = Outcome::BlankScreen;
+    }
+    RunReport {
+        rom: rom.name.clone(),
+        profile: profile.name.clone(),
+        outcome,
+        framebuffer_sha1: sha1_hex(framebuffer),
+        instructions,
+        frames,
+    }
+}
+
+/// This function runs every ROM under every profile across a pool of threads.
+/// Arguments:
+/// - roms: The ROMs to run.
+/// - config: The batch configuration.
+///
+/// Returns: One report per ROM and profile, ordered by ROM then profile.
+pub fn run_batch(roms: &[RomJob], config: &BatchConfig) -> Vec<RunReport> {
+    let job_count = roms.len() * config.profiles.len();
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    }
+    .min(job_count.max(1));
+    let next_job = AtomicUsize::new(0);
+    let reports = Mutex::new(vec![None; job_count]);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    if job >= job_count {
+                        break;
+                    }
+                    let rom = &roms[job / config.profiles.len()];
+                    let profile = &config.profiles[job % config.profiles.len()];
+                    let report = run_rom(rom, profile, config);
+                    reports.lock().expect("a worker panicked")[job] = Some(report);
+                }
+            });
+        }
+    });
+    reports
+        .into_inner()
+        .expect("a worker panicked")
+        .into_iter()
+        .map(|report| report.expect("every job should have been run"))
+        .collect()
+}
+
+/// This function writes reports as a JSON array.
+/// Arguments:
+/// - reports: The reports to write.
+///
+/// Returns: The JSON text.
+#[cfg(feature = "serde")]
+pub fn to_json(reports: &[RunReport]) -> String {
+    serde_json::to_string_pretty(reports).expect("reports only hold strings and numbers")
+}
+
+/// This function writes reports as CSV with a header row.
+/// Arguments:
+/// - reports: The reports to write.
+///
+/// Returns: The CSV text.
+pub fn to_csv(reports: &[RunReport]) -> String {
+    let mut output =
+        String::from("rom,profile,outcome,error,framebuffer_sha1,instructions,frames\n");
+    for report in reports {
+        let error = match &report.outcome {
+            Outcome::Error(error) => error.as_str(),
+            _ => "",
+        };
+        let _ = writeln!(
+            output,
+            "{},{},{},{},{},{},{}",
+            csv_field(&report.rom),
+            csv_field(&report.profile),
+            report.outcome.name(),
+            csv_field(error),
+            report.framebuffer_sha1,
+            report.instructions,
+            report.frames
+        );
+    }
+    output
+}
+
+// Quotes a CSV field when it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(profiles: &[&str]) -> BatchConfig {
+        BatchConfig {
+            frames: 10,
+            threads: 2,
+            profiles: profiles
+                .iter()
+                .map(|name| Profile::from_name(name).unwrap())
+                .collect(),
+            ..BatchConfig::default()
+        }
+    }
+
+    #[test]
+    // Tests the outcomes for a ROM that draws and halts, one that errors and one that spins
+    fn test_run_batch_outcomes() {
+        let roms = [
+            // A05A D005 1204: draw the "2" sprite and jump to itself
+            RomJob {
+                name: "draw".to_owned(),
+                bytes: vec![0xA0, 0x5A, 0xD0, 0x05, 0x12, 0x04],
+            },
+            RomJob {
+                name: "invalid".to_owned(),
+                bytes: vec![0x80, 0x0F],
+            },
+            // 7001 1200: count forever without drawing
+            RomJob {
+                name: "blank".to_owned(),
+                bytes: vec![0x70, 0x01, 0x12, 0x00],
+            },
+        ];
+        let reports = run_batch(&roms, &config(&["chip8", "modern"]));
+        let outcomes: Vec<(&str, &str, &str)> = reports
+            .iter()
+            .map(|report| {
+                (
+                    report.rom.as_str(),
+                    report.profile.as_str(),
+                    report.outcome.name(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("draw", "chip8", "infinite_loop"),
+                ("draw", "modern", "infinite_loop"),
+                ("invalid", "chip8", "error"),
+                ("invalid", "modern", "error"),
+                ("blank", "chip8", "blank_screen"),
+                ("blank", "modern", "blank_screen"),
+            ]
+        );
+        assert_eq!(reports[0].instructions, 3);
+        assert_eq!(reports[0].framebuffer_sha1, reports[1].framebuffer_sha1);
+        assert_eq!(reports[4].instructions, 120);
+    }
+
+    #[test]
+    // Tests that seeded runs are reproducible and that CSV fields are quoted
+    fn test_reports_are_reproducible() {
+        // C00F F029 6100 D015 1208: draw a random font sprite and halt
+        let roms = [RomJob {
+            name: "random, \"font\"".to_owned(),
+            bytes: vec![0xC0, 0x0F, 0xF0, 0x29, 0x61, 0x00, 0xD0, 0x15, 0x12, 0x08],
+        }];
+        let first = run_batch(&roms, &config(&["chip8"]));
+        let second = run_batch(&roms, &config(&["chip8"]));
+        assert_eq!(first, second);
+        assert_eq!(first[0].outcome, Outcome::InfiniteLoop);
+        let csv = to_csv(&first);
+        assert!(
+            csv.starts_with("rom,profile,outcome,error,framebuffer_sha1,instructions,frames\n")
+        );
+        assert!(csv.contains("\"random, \"\"font\"\"\",chip8,infinite_loop,,"));
+        #[cfg(feature = "serde")]
+        assert!(to_json(&first).contains("\"outcome\": \"infinite_loop\""));
+    }
+}
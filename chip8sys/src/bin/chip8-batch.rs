@@ -0,0 +1,126 @@
+//! Runs a library of ROMs headless and prints a JSON or CSV report.
+//!
+//! Usage: `chip8-batch [options] <rom or directory>...`
+//!
+//! Exits with status 1 when any run ended with an error.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use chip8sys::batch::{self, BatchConfig, Outcome, Profile, RomJob};
+
+const USAGE: &str = "Usage: chip8-batch [options] <rom or directory>...
+
+Options:
+  --frames <n>        frames to run each ROM for (default 600)
+  --speed <n>         instructions per frame (default 12)
+  --seed <n>          seed for CXNN (default 0)
+  --threads <n>       worker threads, 0 for one per core (default 0)
+  --profiles <list>   comma separated: chip8,modern,hires,chip8x (default all)
+  --format <format>   json or csv (default json)
+  --output <path>     write the report to a file instead of stdout";
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(true) => ExitCode::FAILURE,
+        Ok(false) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Parses the arguments, runs the batch and writes the report.
+// Returns whether any run ended with an error.
+fn run(args: Vec<String>) -> Result<bool, String> {
+    let mut config = BatchConfig::default();
+    let mut is_csv = false;
+    let mut output = None;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+        match arg.as_str() {
+            "--frames" => config.frames = parse_number(&value("--frames")?)?,
+            "--speed" => config.instructions_per_frame = parse_number(&value("--speed")?)?,
+            "--seed" => config.seed = parse_number(&value("--seed")?)?,
+            "--threads" => config.threads = parse_number(&value("--threads")?)?,
+            "--profiles" => {
+                config.profiles = value("--profiles")?
+                    .split(',')
+                    .map(|name| {
+                        Profile::from_name(name.trim()).ok_or(format!("unknown profile {name}"))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--format" => {
+                is_csv = match value("--format")?.as_str() {
+                    "json" => false,
+                    "csv" => true,
+                    format => return Err(format!("unknown format {format}")),
+                };
+            }
+            "--output" => output = Some(value("--output")?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(false);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        return Err("no ROMs given".to_owned());
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        collect_roms(path, &mut files).map_err(|error| format!("{}: {error}", path.display()))?;
+    }
+    files.sort();
+    let roms = files
+        .iter()
+        .map(|file| {
+            fs::read(file)
+                .map(|bytes| RomJob {
+                    name: file.display().to_string(),
+                    bytes,
+                })
+                .map_err(|error| format!("{}: {error}", file.display()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let reports = batch::run_batch(&roms, &config);
+    let report = if is_csv {
+        batch::to_csv(&reports)
+    } else {
+        batch::to_json(&reports)
+    };
+    match output {
+        Some(path) => fs::write(&path, report).map_err(|error| format!("{path}: {error}"))?,
+        None => println!("{report}"),
+    }
+    Ok(reports
+        .iter()
+        .any(|report| matches!(report.outcome, Outcome::Error(_))))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{value} is not a number"))
+}
+
+// Adds a ROM file, or every file below a directory
+fn collect_roms(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(path)? {
+        collect_roms(&entry?.path(), files)?;
+    }
+    Ok(())
+}
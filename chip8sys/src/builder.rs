@@ -0,0 +1,378 @@
+//! Configuring a Chip-8 system in one place.
+//!
+//! `Chip8SysBuilder` collects the platform, quirks, timing, memory layout, random seed, initial ROM
+//! and memory hooks, checks that they fit together and only then builds the `Chip8Sys`, so a bad
+//! configuration is reported instead of silently truncated.
+
+use crate::bus::MEMORY_SIZE;
+#[cfg(feature = "debugger")]
+use crate::bus::MemoryHook;
+use crate::chip8::{Chip8Quirks, Chip8Sys, TimerMode};
+use crate::chip8error::Chip8Error;
+use crate::font::Font;
+use crate::memory_map::MemoryMap;
+use crate::platform::Platform;
+use crate::timing::TimingModel;
+
+/// This struct builds a `Chip8Sys` from a checked configuration.
+pub struct Chip8SysBuilder<'a> {
+    platform: Platform,
+    quirks: Chip8Quirks,
+    timer_mode: TimerMode,
+    timing_model: TimingModel,
+    memory_map: MemoryMap,
+    random_seed: Option<u64>,
+    rom: Option<&'a [u8]>,
+    #[cfg(feature = "debugger")]
+    hooks: Vec<Box<dyn MemoryHook + Send>>,
+}
+
+impl Default for Chip8SysBuilder<'_> {
+    /// This function returns a builder with the default configuration.
+    /// Arguments: none.
+    /// Returns: A new builder.
+    fn default() -> Self {
+        Chip8SysBuilder::new()
+    }
+}
+
+impl<'a> Chip8SysBuilder<'a> {
+    /// This function returns a builder for the configuration `Chip8Sys::new_chip_8` uses:
+    /// CHIP-8, default quirks, timers run by the CPU, 4KB of memory and the built-in font.
+    /// Arguments: none.
+    /// Returns: A new builder.
+    pub fn new() -> Chip8SysBuilder<'a> {
+        Chip8SysBuilder {
+            platform: Platform::Chip8,
+            quirks: Chip8Quirks::default(),
+            timer_mode: TimerMode::Cycle,
+            timing_model: TimingModel::Instruction,
+            memory_map: MemoryMap {
+                memory_size: MEMORY_SIZE,
+                ..MemoryMap::default()
+            },
+            random_seed: None,
+            rom: None,
+            #[cfg(feature = "debugger")]
+            hooks: Vec::new(),
+        }
+    }
+
+    /// This function selects the emulated platform.
+    /// Arguments:
+    /// - platform: The platform to emulate.
+    ///
+    /// Returns: The updated builder.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// This function sets the quirks.
+    /// Arguments:
+    /// - quirks: The quirk configuration.
+    ///
+    /// Returns: The updated builder.
+    pub fn quirks(mut self, quirks: Chip8Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// This function sets who decrements the delay and sound timers.
+    /// Arguments:
+    /// - mode: The timer update mode.
+    ///
+    /// Returns: The updated builder.
+    pub fn timer_mode(mut self, mode: TimerMode) -> Self {
+        self.timer_mode = mode;
+        self
+    }
+
+    /// This function sets how instruction timing is modelled.
+    /// Arguments:
+    /// - model: The timing model.
+    ///
+    /// Returns: The updated builder.
+    pub fn timing_model(mut self, model: TimingModel) -> Self {
+        self.timing_model = model;
+        self
+    }
+
+    /// This function sets the installed memory, e.g. 2048 for a 2KB COSMAC VIP.
+    /// Addresses past the end wrap around.
+    /// Arguments:
+    /// - size: The size in bytes, a power of two from 1024 to 4096.
+    ///
+    /// Returns: The updated builder.
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_map.memory_size = size;
+        self
+    }
+
+    /// This function selects the font FX29 and FX30 point at, see `font::Font`.
+    /// Arguments:
+    /// - font: The font and its address.
+    ///
+    /// Returns: The updated builder.
+    pub fn font(mut self, font: Font) -> Self {
+        self.memory_map.font = font;
+        self
+    }
+
+    /// This function sets the address ROMs load and start at instead of the platform's.
+    /// Arguments:
+    /// - address: The load address, outside the font and inside memory.
+    ///
+    /// Returns: The updated builder.
+    pub fn load_address(mut self, address: u16) -> Self {
+        self.memory_map.load_address = Some(address);
+        self
+    }
+
+    /// This function sets the address execution starts at when it differs from the load address.
+    /// Arguments:
+    /// - address: The entry address, outside the font and inside memory.
+    ///
+    /// Returns: The updated builder.
+    pub fn entry_address(mut self, address: u16) -> Self {
+        self.memory_map.entry_address = Some(address);
+        self
+    }
+
+    /// This function sets the memory size, font, load and entry addresses at once, e.g.
+    /// `MemoryMap::eti_660()`. Later setters override parts of it.
+    /// Arguments:
+    /// - map: The memory map.
+    ///
+    /// Returns: The updated builder.
+    pub fn memory_map(mut self, map: MemoryMap) -> Self {
+        self.memory_map = map;
+        self
+    }
+
+    /// This function seeds the CXNN random number generator, see `Chip8Sys::set_random_seed`.
+    /// Arguments:
+    /// - seed: The seed.
+    ///
+    /// Returns: The updated builder.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// This function sets a ROM to load once the system is built.
+    /// Arguments:
+    /// - rom_bytes: The ROM image.
+    ///
+    /// Returns: The updated builder.
+    pub fn rom(mut self, rom_bytes: &'a [u8]) -> Self {
+        self.rom = Some(rom_bytes);
+        self
+    }
+
+    /// This function attaches a memory hook, e.g. to trace execution, in the order given.
+    /// Arguments:
+    /// - hook: The hook to attach.
+    ///
+    /// Returns: The updated builder.
+    #[cfg(feature = "debugger")]
+    pub fn hook(mut self, hook: impl MemoryHook + Send + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// This function checks the configuration and builds the system.
+    /// Arguments: none.
+    /// Returns: The Chip-8 system, or InvalidMemorySize, InvalidFontAddress when the font does
+    /// not fit in memory, InvalidLoadAddress when programs would load or start in the font or
+    /// past the end of memory, or RomTooLarge when the ROM does not fit or would overwrite the
+    /// font.
+    pub fn build(self) -> Result<Chip8Sys, Chip8Error> {
+        let mut chip8 = Chip8Sys::new_with_quirks(self.quirks);
+        chip8.set_platform(self.platform);
+        chip8.set_memory_map(self.memory_map)?;
+        if let Some(rom) = self.rom {
+            let font = self.memory_map.font.range();
+            let program_start = chip8.program_start();
+            let rom_end = program_start as usize + rom.len();
+            let is_over_font = program_start < font.start && rom_end > font.start as usize;
+            if rom_end > self.memory_map.memory_size || is_over_font {
+                return Err(Chip8Error::RomTooLarge(rom.len()));
+            }
+        }
+
+        chip8.set_timer_mode(self.timer_mode);
+        chip8.set_timing_model(self.timing_model);
+        if let Some(seed) = self.random_seed {
+            chip8.set_random_seed(Some(seed));
+        }
+        #[cfg(feature = "debugger")]
+        for hook in self.hooks {
+            chip8.memory.add_boxed_hook(hook);
+        }
+        if let Some(rom) = self.rom {
+            chip8.load_rom_bytes(rom);
+        }
+        Ok(chip8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::font::FontSet;
+
+    #[test]
+    // Tests that a default builder gives the same machine as new_chip_8
+    fn test_defaults() {
+        let chip8 = Chip8Sys::builder().build().unwrap();
+        let expected = Chip8Sys::new_chip_8();
+        assert_eq!(chip8.platform(), expected.platform());
+        assert_eq!(chip8.quirks(), expected.quirks());
+        assert_eq!(chip8.timer_mode(), expected.timer_mode());
+        assert_eq!(chip8.memory_map(), expected.memory_map());
+        assert_eq!(chip8.program_counter, 0x200);
+    }
+
+    #[test]
+    // Tests that the platform, quirks, timer mode and seed are applied and survive a reset
+    fn test_machine_settings() {
+        let quirks = Chip8Quirks {
+            wrap_draw: true,
+            ..Chip8Quirks::default()
+        };
+        let mut chip8 = Chip8Sys::builder()
+            .platform(Platform::Chip8X)
+            .quirks(quirks)
+            .timer_mode(TimerMode::External)
+            .random_seed(9)
+            .build()
+            .unwrap();
+        chip8.reset();
+        assert_eq!(chip8.platform(), Platform::Chip8X);
+        assert_eq!(chip8.quirks(), quirks);
+        assert_eq!(chip8.timer_mode(), TimerMode::External);
+        assert_eq!(chip8.random_seed(), Some(9));
+    }
+
+    #[test]
+    // Tests that the ROM is loaded and the memory size, font and load address survive a reset
+    fn test_memory_settings() {
+        let font = Font::new(FontSet::Dream6800).at(0x700);
+        let mut chip8 = Chip8Sys::builder()
+            .memory_size(2048)
+            .font(font)
+            .load_address(0x400)
+            .rom(&[0x12, 0x34])
+            .build()
+            .unwrap();
+        assert_eq!(chip8.memory.fetch(0x400).unwrap(), 0x1234);
+        chip8.reset();
+        assert_eq!(chip8.memory.size(), 2048);
+        assert_eq!(chip8.font(), &font);
+        assert_eq!(chip8.memory[0x700], 0xE0);
+        assert_eq!(chip8.program_start(), 0x400);
+        assert_eq!(chip8.program_counter, 0x400);
+    }
+
+    #[test]
+    // Tests that a smaller memory mirrors the ROM past its end
+    fn test_memory_mirror() {
+        // 2KB of memory mirrors 0x200 at 0xA00
+        let mut chip8 = Chip8Sys::builder()
+            .memory_size(2048)
+            .rom(&[0x12, 0x34])
+            .build()
+            .unwrap();
+        assert_eq!(chip8.memory.fetch(0x0A00).unwrap(), 0x1234);
+    }
+
+    #[test]
+    // Tests that a memory map and an entry address inside the ROM are applied
+    fn test_entry_address() {
+        // ETI-660 ROMs load at 0x600, this one starts at its second instruction
+        let mut chip8 = Chip8Sys::builder()
+            .memory_map(MemoryMap::eti_660())
+            .entry_address(0x602)
+            .rom(&[0x12, 0x34])
+            .build()
+            .unwrap();
+        assert_eq!(chip8.memory.fetch(0x0600).unwrap(), 0x1234);
+        assert_eq!(chip8.program_counter, 0x602);
+    }
+
+    #[test]
+    // Tests that an entry address past memory is rejected
+    fn test_invalid_entry_address() {
+        assert!(matches!(
+            Chip8Sys::builder().entry_address(0x1000).build(),
+            Err(Chip8Error::InvalidLoadAddress(0x1000))
+        ));
+    }
+
+    #[test]
+    // Tests that a memory size other than a power of two is rejected
+    fn test_invalid_memory_size() {
+        assert!(matches!(
+            Chip8Sys::builder().memory_size(3000).build(),
+            Err(Chip8Error::InvalidMemorySize(3000))
+        ));
+    }
+
+    #[test]
+    // Tests that a load address over the font is rejected
+    fn test_load_address_over_font() {
+        assert!(matches!(
+            Chip8Sys::builder().load_address(0x060).build(),
+            Err(Chip8Error::InvalidLoadAddress(0x060))
+        ));
+    }
+
+    #[test]
+    // Tests that a load address past a smaller memory is rejected
+    fn test_load_address_past_memory() {
+        assert!(matches!(
+            Chip8Sys::builder()
+                .platform(Platform::Chip8X)
+                .memory_size(1024)
+                .load_address(0x400)
+                .build(),
+            Err(Chip8Error::InvalidLoadAddress(0x400))
+        ));
+    }
+
+    #[test]
+    // Tests that a ROM longer than the memory after the load address is rejected
+    fn test_rom_past_memory() {
+        let rom = [0; 0x700];
+        assert!(matches!(
+            Chip8Sys::builder().memory_size(2048).rom(&rom).build(),
+            Err(Chip8Error::RomTooLarge(0x700))
+        ));
+    }
+
+    #[test]
+    // Tests that a ROM that would run over a moved font is rejected
+    fn test_rom_over_font() {
+        let rom = [0; 0x700];
+        assert!(matches!(
+            Chip8Sys::builder()
+                .font(Font::default().at(0x300))
+                .rom(&rom)
+                .build(),
+            Err(Chip8Error::RomTooLarge(0x700))
+        ));
+    }
+
+    #[test]
+    // Tests that a font past a smaller memory is rejected
+    fn test_font_past_memory() {
+        assert!(matches!(
+            Chip8Sys::builder()
+                .memory_size(1024)
+                .font(Font::default().at(0x400))
+                .build(),
+            Err(Chip8Error::InvalidFontAddress(0x400))
+        ));
+    }
+}
@@ -0,0 +1,582 @@
+//! The memory bus.
+//!
+//! The interpreter reaches RAM only through `Bus::read`, `Bus::write` and `Bus::fetch`, which pass
+//! every access through the hooks attached to the bus. Hooks can watch the traffic, change the byte
+//! the CPU reads, drop or replace writes, or stop the CPU with an error. Indexing
+//! `Chip8Sys::memory` directly skips the hooks, which is what loaders, debuggers and tests want.
+//! Attaching hooks needs the `debugger` feature.
+
+use core::ops::{Deref, DerefMut, RangeInclusive};
+
+use crate::chip8error::Chip8Error;
+#[cfg(feature = "debugger")]
+use crate::coverage::Coverage;
+#[cfg(feature = "debugger")]
+use crate::guard::WriteGuard;
+
+/// This constant defines the size of the Chip-8 address space in bytes.
+pub const MEMORY_SIZE: usize = 4096;
+/// This constant defines the smallest memory size a bus can be given.
+pub const MIN_MEMORY_SIZE: usize = 1024;
+
+// Checks that a memory size is one a bus can be given
+pub(crate) fn check_size(size: usize) -> Result<(), Chip8Error> {
+    if !size.is_power_of_two() || !(MIN_MEMORY_SIZE..=MEMORY_SIZE).contains(&size) {
+        return Err(Chip8Error::InvalidMemorySize(size));
+    }
+    Ok(())
+}
+
+/// This enum lists the kinds of memory access the CPU makes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// A data read, by DXYN or FX65.
+    Read,
+    /// A data write, by FX33 or FX55.
+    Write,
+    /// An instruction fetch.
+    Execute,
+}
+
+/// This trait observes and intercepts the CPU's memory traffic.
+/// Every method has a default that lets the access through unchanged, so a hook only implements
+/// the accesses it cares about. Returning an error stops `Chip8Sys::run` with that error before
+/// the instruction takes effect: the program counter, registers, timers, display, RAM, coverage
+/// counters and write guard are left as they were, so running again retries the instruction and
+/// calls the hooks again. Hooks may already have seen the instruction's earlier accesses, so a
+/// hook that keeps its own counts sees a retried instruction twice, and a multi-byte write is
+/// only stored once every byte has passed the hooks.
+pub trait MemoryHook {
+    /// This function is called when the CPU reads a data byte.
+    /// Arguments:
+    /// - address: The address being read.
+    /// - value: The byte in RAM, or the value returned by an earlier hook.
+    ///
+    /// Returns: The byte the CPU sees.
+    fn read(&mut self, address: u16, value: u8) -> Result<u8, Chip8Error> {
+        let _ = address;
+        Ok(value)
+    }
+
+    /// This function is called before the CPU writes a data byte.
+    /// Arguments:
+    /// - address: The address being written.
+    /// - old_value: The byte currently in RAM.
+    /// - value: The byte being written, or the value returned by an earlier hook.
+    ///
+    /// Returns: The byte to store, or None to drop the write.
+    fn write(&mut self, address: u16, old_value: u8, value: u8) -> Result<Option<u8>, Chip8Error> {
+        let _ = (address, old_value);
+        Ok(Some(value))
+    }
+
+    /// This function is called when the CPU fetches an instruction, before it runs.
+    /// Arguments:
+    /// - address: The address of the instruction's first byte.
+    /// - opcode: The instruction word.
+    ///
+    /// Returns: Ok to run the instruction.
+    fn execute(&mut self, address: u16, opcode: u16) -> Result<(), Chip8Error> {
+        let _ = (address, opcode);
+        Ok(())
+    }
+}
+
+/// This struct stops the CPU when it touches a range of addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// This field stores the watched addresses.
+    pub addresses: RangeInclusive<u16>,
+    /// This field stores the kind of access that triggers the watchpoint.
+    pub access: Access,
+}
+
+impl Watchpoint {
+    /// This function builds a watchpoint.
+    /// Arguments:
+    /// - addresses: The watched addresses.
+    /// - access: The kind of access that triggers the watchpoint.
+    ///
+    /// Returns: The watchpoint, ready for `Bus::add_hook`.
+    pub fn new(addresses: RangeInclusive<u16>, access: Access) -> Watchpoint {
+        Watchpoint { addresses, access }
+    }
+
+    // Stops the CPU if the access is the one being watched
+    fn check(&self, access: Access, address: u16) -> Result<(), Chip8Error> {
+        if access == self.access && self.addresses.contains(&address) {
+            return Err(Chip8Error::Watchpoint(access, address));
+        }
+        Ok(())
+    }
+}
+
+impl MemoryHook for Watchpoint {
+    fn read(&mut self, address: u16, value: u8) -> Result<u8, Chip8Error> {
+        self.check(Access::Read, address)?;
+        Ok(value)
+    }
+
+    fn write(&mut self, address: u16, _old_value: u8, value: u8) -> Result<Option<u8>, Chip8Error> {
+        self.check(Access::Write, address)?;
+        Ok(Some(value))
+    }
+
+    fn execute(&mut self, address: u16, _opcode: u16) -> Result<(), Chip8Error> {
+        self.check(Access::Execute, address)
+    }
+}
+
+/// This struct identifies a hook attached to a bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HookId(usize);
+
+/// This struct is the Chip-8 RAM and the hooks that watch the CPU's access to it.
+/// It dereferences to the RAM array, so indexing it reads and writes RAM without running hooks.
+pub struct Bus {
+    // the 4KB of RAM
+    ram: [u8; MEMORY_SIZE],
+    // the installed memory size minus one, addresses past it wrap around
+    address_mask: u16,
+    // hooks run in the order they were added
+    #[cfg(feature = "debugger")]
+    hooks: Vec<(HookId, Box<dyn MemoryHook + Send>)>,
+    #[cfg(feature = "debugger")]
+    next_hook_id: usize,
+    // checks CPU writes for self-modifying code, see guard.rs
+    #[cfg(feature = "debugger")]
+    pub(crate) guard: WriteGuard,
+    // access counters, None while coverage is off
+    #[cfg(feature = "debugger")]
+    pub(crate) coverage: Option<Box<Coverage>>,
+    // set while a debugger run retries the instruction that stopped it, which lets that
+    // instruction past its watchpoints
+    #[cfg(feature = "debugger")]
+    pub(crate) is_resuming: bool,
+    // coverage counts of the running instruction, applied once it completes
+    #[cfg(feature = "debugger")]
+    pending_counts: Vec<(Access, u16)>,
+    #[cfg(feature = "debugger")]
+    is_instruction_open: bool,
+}
+
+impl Default for Bus {
+    /// This function returns a bus with cleared RAM and no hooks.
+    /// Arguments: none.
+    /// Returns: A new bus.
+    fn default() -> Self {
+        Bus::new()
+    }
+}
+
+impl Deref for Bus {
+    type Target = [u8; MEMORY_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.ram
+    }
+}
+
+impl DerefMut for Bus {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.ram
+    }
+}
+
+impl Bus {
+    /// This function builds a bus with cleared RAM and no hooks.
+    /// Arguments: none.
+    /// Returns: A new bus.
+    pub fn new() -> Bus {
+        Bus {
+            ram: [0; MEMORY_SIZE],
+            address_mask: (MEMORY_SIZE - 1) as u16,
+            #[cfg(feature = "debugger")]
+            hooks: Vec::new(),
+            #[cfg(feature = "debugger")]
+            next_hook_id: 0,
+            #[cfg(feature = "debugger")]
+            guard: WriteGuard::default(),
+            #[cfg(feature = "debugger")]
+            coverage: None,
+            #[cfg(feature = "debugger")]
+            is_resuming: false,
+            #[cfg(feature = "debugger")]
+            pending_counts: Vec::new(),
+            #[cfg(feature = "debugger")]
+            is_instruction_open: false,
+        }
+    }
+
+    /// This function returns the installed memory size.
+    /// Arguments: none.
+    /// Returns: The size in bytes, 4096 unless configured through `Chip8SysBuilder`.
+    pub fn size(&self) -> usize {
+        self.address_mask as usize + 1
+    }
+
+    // Installs less memory, like a 2KB COSMAC VIP; the CPU's addresses wrap at the new size
+    pub(crate) fn set_size(&mut self, size: usize) -> Result<(), Chip8Error> {
+        check_size(size)?;
+        self.address_mask = (size - 1) as u16;
+        Ok(())
+    }
+
+    /// This function attaches a hook, which sees every access after the hooks added before it.
+    /// Arguments:
+    /// - hook: The hook to attach.
+    ///
+    /// Returns: An id for `remove_hook`.
+    #[cfg(feature = "debugger")]
+    pub fn add_hook(&mut self, hook: impl MemoryHook + Send + 'static) -> HookId {
+        self.add_boxed_hook(Box::new(hook))
+    }
+
+    // Attaches a hook that is already boxed, as the builder keeps them
+    #[cfg(feature = "debugger")]
+    pub(crate) fn add_boxed_hook(&mut self, hook: Box<dyn MemoryHook + Send>) -> HookId {
+        let id = HookId(self.next_hook_id);
+        self.next_hook_id += 1;
+        self.hooks.push((id, hook));
+        id
+    }
+
+    /// This function detaches a hook.
+    /// Arguments:
+    /// - id: The id returned by `add_hook`.
+    ///
+    /// Returns: The hook, or None if it was already removed.
+    #[cfg(feature = "debugger")]
+    pub fn remove_hook(&mut self, id: HookId) -> Option<Box<dyn MemoryHook + Send>> {
+        let index = self.hooks.iter().position(|(hook_id, _)| *hook_id == id)?;
+        Some(self.hooks.remove(index).1)
+    }
+
+    /// This function detaches every hook.
+    /// Arguments: none.
+    /// Returns: The updated bus.
+    #[cfg(feature = "debugger")]
+    pub fn clear_hooks(&mut self) -> &mut Self {
+        self.hooks.clear();
+        self
+    }
+
+    /// This function returns the number of attached hooks.
+    /// Arguments: none.
+    /// Returns: The hook count.
+    #[cfg(feature = "debugger")]
+    pub fn hook_count(&self) -> usize {
+        self.hooks.len()
+    }
+
+    // Starts an instruction; its coverage counts and write guard marks only stick if it completes
+    pub(crate) fn begin_instruction(&mut self) {
+        #[cfg(feature = "debugger")]
+        {
+            self.is_instruction_open = true;
+            self.pending_counts.clear();
+            self.guard.begin_instruction();
+        }
+    }
+
+    // Ends the instruction started by begin_instruction, dropping its bookkeeping if it failed
+    pub(crate) fn end_instruction(&mut self, is_completed: bool) {
+        #[cfg(feature = "debugger")]
+        {
+            self.is_instruction_open = false;
+            self.guard.end_instruction(is_completed);
+            if let Some(coverage) = self.coverage.as_mut().filter(|_| is_completed) {
+                for &(access, address) in &self.pending_counts {
+                    coverage.count(access, address);
+                }
+            }
+        }
+        #[cfg(not(feature = "debugger"))]
+        let _ = is_completed;
+    }
+
+    // Counts an access for coverage, held back until the running instruction completes
+    #[cfg(feature = "debugger")]
+    fn count(&mut self, access: Access, address: u16) {
+        if let Some(coverage) = &mut self.coverage {
+            if self.is_instruction_open {
+                self.pending_counts.push((access, address));
+            } else {
+                coverage.count(access, address);
+            }
+        }
+    }
+
+    /// This function reads a data byte on behalf of the CPU.
+    /// Arguments:
+    /// - address: The address to read, wrapped to the memory size.
+    ///
+    /// Returns: The byte after every hook has seen it, or the first hook error.
+    pub fn read(&mut self, address: u16) -> Result<u8, Chip8Error> {
+        let address = address & self.address_mask;
+        let value = self.ram[address as usize];
+        #[cfg(feature = "debugger")]
+        let is_resuming = self.is_resuming;
+        #[cfg(feature = "debugger")]
+        let value = self.hooks.iter_mut().try_fold(value, |value, (_, hook)| {
+            skip_watchpoint(hook.read(address, value), value, is_resuming)
+        })?;
+        #[cfg(feature = "debugger")]
+        self.count(Access::Read, address);
+        Ok(value)
+    }
+
+    // Reads consecutive data bytes, as DXYN and FX65 do
+    pub(crate) fn read_bytes(&mut self, address: u16, values: &mut [u8]) -> Result<(), Chip8Error> {
+        for (offset, value) in values.iter_mut().enumerate() {
+            *value = self.read(address.wrapping_add(offset as u16))?;
+        }
+        Ok(())
+    }
+
+    /// This function writes a data byte on behalf of the CPU.
+    /// Arguments:
+    /// - address: The address to write, wrapped to the memory size.
+    /// - value: The byte to write.
+    ///
+    /// Returns: Ok once the byte was stored or dropped, or the first hook or write guard error.
+    pub fn write(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        self.write_bytes(address, &[value])
+    }
+
+    // Writes up to 16 consecutive data bytes, as FX33 and FX55 do. Every byte passes the hooks
+    // and the write guard before any is stored, so an error leaves RAM unchanged.
+    pub(crate) fn write_bytes(&mut self, address: u16, values: &[u8]) -> Result<(), Chip8Error> {
+        let mut filtered = [None; 16];
+        for (offset, (&value, slot)) in values.iter().zip(&mut filtered).enumerate() {
+            let address = address.wrapping_add(offset as u16) & self.address_mask;
+            #[cfg(feature = "debugger")]
+            let value = self.filter_write(address, value)?;
+            #[cfg(not(feature = "debugger"))]
+            let value = Some(value);
+            *slot = Some((address, value));
+        }
+        for (address, value) in filtered.into_iter().flatten() {
+            #[cfg(feature = "debugger")]
+            self.count(Access::Write, address);
+            if let Some(value) = value {
+                self.ram[address as usize] = value;
+            }
+        }
+        Ok(())
+    }
+
+    // Passes a write through the hooks and the write guard, None when dropped
+    #[cfg(feature = "debugger")]
+    fn filter_write(&mut self, address: u16, value: u8) -> Result<Option<u8>, Chip8Error> {
+        let old_value = self.ram[address as usize];
+        let mut value = Some(value);
+        for (_, hook) in &mut self.hooks {
+            let Some(byte) = value else {
+                break;
+            };
+            value = skip_watchpoint(
+                hook.write(address, old_value, byte),
+                Some(byte),
+                self.is_resuming,
+            )?;
+        }
+        match value {
+            Some(value) if self.guard.check_write(address, value)? => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    /// This function fetches an instruction word on behalf of the CPU.
+    /// Fetches are reported to the hooks as executes, not as reads.
+    /// Arguments:
+    /// - address: The address of the instruction, wrapped to the memory size.
+    ///
+    /// Returns: The big-endian instruction word, or the first hook error.
+    pub fn fetch(&mut self, address: u16) -> Result<u16, Chip8Error> {
+        let address = address & self.address_mask;
+        let opcode = u16::from_be_bytes([
+            self.ram[address as usize],
+            self.ram[((address + 1) & self.address_mask) as usize],
+        ]);
+        #[cfg(feature = "debugger")]
+        for (_, hook) in &mut self.hooks {
+            skip_watchpoint(hook.execute(address, opcode), (), self.is_resuming)?;
+        }
+        #[cfg(feature = "debugger")]
+        self.guard.mark_executed(address);
+        #[cfg(feature = "debugger")]
+        self.count(Access::Execute, address);
+        Ok(opcode)
+    }
+
+    // Moves another bus's hooks, guard settings and coverage onto this one so they survive a reset
+    #[cfg(feature = "debugger")]
+    pub(crate) fn take_settings_from(&mut self, other: &mut Bus) {
+        self.hooks = core::mem::take(&mut other.hooks);
+        self.next_hook_id = other.next_hook_id;
+        self.guard = other.guard.settings();
+        self.coverage = other.coverage.take();
+    }
+}
+
+// Lets an access through when its only objection is a watchpoint being resumed from
+#[cfg(feature = "debugger")]
+fn skip_watchpoint<T>(
+    result: Result<T, Chip8Error>,
+    value: T,
+    is_resuming: bool,
+) -> Result<T, Chip8Error> {
+    match result {
+        Err(Chip8Error::Watchpoint(..)) if is_resuming => Ok(value),
+        result => result,
+    }
+}
+
+#[cfg(all(test, feature = "debugger"))]
+mod test {
+    use super::*;
+    use crate::chip8::Chip8Sys;
+    use crate::decode::test::single_instruction_chip_8;
+
+    // Records accesses and drops writes to the program area
+    struct Recorder {
+        accesses: std::sync::Arc<std::sync::Mutex<Vec<(Access, u16)>>>,
+    }
+
+    impl MemoryHook for Recorder {
+        fn read(&mut self, address: u16, value: u8) -> Result<u8, Chip8Error> {
+            self.accesses.lock().unwrap().push((Access::Read, address));
+            Ok(value)
+        }
+
+        fn write(
+            &mut self,
+            address: u16,
+            _old_value: u8,
+            value: u8,
+        ) -> Result<Option<u8>, Chip8Error> {
+            self.accesses.lock().unwrap().push((Access::Write, address));
+            Ok((address < 0x200).then_some(value))
+        }
+
+        fn execute(&mut self, address: u16, _opcode: u16) -> Result<(), Chip8Error> {
+            self.accesses
+                .lock()
+                .unwrap()
+                .push((Access::Execute, address));
+            Ok(())
+        }
+    }
+
+    #[test]
+    // Tests that hooks see fetches, reads and writes and can drop writes
+    fn test_hooks_see_traffic() {
+        // A100 F133 A202 F055: store V1 as BCD at 0x100, then try to overwrite this program
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0xA1, 0x00, 0xF1, 0x33, 0xA2, 0x02, 0xF0, 0x55]);
+        chip8.register[1] = 123;
+        let accesses = std::sync::Arc::default();
+        chip8.memory.add_hook(Recorder {
+            accesses: std::sync::Arc::clone(&accesses),
+        });
+        chip8.tick(4).unwrap();
+        assert_eq!(chip8.memory[0x100..0x103], [1, 2, 3]);
+        assert_eq!(
+            chip8.memory[0x202], 0xF1,
+            "the hook should have dropped the write"
+        );
+        assert_eq!(
+            *accesses.lock().unwrap(),
+            vec![
+                (Access::Execute, 0x200),
+                (Access::Execute, 0x202),
+                (Access::Write, 0x100),
+                (Access::Write, 0x101),
+                (Access::Write, 0x102),
+                (Access::Execute, 0x204),
+                (Access::Execute, 0x206),
+                (Access::Write, 0x202),
+            ]
+        );
+    }
+
+    #[test]
+    // Tests that a hook error leaves the CPU before the instruction, ready to run it again
+    fn test_hook_error_is_resumable() {
+        // F255: store V0-V2 at I, stopped by a watchpoint on the last byte
+        let mut chip8 = single_instruction_chip_8(0xF255);
+        chip8.register[..3].copy_from_slice(&[1, 2, 3]);
+        chip8.register_i = 0x300;
+        chip8.sound_timer = 5;
+        let id = chip8
+            .memory
+            .add_hook(Watchpoint::new(0x302..=0x302, Access::Write));
+        assert!(matches!(
+            chip8.run(),
+            Err(Chip8Error::Watchpoint(Access::Write, 0x302))
+        ));
+        assert_eq!(chip8.memory[0x300..0x303], [0, 0, 0]);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.register_i, 0x300);
+        assert_eq!(chip8.sound_timer, 5);
+        chip8.memory.remove_hook(id);
+        chip8.run().unwrap();
+        assert_eq!(chip8.memory[0x300..0x303], [1, 2, 3]);
+        assert_eq!(chip8.program_counter, 0x202);
+        assert_eq!(chip8.register_i, 0x303);
+        assert_eq!(chip8.sound_timer, 4);
+
+        // D015: a read stopped on the last sprite row leaves the display alone
+        let mut chip8 = single_instruction_chip_8(0xD015);
+        chip8.register_i = 0x050;
+        chip8
+            .memory
+            .add_hook(Watchpoint::new(0x054..=0x054, Access::Read));
+        assert!(chip8.run().is_err());
+        assert!(chip8.frame_buffer.iter().all(|&chunk| chunk == 0));
+        assert_eq!(chip8.program_counter, 0x200);
+    }
+
+    #[test]
+    // Tests that a stopped instruction leaves no coverage counts or guard marks behind
+    fn test_hook_error_leaves_no_bookkeeping() {
+        // F255: a watchpoint on the last byte stops the store after two bytes passed the guard
+        let mut chip8 = single_instruction_chip_8(0xF255);
+        chip8.register_i = 0x300;
+        chip8
+            .set_coverage(true)
+            .set_write_guard(crate::guard::WriteGuardMode::Warn);
+        chip8
+            .memory
+            .add_hook(Watchpoint::new(0x302..=0x302, Access::Write));
+        assert!(chip8.run().is_err());
+        let coverage = chip8.coverage().unwrap();
+        assert_eq!(coverage.executions()[0x200], 0);
+        assert!(
+            coverage.writes()[0x300..0x303]
+                .iter()
+                .all(|&count| count == 0)
+        );
+        assert!(!chip8.is_executed(0x200));
+        assert!(!chip8.is_written(0x300));
+    }
+
+    #[test]
+    // Tests that a watchpoint stops the CPU, survives a reset and can be removed
+    fn test_watchpoint() {
+        let mut chip8 = single_instruction_chip_8(0xF065);
+        chip8.register_i = 0x300;
+        let id = chip8
+            .memory
+            .add_hook(Watchpoint::new(0x300..=0x30F, Access::Read));
+        assert!(matches!(
+            chip8.run(),
+            Err(Chip8Error::Watchpoint(Access::Read, 0x300))
+        ));
+        chip8.reset();
+        assert_eq!(chip8.memory.hook_count(), 1);
+        assert!(chip8.memory.remove_hook(id).is_some());
+        assert!(chip8.memory.remove_hook(id).is_none());
+    }
+}
@@ -0,0 +1,682 @@
+//! Cheats: RAM search, frozen values and ROM patches.
+//!
+//! `RamSearch` narrows RAM and the V registers down to the few places a game keeps, say, its lives,
+//! by comparing snapshots between filters. A `Cheat` then freezes such a place to a value every
+//! frame, or patches the ROM image each time it is loaded. Cheat lists are kept as text:
+//!
+//! ```text
+//! # Space Invaders
+//! [Infinite lives]
+//! freeze 3F0 = 03
+//! freeze V5 = 03
+//! [Skip title] off
+//! patch 2A4 = 12 B0
+//! ```
+//!
+//! Addresses and values are hex. A header followed by `off` loads the cheat disabled.
+
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use crate::bus::MEMORY_SIZE;
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+
+/// This enum names a byte a cheat or search can look at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CheatTarget {
+    /// A RAM address.
+    Memory(u16),
+    /// A V register, 0x0-0xF.
+    Register(u8),
+}
+
+impl CheatTarget {
+    /// This function reads the target's current value.
+    /// Addresses wrap at the installed memory size, like the CPU's.
+    /// Arguments:
+    /// - chip8: The Chip-8 system to read.
+    ///
+    /// Returns: The byte.
+    pub fn read(self, chip8: &Chip8Sys) -> u8 {
+        match self {
+            CheatTarget::Memory(address) => chip8.memory[memory_index(chip8, address as usize)],
+            CheatTarget::Register(x) => chip8.register[x as usize & 0xF],
+        }
+    }
+
+    /// This function overwrites the target, bypassing memory hooks.
+    /// Addresses wrap at the installed memory size, like the CPU's.
+    /// Arguments:
+    /// - chip8: The Chip-8 system to change.
+    /// - value: The byte to write.
+    ///
+    /// Returns: none.
+    pub fn write(self, chip8: &mut Chip8Sys, value: u8) {
+        match self {
+            CheatTarget::Memory(address) => {
+                let index = memory_index(chip8, address as usize);
+                chip8.memory[index] = value;
+            }
+            CheatTarget::Register(x) => chip8.register[x as usize & 0xF] = value,
+        }
+    }
+}
+
+impl fmt::Display for CheatTarget {
+    /// This function formats the target as in cheat lists, `3F0` or `V5`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheatTarget::Memory(address) => write!(f, "{address:03X}"),
+            CheatTarget::Register(x) => write!(f, "V{x:X}"),
+        }
+    }
+}
+
+impl FromStr for CheatTarget {
+    type Err = Chip8Error;
+
+    /// This function parses a target written as `3F0`, `0x3F0` or `V5`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || Chip8Error::InvalidCheat(format!("{text} is not an address or register"));
+        let text = text.trim();
+        if let Some(register) = text.strip_prefix(['V', 'v']) {
+            return u8::from_str_radix(register, 16)
+                .ok()
+                .filter(|x| *x <= 0xF)
+                .map(CheatTarget::Register)
+                .ok_or_else(invalid);
+        }
+        parse_address(text)
+            .map(CheatTarget::Memory)
+            .ok_or_else(invalid)
+    }
+}
+
+// Wraps an address to the installed memory, see Bus::size
+fn memory_index(chip8: &Chip8Sys, address: usize) -> usize {
+    address & (chip8.memory.size() - 1)
+}
+
+// Parses a hex address below 0x1000, with or without 0x
+fn parse_address(text: &str) -> Option<u16> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    u16::from_str_radix(digits, 16)
+        .ok()
+        .filter(|address| (*address as usize) < MEMORY_SIZE)
+}
+
+/// This enum lists what a cheat does.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CheatEffect {
+    /// Writes the value to the target at the end of every frame.
+    Freeze { target: CheatTarget, value: u8 },
+    /// Writes the bytes over the ROM image each time a ROM is loaded.
+    Patch { address: u16, bytes: Vec<u8> },
+}
+
+/// This struct is a named group of effects that is switched on and off together.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cheat {
+    /// This field stores the name shown to players.
+    pub name: String,
+    /// This field controls whether the cheat is applied.
+    pub is_enabled: bool,
+    /// This field stores the effects.
+    pub effects: Vec<CheatEffect>,
+}
+
+impl Cheat {
+    /// This function builds an enabled cheat that freezes one target.
+    /// Arguments:
+    /// - name: The cheat's name.
+    /// - target: The byte to freeze.
+    /// - value: The value to hold it at.
+    ///
+    /// Returns: The cheat.
+    pub fn freeze(name: &str, target: CheatTarget, value: u8) -> Cheat {
+        Cheat {
+            name: name.to_owned(),
+            is_enabled: true,
+            effects: vec![CheatEffect::Freeze { target, value }],
+        }
+    }
+}
+
+/// This struct is a list of cheats, as read from and written to the text format.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CheatList {
+    /// This field stores the cheats in file order.
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    /// This function parses a cheat list, see the module docs for the format.
+    /// Arguments:
+    /// - text: The cheat list.
+    ///
+    /// Returns: The cheats, or InvalidCheat naming the first bad line.
+    pub fn parse(text: &str) -> Result<CheatList, Chip8Error> {
+        let mut list = CheatList::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error =
+                |message: &str| Chip8Error::InvalidCheat(format!("line {}: {message}", index + 1));
+            if let Some(header) = line.strip_prefix('[') {
+                let (name, rest) = header
+                    .split_once(']')
+                    .ok_or_else(|| error("expected ] after the cheat name"))?;
+                let is_enabled = match rest.trim() {
+                    "" => true,
+                    "off" => false,
+                    _ => return Err(error("expected nothing or off after the cheat name")),
+                };
+                list.cheats.push(Cheat {
+                    name: name.trim().to_owned(),
+                    is_enabled,
+                    effects: Vec::new(),
+                });
+                continue;
+            }
+            let cheat = list
+                .cheats
+                .last_mut()
+                .ok_or_else(|| error("expected a [name] before the first effect"))?;
+            let (kind, rest) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| error("expected freeze or patch"))?;
+            let (target, values) = rest
+                .split_once('=')
+                .ok_or_else(|| error("expected = between the target and the value"))?;
+            let bytes = values
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| error("expected hex bytes after ="))?;
+            let effect = match (kind, bytes.as_slice()) {
+                ("freeze", &[value]) => CheatEffect::Freeze {
+                    target: target
+                        .parse()
+                        .map_err(|_| error("expected an address or VX"))?,
+                    value,
+                },
+                ("freeze", _) => return Err(error("freeze takes a single byte")),
+                ("patch", []) => return Err(error("patch needs at least one byte")),
+                ("patch", _) => CheatEffect::Patch {
+                    address: parse_address(target.trim())
+                        .ok_or_else(|| error("expected a hex address"))?,
+                    bytes,
+                },
+                _ => return Err(error("expected freeze or patch")),
+            };
+            cheat.effects.push(effect);
+        }
+        Ok(list)
+    }
+
+    /// This function writes the cheats in the text format.
+    /// Arguments: none.
+    /// Returns: The cheat list text.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for cheat in &self.cheats {
+            let state = if cheat.is_enabled { "" } else { " off" };
+            let _ = writeln!(text, "[{}]{state}", cheat.name);
+            for effect in &cheat.effects {
+                match effect {
+                    CheatEffect::Freeze { target, value } => {
+                        let _ = writeln!(text, "freeze {target} = {value:02X}");
+                    }
+                    CheatEffect::Patch { address, bytes } => {
+                        let _ = write!(text, "patch {address:03X} =");
+                        for byte in bytes {
+                            let _ = write!(text, " {byte:02X}");
+                        }
+                        text.push('\n');
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+/// This enum lists the comparisons a RAM search can filter by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SearchCondition {
+    /// The value equals the given byte.
+    Equal(u8),
+    /// The value differs from the given byte.
+    NotEqual(u8),
+    /// The value changed since the last filter.
+    Changed,
+    /// The value stayed the same since the last filter.
+    Unchanged,
+    /// The value grew since the last filter.
+    Increased,
+    /// The value shrank since the last filter.
+    Decreased,
+}
+
+impl SearchCondition {
+    // Tests a candidate's previous and current value
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            SearchCondition::Equal(value) => current == value,
+            SearchCondition::NotEqual(value) => current != value,
+            SearchCondition::Changed => current != previous,
+            SearchCondition::Unchanged => current == previous,
+            SearchCondition::Increased => current > previous,
+            SearchCondition::Decreased => current < previous,
+        }
+    }
+}
+
+/// This struct narrows RAM and the V registers down by comparing snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RamSearch {
+    // the remaining targets and their value at the last snapshot
+    candidates: Vec<(CheatTarget, u8)>,
+}
+
+impl RamSearch {
+    /// This function starts a search with every installed address and every register as a
+    /// candidate.
+    /// Arguments:
+    /// - chip8: The Chip-8 system to snapshot.
+    ///
+    /// Returns: The search.
+    pub fn new(chip8: &Chip8Sys) -> RamSearch {
+        let candidates = (0..chip8.memory.size() as u16)
+            .map(CheatTarget::Memory)
+            .chain((0..16).map(CheatTarget::Register))
+            .map(|target| (target, target.read(chip8)))
+            .collect();
+        RamSearch { candidates }
+    }
+
+    /// This function keeps the candidates that match a condition and takes a new snapshot.
+    /// Arguments:
+    /// - chip8: The Chip-8 system to compare against the last snapshot.
+    /// - condition: The comparison to keep candidates by.
+    ///
+    /// Returns: The number of candidates left.
+    pub fn filter(&mut self, chip8: &Chip8Sys, condition: SearchCondition) -> usize {
+        self.candidates.retain_mut(|(target, previous)| {
+            let current = target.read(chip8);
+            let is_match = condition.matches(*previous, current);
+            *previous = current;
+            is_match
+        });
+        self.candidates.len()
+    }
+
+    /// This function returns the remaining candidates.
+    /// Arguments: none.
+    /// Returns: Each target with its value at the last snapshot, addresses first.
+    pub fn candidates(&self) -> &[(CheatTarget, u8)] {
+        &self.candidates
+    }
+}
+
+impl Chip8Sys {
+    /// This function adds a cheat, replacing any cheat with the same name.
+    /// Patches are applied straight away and again on every ROM load.
+    /// Arguments:
+    /// - cheat: The cheat to add.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn add_cheat(&mut self, cheat: Cheat) -> &mut Self {
+        self.remove_cheat(&cheat.name);
+        self.cheats.push(cheat);
+        self.apply_patches();
+        self
+    }
+
+    /// This function removes a cheat. Bytes it patched stay patched until the ROM is reloaded.
+    /// Arguments:
+    /// - name: The cheat's name.
+    ///
+    /// Returns: The cheat, or None if there is no cheat by that name.
+    pub fn remove_cheat(&mut self, name: &str) -> Option<Cheat> {
+        let index = self.cheats.iter().position(|cheat| cheat.name == name)?;
+        Some(self.cheats.remove(index))
+    }
+
+    /// This function switches a cheat on or off.
+    /// Arguments:
+    /// - name: The cheat's name.
+    /// - is_enabled: True to apply the cheat.
+    ///
+    /// Returns: True if a cheat by that name exists.
+    pub fn set_cheat_enabled(&mut self, name: &str, is_enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.iter_mut().find(|cheat| cheat.name == name) else {
+            return false;
+        };
+        cheat.is_enabled = is_enabled;
+        self.apply_patches();
+        true
+    }
+
+    /// This function returns the cheats.
+    /// Arguments: none.
+    /// Returns: The cheats in the order they were added.
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// This function replaces every cheat with a cheat list.
+    /// Arguments:
+    /// - list: The new cheats.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_cheats(&mut self, list: CheatList) -> &mut Self {
+        self.cheats = list.cheats;
+        self.apply_patches();
+        self
+    }
+
+    /// This function returns the cheats as a list for saving.
+    /// Arguments: none.
+    /// Returns: The cheat list.
+    pub fn cheat_list(&self) -> CheatList {
+        CheatList {
+            cheats: self.cheats.clone(),
+        }
+    }
+
+    /// This function writes every frozen value now. It also runs at the end of every frame.
+    /// Arguments: none.
+    /// Returns: The updated Chip-8 system.
+    pub fn apply_cheats(&mut self) -> &mut Self {
+        let freezes: Vec<(CheatTarget, u8)> = self
+            .enabled_effects()
+            .filter_map(|effect| match effect {
+                CheatEffect::Freeze { target, value } => Some((*target, *value)),
+                CheatEffect::Patch { .. } => None,
+            })
+            .collect();
+        for (target, value) in freezes {
+            target.write(self, value);
+        }
+        self
+    }
+
+    // Writes the enabled patches over the ROM image, called on every ROM load
+    pub(crate) fn apply_patches(&mut self) {
+        let patches: Vec<(u16, Vec<u8>)> = self
+            .enabled_effects()
+            .filter_map(|effect| match effect {
+                CheatEffect::Patch { address, bytes } => Some((*address, bytes.clone())),
+                CheatEffect::Freeze { .. } => None,
+            })
+            .collect();
+        for (address, bytes) in patches {
+            for (offset, byte) in bytes.into_iter().enumerate() {
+                let index = memory_index(self, address as usize + offset);
+                self.memory[index] = byte;
+            }
+        }
+    }
+
+    // The effects of every enabled cheat, in order
+    fn enabled_effects(&self) -> impl Iterator<Item = &CheatEffect> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.is_enabled)
+            .flat_map(|cheat| &cheat.effects)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that a RAM search finds a counter a ROM keeps decrementing
+    fn test_ram_search() {
+        // 6A03 A300 FA55 7AFF 1204: keep lives in VA and 0x300, losing one each loop
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x6A, 0x03, 0xA3, 0x00, 0xFA, 0x55, 0x7A, 0xFF, 0x12, 0x04]);
+        chip8.register_i = 0x300;
+        chip8.tick(3).unwrap();
+        let mut search = RamSearch::new(&chip8);
+        assert_eq!(search.filter(&chip8, SearchCondition::Unchanged), 4096 + 16);
+        chip8.tick(3).unwrap();
+        search.filter(&chip8, SearchCondition::Decreased);
+        chip8.tick(3).unwrap();
+        search.filter(&chip8, SearchCondition::Decreased);
+        let targets: Vec<CheatTarget> = search
+            .candidates()
+            .iter()
+            .map(|(target, _)| *target)
+            .collect();
+        assert!(targets.contains(&CheatTarget::Register(0xA)));
+        assert!(targets.iter().all(|target| matches!(
+            target,
+            CheatTarget::Register(0xA) | CheatTarget::Memory(0x300..=0x30A)
+        )));
+        assert_eq!(search.filter(&chip8, SearchCondition::Equal(0xFF)), 0);
+    }
+
+    #[test]
+    // Tests each search condition against a previous and a current value
+    fn test_search_conditions() {
+        let conditions = [
+            (SearchCondition::Equal(5), [true, false, false]),
+            (SearchCondition::NotEqual(5), [false, true, true]),
+            (SearchCondition::Changed, [true, true, false]),
+            (SearchCondition::Unchanged, [false, false, true]),
+            (SearchCondition::Increased, [true, false, false]),
+            (SearchCondition::Decreased, [false, true, false]),
+        ];
+        // 4 -> 5, 5 -> 4 and 4 -> 4
+        for (condition, expected) in conditions {
+            let results = [(4, 5), (5, 4), (4, 4)].map(|(a, b)| condition.matches(a, b));
+            assert_eq!(results, expected, "{condition:?}");
+        }
+    }
+
+    #[test]
+    // Tests that a search on a smaller installed memory only covers that memory
+    fn test_small_memory_search() {
+        let chip8 = Chip8Sys::builder().memory_size(2048).build().unwrap();
+        let search = RamSearch::new(&chip8);
+        assert_eq!(search.candidates().len(), 2048 + 16);
+        assert!(search.candidates().iter().all(|(target, _)| match target {
+            CheatTarget::Memory(address) => *address < 0x800,
+            CheatTarget::Register(_) => true,
+        }));
+    }
+
+    #[test]
+    // Tests that freezes and patches wrap at a smaller installed memory like the CPU does
+    fn test_small_memory_wrap() {
+        let mut chip8 = Chip8Sys::builder().memory_size(2048).build().unwrap();
+        // 0x900 is 0x100 to the CPU
+        CheatTarget::Memory(0x900).write(&mut chip8, 0x42);
+        assert_eq!(chip8.memory[0x100], 0x42);
+        assert_eq!(chip8.memory[0x900], 0);
+        assert_eq!(CheatTarget::Memory(0x900).read(&chip8), 0x42);
+        chip8.add_cheat(Cheat {
+            name: "Patch".to_owned(),
+            is_enabled: true,
+            effects: vec![CheatEffect::Patch {
+                address: 0x7FF,
+                bytes: vec![0xAA, 0xBB],
+            }],
+        });
+        assert_eq!(chip8.memory[0x7FF], 0xAA);
+        assert_eq!(chip8.memory[0x000], 0xBB);
+    }
+
+    // Two cheats: a freeze of a register and an address, and a patch loaded disabled
+    const CHEATS: &str =
+        "# test\n[Lives]\nfreeze VA = 03\nfreeze 300 = 09\n[Patch] off\npatch 0x202 = A3 10\n";
+
+    #[test]
+    // Tests that headers, effects, comments and the off flag are parsed
+    fn test_parse_cheat_list() {
+        let list = CheatList::parse(CHEATS).unwrap();
+        assert_eq!(
+            list.cheats,
+            [
+                Cheat {
+                    name: "Lives".to_owned(),
+                    is_enabled: true,
+                    effects: vec![
+                        CheatEffect::Freeze {
+                            target: CheatTarget::Register(0xA),
+                            value: 3,
+                        },
+                        CheatEffect::Freeze {
+                            target: CheatTarget::Memory(0x300),
+                            value: 9,
+                        },
+                    ],
+                },
+                Cheat {
+                    name: "Patch".to_owned(),
+                    is_enabled: false,
+                    effects: vec![CheatEffect::Patch {
+                        address: 0x202,
+                        bytes: vec![0xA3, 0x10],
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    // Tests that a cheat list survives being written and parsed again
+    fn test_cheat_list_round_trip() {
+        let list = CheatList::parse(CHEATS).unwrap();
+        let text = list.to_text();
+        assert_eq!(
+            text,
+            "[Lives]\nfreeze VA = 03\nfreeze 300 = 09\n[Patch] off\npatch 202 = A3 10\n"
+        );
+        assert_eq!(CheatList::parse(&text).unwrap(), list);
+    }
+
+    #[test]
+    // Tests that each kind of bad line is reported with its line number
+    fn test_invalid_cheat_lines() {
+        for (text, message) in [
+            (
+                "freeze V0 = 01",
+                "line 1: expected a [name] before the first effect",
+            ),
+            (
+                "[x\nfreeze V0 = 01",
+                "line 1: expected ] after the cheat name",
+            ),
+            (
+                "[x] on",
+                "line 1: expected nothing or off after the cheat name",
+            ),
+            ("[x]\nfreeze", "line 2: expected freeze or patch"),
+            ("[x]\nthaw V0 = 01", "line 2: expected freeze or patch"),
+            (
+                "[x]\nfreeze V0 01",
+                "line 2: expected = between the target and the value",
+            ),
+            ("[x]\nfreeze V0 = 1G", "line 2: expected hex bytes after ="),
+            (
+                "[x]\nfreeze V0 = 01 02",
+                "line 2: freeze takes a single byte",
+            ),
+            ("[x]\n\nfreeze VG = 01", "line 3: expected an address or VX"),
+            ("[x]\nfreeze 1000 = 01", "line 2: expected an address or VX"),
+            ("[x]\npatch 200 =", "line 2: patch needs at least one byte"),
+            ("[x]\npatch V0 = 01", "line 2: expected a hex address"),
+        ] {
+            assert!(
+                matches!(
+                    CheatList::parse(text),
+                    Err(Chip8Error::InvalidCheat(error)) if error == message
+                ),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    // Tests the target syntax in both directions
+    fn test_targets() {
+        assert_eq!(
+            "V5".parse::<CheatTarget>().unwrap(),
+            CheatTarget::Register(5)
+        );
+        assert_eq!(
+            "vf".parse::<CheatTarget>().unwrap(),
+            CheatTarget::Register(0xF)
+        );
+        assert_eq!(
+            "0x3F0".parse::<CheatTarget>().unwrap(),
+            CheatTarget::Memory(0x3F0)
+        );
+        assert_eq!(
+            " 3f0 ".parse::<CheatTarget>().unwrap(),
+            CheatTarget::Memory(0x3F0)
+        );
+        assert!("V10".parse::<CheatTarget>().is_err());
+        assert!("1000".parse::<CheatTarget>().is_err());
+        assert_eq!(CheatTarget::Memory(0x50).to_string(), "050");
+        assert_eq!(CheatTarget::Register(0xA).to_string(), "VA");
+    }
+
+    #[test]
+    // Tests that patches only apply while enabled and are applied again on every ROM load
+    fn test_patches() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_cheats(CheatList::parse(CHEATS).unwrap());
+        let rom = [0x6A, 0x00, 0xA3, 0x00, 0x12, 0x04];
+        chip8.load_rom_bytes(&rom);
+        assert_eq!(
+            chip8.memory[0x203], 0x00,
+            "a disabled patch should not apply"
+        );
+        assert!(chip8.set_cheat_enabled("Patch", true));
+        assert_eq!(chip8.memory[0x203], 0x10);
+        chip8.load_rom_bytes(&rom);
+        assert_eq!(chip8.memory[0x203], 0x10);
+    }
+
+    #[test]
+    // Tests that freezes are written at the end of every frame
+    fn test_freezes() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_cheats(CheatList::parse(CHEATS).unwrap());
+        // 6A00 1202: clear VA and halt
+        chip8.load_rom_bytes(&[0x6A, 0x00, 0x12, 0x02]);
+        chip8.tick(1).unwrap();
+        assert_eq!(chip8.register[0xA], 0);
+        chip8.tick_timers(1);
+        assert_eq!(chip8.register[0xA], 3);
+        assert_eq!(chip8.memory[0x300], 9);
+        assert!(chip8.set_cheat_enabled("Lives", false));
+        chip8.register[0xA] = 0;
+        chip8.tick_timers(1);
+        assert_eq!(chip8.register[0xA], 0);
+    }
+
+    #[test]
+    // Tests that cheats survive a reset, replace cheats of the same name and can be removed
+    fn test_cheat_management() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_cheats(CheatList::parse(CHEATS).unwrap());
+        chip8.reset();
+        assert_eq!(chip8.cheats().len(), 2);
+        chip8.add_cheat(Cheat::freeze("Lives", CheatTarget::Register(1), 7));
+        assert_eq!(chip8.cheats().len(), 2);
+        assert_eq!(chip8.cheats()[1].name, "Lives");
+        assert_eq!(chip8.cheat_list().cheats, chip8.cheats());
+        assert!(chip8.remove_cheat("Lives").is_some());
+        assert!(chip8.remove_cheat("Lives").is_none());
+        assert!(!chip8.set_cheat_enabled("Lives", true));
+    }
+}
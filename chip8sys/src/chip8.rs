@@ -1,14 +1,27 @@
-use core::panic;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::Read;
 
+use crate::builder::Chip8SysBuilder;
+use crate::bus::Bus;
+#[cfg(feature = "debugger")]
+use crate::cheat::Cheat;
 use crate::chip8error::Chip8Error;
+use crate::font::Font;
+use crate::halt::LoopDetector;
+use crate::platform::{
+    CHIP8X_COLOR_COLUMNS, CHIP8X_COLOR_ROWS, CHIP8X_DEFAULT_FOREGROUND, Platform,
+};
+#[cfg(feature = "debugger")]
+use crate::profile::Profiler;
+use crate::random::Rng;
+use crate::timing::{TimingModel, VipClock};
 
-const EMPTY_MEMORY: [u8; 4096] = [0; 4096];
 const EMPTY_REGISTER: [u8; 16] = [0; 16];
 const EMPTY_STACK: [u16; 16] = [0; 16];
-/// This constant defines the start address for program memory.
-const PROGRAM_START: usize = 0x200;
+const DEFAULT_COLOR_MAP: [u8; CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS] =
+    [CHIP8X_DEFAULT_FOREGROUND; CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS];
 
 /// This constant defines the Chip-8 display width in pixels.
 pub const DISPLAY_WIDTH: usize = 64;
@@ -18,6 +31,10 @@ pub const DISPLAY_HEIGHT: usize = 32;
 pub const DISPLAY_PIXELS: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT;
 /// This constant defines the packed framebuffer length in bytes.
 pub const FRAMEBUFFER_PACKED_LEN: usize = DISPLAY_PIXELS / 8;
+/// This constant defines the largest display height of any supported platform.
+pub const MAX_DISPLAY_HEIGHT: usize = 64;
+/// This constant defines the packed framebuffer length of the largest supported display.
+pub const FRAMEBUFFER_MAX_LEN: usize = DISPLAY_WIDTH * MAX_DISPLAY_HEIGHT / 8;
 
 /// This type represents the 16-key Chip-8 keypad as a bitmask.
 pub type Chip8KeyMask = u16;
@@ -42,6 +59,9 @@ pub struct Chip8Quirks {
     pub wrap_draw: bool,
     /// This field controls whether shift instructions modify VX in place.
     pub shift_uses_vx: bool,
+    /// This field controls whether FX0A waits for the key to be released, like the COSMAC VIP,
+    /// or completes as soon as it is pressed.
+    pub wait_for_key_release: bool,
 }
 
 impl Default for Chip8Quirks {
@@ -54,34 +74,27 @@ impl Default for Chip8Quirks {
             reset_vf_on_logic: true,
             wrap_draw: false,
             shift_uses_vx: false,
+            wait_for_key_release: false,
         }
     }
 }
 
-// This is the built in Chip-8 font that Roms expect to access
-const FONT: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0 loc 0x050
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1 loc 0x055
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2 loc 0x05A
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3 loc 0x05F
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4 loc 0x064
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5 loc 0x069
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6 loc 0x06E
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7 loc 0x073
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8 loc 0x078
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9 loc 0x07D
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A loc 0x082
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B loc 0x087
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C loc 0x08C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D loc 0x091
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E loc 0x096
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F loc 0x09B
-];
-const FONT_RANGE_MIN: u8 = 0x050;
-const FONT_RANGE_MAX: u8 = 0x0A0;
+impl Chip8Quirks {
+    /// This function returns the quirks of the original COSMAC VIP interpreter, whose FX0A
+    /// waits for the key to be released as well as pressed.
+    /// Arguments: none.
+    /// Returns: The COSMAC VIP quirk settings.
+    pub fn cosmac_vip() -> Chip8Quirks {
+        Chip8Quirks {
+            wait_for_key_release: true,
+            ..Chip8Quirks::default()
+        }
+    }
+}
 
 pub struct Chip8Sys {
-    pub memory: [u8; 4096],
+    // RAM, reached by the CPU through the bus so hooks can watch it
+    pub memory: Bus,
     pub register: [u8; 16],
     pub register_i: u16,
     pub delay_timer: u8, // Will be used eventually
@@ -90,13 +103,41 @@ pub struct Chip8Sys {
     pub program_counter: u16,
     pub stack_pointer: u8, // Will be used eventually
     pub stack: [u16; 16],  // Will be used eventually
-    pub frame_buffer: [u8; 256],
+    // sized for the largest display, only the active platform's part is used
+    pub frame_buffer: [u8; FRAMEBUFFER_MAX_LEN],
+    // true once pixels or colours changed since the frontend last cleared it
+    pub(crate) is_display_dirty: bool,
     // NOTE: The wait for key press code is dependent on the length of keys <= registers
     pub keys: [bool; 16], // represents the 16 keys of Chip-8. true = pressed
-    wait_for_key_press: Option<u8>, // for instruction 0xFXA0
+    pub(crate) wait_for_key_press: Option<u8>, // for instruction 0xFXA0
+    // the key FX0A saw go down and is waiting to see released
+    pub(crate) wait_pressed_key: Option<u8>,
+    // keys as of the last check, used to find press and release edges
+    pub(crate) previous_keys: [bool; 16],
+    // edges reported through key_down/key_up since the last check, so short taps are not lost
+    pub(crate) key_presses: Chip8KeyMask,
+    pub(crate) key_releases: Chip8KeyMask,
     pub is_playing_sound: bool,
     // controls whether timers are updated internally or externally
     timer_mode: TimerMode,
+    // controls how instruction timing is modelled
+    pub(crate) timing_model: TimingModel,
+    // counts COSMAC VIP machine cycles and display interrupts
+    pub(crate) vip_clock: VipClock,
+    // the emulated platform, decides display size and program start
+    pub(crate) platform: Platform,
+    // true when the platform was picked from the ROM's signature rather than configured
+    pub(crate) is_platform_detected: bool,
+    // the address ROMs load and start at, None for the platform's program start
+    pub(crate) load_address: Option<u16>,
+    // the address execution starts at, None for the load address
+    pub(crate) entry_address: Option<u16>,
+    // the glyphs FX29 and FX30 point at
+    pub(crate) font: Font,
+    // CHIP-8X foreground colour per 8 pixel column and row
+    pub(crate) color_map: [u8; CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS],
+    // CHIP-8X background colour
+    pub(crate) background_color: u8,
     // handles if FX55 & FX65 increment I index register
     is_inc_index: bool,
     // quirk that resets reg[0xF] to 0 when AND, OR, and XOR are set (0x8XY1-3)
@@ -105,6 +146,21 @@ pub struct Chip8Sys {
     is_wrap_draw: bool,
     // quirk that modifies vx in place and ignores vy for <<= and >>= 0x8XY6 & ..E
     is_mod_vx_in_place: bool,
+    // quirk that makes 0xFX0A wait for the key to be released instead of just pressed
+    is_wait_for_release: bool,
+    // watches loops to report halted and idle programs
+    pub(crate) loop_detector: LoopDetector,
+    // the generator 0xCXNN draws from
+    pub(crate) rng: Rng,
+    // collects the profile, None while profiling is off
+    #[cfg(feature = "debugger")]
+    pub(crate) profiler: Option<Box<Profiler>>,
+    // cheats applied each frame and on ROM load
+    #[cfg(feature = "debugger")]
+    pub(crate) cheats: Vec<Cheat>,
+    // addresses run_until_break stops at, sorted
+    #[cfg(feature = "debugger")]
+    pub(crate) breakpoints: Vec<u16>,
 }
 
 impl Chip8Sys {
@@ -115,74 +171,87 @@ impl Chip8Sys {
         is_wrap_draw: bool,
         is_mod_vx_in_place: bool,
     ) -> Chip8Sys {
-        let mut new_chip_8_sys = Chip8Sys {
-            memory: EMPTY_MEMORY,
-            register: EMPTY_REGISTER,
-            register_i: 0,
-            delay_timer: 0,
-            dt_cycle_ct: 0,
-            sound_timer: 0,
-            program_counter: PROGRAM_START as u16, // initialize PC to start reading at 0x200
-            stack_pointer: 0,
-            stack: EMPTY_STACK,
-            frame_buffer: [0x00; 256],
-            keys: [false; 16],
-            wait_for_key_press: None,
-            is_playing_sound: false,
-            timer_mode: TimerMode::Cycle,
-            is_inc_index,
-            is_register_f_reset,
-            is_wrap_draw,
-            is_mod_vx_in_place,
-        };
-        // load the font in memeory
-        for i in FONT_RANGE_MIN..FONT_RANGE_MAX {
-            new_chip_8_sys.memory[i as usize] = FONT[i as usize - FONT_RANGE_MIN as usize];
-        }
-        new_chip_8_sys
+        Chip8Sys::new_with_quirks(Chip8Quirks {
+            increment_i_on_store: is_inc_index,
+            reset_vf_on_logic: is_register_f_reset,
+            wrap_draw: is_wrap_draw,
+            shift_uses_vx: is_mod_vx_in_place,
+            ..Chip8Quirks::default()
+        })
     }
     // sets up a new chip 8 with default quirks for the chip 8 system
     pub fn new_chip_8() -> Chip8Sys {
+        Chip8Sys::new_with_quirks(Chip8Quirks::default())
+    }
+}
+
+impl Chip8Sys {
+    /// This function builds a new Chip-8 instance from a quirk configuration.
+    /// Arguments:
+    /// - quirks: The quirk configuration to apply.
+    ///
+    /// Returns: A new Chip-8 system instance.
+    pub fn new_with_quirks(quirks: Chip8Quirks) -> Chip8Sys {
         let mut new_chip_8_sys = Chip8Sys {
-            memory: EMPTY_MEMORY,
+            memory: Bus::new(),
             register: EMPTY_REGISTER,
             register_i: 0,
             delay_timer: 0,
             dt_cycle_ct: 0,
             sound_timer: 0,
-            program_counter: PROGRAM_START as u16, // initialize PC to start reading at 0x200
+            program_counter: Platform::Chip8.program_start(), // initialize PC to start reading at 0x200
             stack_pointer: 0,
             stack: EMPTY_STACK,
-            frame_buffer: [0x00; 256],
+            frame_buffer: [0x00; FRAMEBUFFER_MAX_LEN],
+            is_display_dirty: true,
             keys: [false; 16],
             wait_for_key_press: None,
+            wait_pressed_key: None,
+            previous_keys: [false; 16],
+            key_presses: 0,
+            key_releases: 0,
             is_playing_sound: false,
             timer_mode: TimerMode::Cycle,
-            is_inc_index: true,
-            is_register_f_reset: true,
-            is_wrap_draw: false,
-            is_mod_vx_in_place: false,
+            timing_model: TimingModel::Instruction,
+            vip_clock: VipClock::default(),
+            platform: Platform::Chip8,
+            is_platform_detected: false,
+            load_address: None,
+            entry_address: None,
+            font: Font::default(),
+            color_map: DEFAULT_COLOR_MAP,
+            background_color: 0,
+            is_inc_index: quirks.increment_i_on_store,
+            is_register_f_reset: quirks.reset_vf_on_logic,
+            is_wrap_draw: quirks.wrap_draw,
+            is_mod_vx_in_place: quirks.shift_uses_vx,
+            is_wait_for_release: quirks.wait_for_key_release,
+            loop_detector: LoopDetector::default(),
+            rng: Rng::default(),
+            #[cfg(feature = "debugger")]
+            profiler: None,
+            #[cfg(feature = "debugger")]
+            cheats: Vec::new(),
+            #[cfg(feature = "debugger")]
+            breakpoints: Vec::new(),
         };
-        // load the font in memeory
-        for i in FONT_RANGE_MIN..FONT_RANGE_MAX {
-            new_chip_8_sys.memory[i as usize] = FONT[i as usize - FONT_RANGE_MIN as usize];
-        }
+        new_chip_8_sys.load_font();
         new_chip_8_sys
     }
-}
 
-impl Chip8Sys {
-    /// This function builds a new Chip-8 instance from a quirk configuration.
-    /// Arguments:
-    /// - quirks: The quirk configuration to apply.
-    /// Returns: A new Chip-8 system instance.
-    pub fn new_with_quirks(quirks: Chip8Quirks) -> Chip8Sys {
-        Chip8Sys::new_set_quirks(
-            quirks.increment_i_on_store,
-            quirks.reset_vf_on_logic,
-            quirks.wrap_draw,
-            quirks.shift_uses_vx,
-        )
+    /// This function starts configuring a Chip-8 system, see `Chip8SysBuilder`.
+    /// Arguments: none.
+    /// Returns: A builder with the default configuration.
+    pub fn builder<'a>() -> Chip8SysBuilder<'a> {
+        Chip8SysBuilder::new()
+    }
+
+    /// This function returns the address ROMs are loaded at and start from.
+    /// Arguments: none.
+    /// Returns: The configured load address, or the platform's program start.
+    pub fn program_start(&self) -> u16 {
+        self.load_address
+            .unwrap_or_else(|| self.platform.program_start())
     }
 
     /// This function returns the active quirk configuration for the Chip-8 instance.
@@ -194,29 +263,66 @@ impl Chip8Sys {
             reset_vf_on_logic: self.is_register_f_reset(),
             wrap_draw: self.is_wrap_draw(),
             shift_uses_vx: self.is_mod_vx_in_place(),
+            wait_for_key_release: self.is_wait_for_release(),
         }
     }
 
     /// This function updates the quirk configuration for the Chip-8 instance.
     /// Arguments:
     /// - quirks: The new quirk configuration to apply.
+    ///
     /// Returns: The updated Chip-8 system.
     pub fn set_quirks(&mut self, quirks: Chip8Quirks) -> &mut Self {
         self.is_inc_index = quirks.increment_i_on_store;
         self.is_register_f_reset = quirks.reset_vf_on_logic;
         self.is_wrap_draw = quirks.wrap_draw;
         self.is_mod_vx_in_place = quirks.shift_uses_vx;
+        self.is_wait_for_release = quirks.wait_for_key_release;
         self
     }
 
-    /// This function resets the Chip-8 system while preserving its quirks.
+    /// This function resets the Chip-8 system while preserving its quirks, timing settings,
+    /// random number generator, memory map, memory hooks, write guard,
+    /// coverage counters, profile, cheats, breakpoints and configured platform.
     /// Arguments: none.
     /// Returns: The updated Chip-8 system.
     pub fn reset(&mut self) -> &mut Self {
         let quirks = self.quirks();
         let timer_mode = self.timer_mode;
+        let timing_model = self.timing_model;
+        let platform = self.platform;
+        let is_platform_detected = self.is_platform_detected;
+        let memory_map = self.memory_map();
+        let rng = core::mem::take(&mut self.rng);
+        #[cfg(feature = "debugger")]
+        let (mut memory, profiler, cheats, breakpoints) = (
+            core::mem::take(&mut self.memory),
+            self.profiler.take(),
+            core::mem::take(&mut self.cheats),
+            core::mem::take(&mut self.breakpoints),
+        );
         *self = Chip8Sys::new_with_quirks(quirks);
+        // the map's default load address depends on the platform
+        if !is_platform_detected {
+            self.platform = platform;
+        }
+        self.set_memory_map(memory_map)
+            .expect("the memory map was valid before the reset");
+        #[cfg(feature = "debugger")]
+        {
+            self.memory.take_settings_from(&mut memory);
+            self.profiler = profiler;
+            self.restart_profile();
+            self.cheats = cheats;
+            self.breakpoints = breakpoints;
+        }
         self.timer_mode = timer_mode;
+        self.timing_model = timing_model;
+        self.rng = rng.restarted();
+        // a detected platform belongs to the ROM that was loaded, not to the configuration
+        if !is_platform_detected {
+            self.set_platform(platform);
+        }
         self
     }
 
@@ -230,6 +336,7 @@ impl Chip8Sys {
     /// This function sets the timer update mode.
     /// Arguments:
     /// - mode: The desired timer update mode.
+    ///
     /// Returns: The updated Chip-8 system.
     pub fn set_timer_mode(&mut self, mode: TimerMode) -> &mut Self {
         self.timer_mode = mode;
@@ -239,9 +346,14 @@ impl Chip8Sys {
     /// This function decrements delay and sound timers by a number of ticks.
     /// Arguments:
     /// - ticks: The number of 60Hz timer ticks to apply.
+    ///
     /// Returns: The updated Chip-8 system.
     pub fn tick_timers(&mut self, ticks: u32) -> &mut Self {
         for _ in 0..ticks {
+            // the VIP timing model ends frames on its own interrupt
+            if self.timing_model == TimingModel::Instruction {
+                self.end_frame();
+            }
             if self.delay_timer > 0 {
                 self.delay_timer -= 1;
             }
@@ -255,12 +367,15 @@ impl Chip8Sys {
         self
     }
 
-    /// This function loads ROM bytes into memory starting at 0x200.
+    /// This function loads ROM bytes into memory at the program start (usually 0x200).
+    /// A CHIP-8 ROM carrying the hi-res signature switches the system to hi-res CHIP-8.
     /// Arguments:
     /// - rom_bytes: The ROM byte slice to load into memory.
+    ///
     /// Returns: The updated Chip-8 system.
     pub fn load_rom_bytes(&mut self, rom_bytes: &[u8]) -> &mut Self {
-        let program_len = self.memory.len().saturating_sub(PROGRAM_START);
+        let program_start = self.program_start() as usize;
+        let program_len = self.memory.size().saturating_sub(program_start);
         /*
         println!(
             "Game memory length: {}, {:X}",
@@ -283,23 +398,44 @@ impl Chip8Sys {
                 "{:02x}: load {:02X} in memory location {:02X}",
                 index,
                 value,
-                PROGRAM_START + index
+                program_start + index
             );
             // */
-            self.memory[PROGRAM_START + index] = value;
+            self.memory[program_start + index] = value;
+        }
+        #[cfg(feature = "debugger")]
+        {
+            let rom_end = program_start + rom_bytes.len().min(program_len);
+            self.memory
+                .guard
+                .set_rom(program_start as u16..rom_end as u16);
         }
+        self.detect_platform(rom_bytes);
+        #[cfg(feature = "debugger")]
+        self.apply_patches();
         self
     }
 
+    // Work done once per 60Hz frame: closes the profiler frame and rewrites frozen cheats
+    pub(crate) fn end_frame(&mut self) {
+        #[cfg(feature = "debugger")]
+        {
+            self.profile_frame();
+            self.apply_cheats();
+        }
+    }
+
     /// This function sets the keypad state from a 16-bit mask.
     /// Arguments:
     /// - mask: A bitmask where bit N indicates whether key N is pressed.
+    ///
     /// Returns: The updated Chip-8 system.
     pub fn set_keys_mask(&mut self, mask: Chip8KeyMask) -> &mut Self {
         for index in 0..self.keys.len() {
             let bit = 1u16 << index;
             self.keys[index] = (mask & bit) != 0;
         }
+        self.wake_on_input();
         self
     }
 
@@ -319,22 +455,96 @@ impl Chip8Sys {
     /// This function replaces the keypad state with a provided array.
     /// Arguments:
     /// - keys: The new key state array.
+    ///
     /// Returns: The updated Chip-8 system.
     pub fn set_keys(&mut self, keys: [bool; 16]) -> &mut Self {
         self.keys = keys;
+        self.wake_on_input();
         self
     }
 
-    /// This function returns the packed framebuffer buffer.
+    /// This function presses a single keypad key.
+    /// Unlike the mask setters the press is remembered until the next instruction, so a key
+    /// released again before then still completes FX0A.
+    /// Arguments:
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: The updated Chip-8 system, or InvalidKeypadKey when the key is > 0xF.
+    pub fn key_down(&mut self, key: u8) -> Result<&mut Self, Chip8Error> {
+        if key > 0xF {
+            return Err(Chip8Error::InvalidKeypadKey(key));
+        }
+        self.keys[key as usize] = true;
+        self.key_presses |= 1 << key;
+        self.wake_on_input();
+        Ok(self)
+    }
+
+    /// This function releases a single keypad key.
+    /// Arguments:
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: The updated Chip-8 system, or InvalidKeypadKey when the key is > 0xF.
+    pub fn key_up(&mut self, key: u8) -> Result<&mut Self, Chip8Error> {
+        if key > 0xF {
+            return Err(Chip8Error::InvalidKeypadKey(key));
+        }
+        self.keys[key as usize] = false;
+        self.key_releases |= 1 << key;
+        self.wake_on_input();
+        Ok(self)
+    }
+
+    /// This function reports whether FX0A is waiting for a key.
+    /// Arguments: none.
+    /// Returns: True while the CPU is stopped on FX0A.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.wait_for_key_press.is_some()
+    }
+
+    /// This function returns the register FX0A will store the key in.
+    /// Arguments: none.
+    /// Returns: The register X, or None when the CPU is not waiting for a key.
+    pub fn waiting_register(&self) -> Option<u8> {
+        self.wait_for_key_press
+    }
+
+    /// This function returns the packed framebuffer buffer for the active display size.
     /// Arguments: none.
     /// Returns: The packed framebuffer buffer.
     pub fn framebuffer_packed(&self) -> &[u8] {
-        &self.frame_buffer
+        &self.frame_buffer[..self.platform.framebuffer_len()]
+    }
+
+    /// This function reports whether the display changed since `clear_display_dirty`, so a
+    /// frontend only redraws when `DXYN` or `00E0` actually changed pixels, or when the platform,
+    /// colours or state changed. A new or reset system starts dirty.
+    /// Arguments: none.
+    /// Returns: True when the display needs redrawing.
+    pub fn is_display_dirty(&self) -> bool {
+        self.is_display_dirty
+    }
+
+    /// This function marks the display as drawn.
+    /// Arguments: none.
+    /// Returns: The updated Chip-8 system.
+    pub fn clear_display_dirty(&mut self) -> &mut Self {
+        self.is_display_dirty = false;
+        self
+    }
+
+    // 0x00E0 - clears the display, only dirtying it when a pixel was lit
+    pub(crate) fn clear_display(&mut self) {
+        if self.frame_buffer.iter().any(|&chunk| chunk != 0) {
+            self.frame_buffer = [0x00; FRAMEBUFFER_MAX_LEN];
+            self.is_display_dirty = true;
+        }
     }
 
     /// This function runs a number of CPU cycles.
     /// Arguments:
     /// - cycles: The number of cycles to execute.
+    ///
     /// Returns: A result indicating whether execution succeeded.
     pub fn tick(&mut self, cycles: u32) -> Result<(), Chip8Error> {
         for _ in 0..cycles {
@@ -364,33 +574,53 @@ impl Chip8Sys {
         self.is_playing_sound
     }
 
+    // Collects the key edges since the last call and completes a pending 0xFX0A.
+    // Returns true while the CPU should keep waiting.
     pub fn check_waiting(&mut self) -> bool {
-        match self.wait_for_key_press {
-            Some(r) => match r {
-                0..0xF => {
-                    for (n, p) in self.keys.iter().enumerate() {
-                        if *p {
-                            self.register[r as usize] = n as u8;
-                            return false;
-                        }
-                    }
-                }
-                // We should never get here because wait_for_key_press is private and only set
-                // by the 0xFX0A op code.
-                r => panic!(
-                    "Register {:02X} does not exist. It should be in 0..0xF and only set by 0xFX0A op code.",
-                    r
-                ),
-            },
-            None => return false,
+        let (presses, releases) = self.take_key_edges();
+        let Some(register) = self.wait_for_key_press else {
+            return false;
+        };
+        if self.wait_pressed_key.is_none() {
+            // the lowest newly pressed key wins
+            self.wait_pressed_key = (0..16u8).find(|key| presses & (1 << key) != 0);
+        }
+        let Some(key) = self.wait_pressed_key else {
+            return true;
+        };
+        let is_released = releases & (1 << key) != 0 || !self.keys[key as usize];
+        if self.is_wait_for_release && !is_released {
+            return true;
         }
-        true
+        self.register[register as usize] = key;
+        self.wait_for_key_press = None;
+        self.wait_pressed_key = None;
+        false
+    }
+
+    // Returns the (pressed, released) key masks since the last call, combining the changes
+    // seen in `keys` with the events from key_down/key_up
+    fn take_key_edges(&mut self) -> (Chip8KeyMask, Chip8KeyMask) {
+        let mut presses = self.key_presses;
+        let mut releases = self.key_releases;
+        for (key, (&is_down, &was_down)) in self.keys.iter().zip(&self.previous_keys).enumerate() {
+            if is_down && !was_down {
+                presses |= 1 << key;
+            } else if !is_down && was_down {
+                releases |= 1 << key;
+            }
+        }
+        self.previous_keys = self.keys;
+        self.key_presses = 0;
+        self.key_releases = 0;
+        (presses, releases)
     }
     pub fn wait(&mut self, register: u8) -> Result<(), Chip8Error> {
         if register > 0xF {
             return Err(Chip8Error::InvalidWaitRegister(register));
         }
         self.wait_for_key_press = Some(register);
+        self.wait_pressed_key = None;
         Ok(())
     }
     pub fn is_inc_index(&self) -> bool {
@@ -405,6 +635,9 @@ impl Chip8Sys {
     pub fn is_mod_vx_in_place(&self) -> bool {
         self.is_mod_vx_in_place
     }
+    pub fn is_wait_for_release(&self) -> bool {
+        self.is_wait_for_release
+    }
     /*
     // This will print the frame_buffer to the console
     fn debug_print_frame_buffer(&self) {
@@ -418,28 +651,33 @@ impl Chip8Sys {
         }
     }
     // */
+    #[cfg(feature = "std")]
     pub fn load_rom(&mut self, file_path: &str) -> &mut Self {
         // let path = env::current_dir().unwrap();
         // println!("Path is: {}", path.display());
         let mut file = File::open(file_path).expect("should have been able to open the file");
-        let mut rom = vec![0; self.memory.len().saturating_sub(PROGRAM_START)];
-        file.read(&mut rom[..])
+        let mut rom = Vec::new();
+        file.read_to_end(&mut rom)
             .expect("Should have been able to read the rom file");
         self.load_rom_bytes(&rom)
     }
 }
 
 #[cfg(test)]
+// the original tests discard run()'s unit result with `let _ =`
+#[allow(clippy::let_unit_value)]
 mod test {
     use super::*;
+    #[cfg(feature = "std")]
+    use crate::snapshot::assert_frame_snapshot;
 
     #[test]
     // Tests to make sure that we can create a new Chip8Sys with the font in the right place;
     fn create_new_chip_8_sys() {
         let new_chip_8_sys = Chip8Sys::new_chip_8();
         assert_eq!(
-            new_chip_8_sys.memory[(FONT_RANGE_MIN as usize)..(FONT_RANGE_MAX as usize)],
-            FONT
+            new_chip_8_sys.memory[0x050..0x0A0],
+            *crate::font::FontSet::Chip48.glyphs()
         );
     }
 
@@ -457,36 +695,21 @@ mod test {
 
     #[test]
     #[ignore]
+    #[cfg(feature = "std")]
     // Tests whether or not the Corax+ test rom passes
     fn run_corax_plus_test_rom() {
         let mut chip8 = Chip8Sys::new_chip_8();
         let file_path = "../roms/3-corax+.ch8";
         chip8.load_rom(file_path);
 
-        let pass_fb: [u8; 256] = [
-            0, 0, 0, 0, 0, 0, 0, 0, 58, 128, 58, 128, 58, 128, 59, 128, 25, 20, 9, 20, 59, 148, 35,
-            20, 10, 152, 50, 152, 40, 152, 48, 152, 58, 144, 58, 144, 56, 144, 35, 16, 0, 0, 0, 0,
-            0, 0, 0, 0, 42, 128, 59, 128, 59, 128, 59, 128, 57, 20, 43, 20, 59, 20, 33, 148, 10,
-            152, 42, 24, 40, 152, 48, 152, 10, 144, 59, 144, 59, 16, 35, 144, 0, 0, 0, 0, 0, 0, 0,
-            0, 58, 128, 59, 128, 59, 128, 59, 128, 49, 20, 58, 148, 56, 148, 35, 20, 10, 152, 42,
-            152, 41, 24, 50, 24, 50, 144, 59, 144, 57, 16, 35, 144, 0, 0, 0, 0, 0, 0, 0, 0, 58,
-            128, 59, 0, 57, 128, 2, 128, 9, 20, 57, 20, 58, 20, 41, 20, 18, 152, 41, 24, 43, 152,
-            42, 152, 18, 144, 59, 144, 59, 144, 18, 144, 0, 0, 0, 0, 0, 0, 0, 0, 58, 128, 59, 128,
-            59, 128, 0, 0, 57, 20, 56, 148, 59, 20, 0, 0, 10, 152, 43, 24, 42, 24, 0, 0, 50, 144,
-            59, 144, 59, 144, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 50, 128, 59, 128, 57, 128, 2, 142, 17,
-            20, 57, 148, 34, 20, 43, 130, 18, 152, 40, 152, 51, 152, 40, 140, 58, 144, 59, 144, 35,
-            144, 16, 174, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        ];
         for _ in 0..500 {
             let _ = chip8.run().unwrap();
         }
-        assert_eq!(
-            pass_fb, chip8.frame_buffer,
-            "Frame Buffer should match the one from a passing run after 140 cycles."
-        );
+        assert_frame_snapshot(&chip8, "corax_plus_test_rom");
     }
     #[test]
     #[ignore]
+    #[cfg(feature = "std")]
     fn run_flags_test_rom() {
         let mut chip8 = Chip8Sys::new_chip_8();
         let file_path = "../roms/4-flags.ch8";
@@ -495,31 +718,14 @@ mod test {
 
         // This has "disp. wait slow x" in the output
         // Needs to be updated if/when I fix that.
-        let pass_fb: [u8; 256] = [
-            164, 204, 163, 0, 0, 14, 0, 0, 234, 170, 161, 21, 84, 2, 85, 80, 174, 204, 65, 25, 152,
-            12, 102, 96, 170, 136, 67, 145, 16, 14, 68, 64, 0, 0, 0, 0, 0, 0, 0, 0, 224, 0, 2, 128,
-            0, 14, 0, 0, 101, 85, 3, 149, 85, 76, 85, 85, 38, 102, 0, 153, 153, 130, 102, 102, 228,
-            68, 0, 145, 17, 12, 68, 68, 0, 0, 0, 0, 0, 0, 0, 0, 224, 0, 3, 128, 0, 14, 0, 0, 133,
-            85, 0, 149, 85, 76, 85, 80, 230, 102, 0, 153, 153, 136, 102, 96, 228, 68, 0, 145, 17,
-            14, 68, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 228, 204, 162, 128, 0, 14,
-            0, 0, 138, 170, 163, 149, 85, 76, 85, 85, 142, 204, 64, 153, 153, 130, 102, 102, 234,
-            170, 64, 145, 17, 12, 68, 68, 0, 0, 0, 0, 0, 0, 0, 0, 224, 0, 3, 128, 0, 14, 0, 0, 133,
-            85, 0, 149, 85, 76, 85, 80, 230, 102, 0, 153, 153, 136, 102, 96, 228, 68, 0, 145, 17,
-            14, 68, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 238, 174, 195, 184, 0, 0,
-            2, 142, 164, 236, 162, 49, 84, 0, 43, 130, 164, 168, 195, 33, 152, 0, 40, 140, 228,
-            174, 162, 57, 16, 0, 16, 174, 0, 0, 0, 0, 0, 0, 0, 0,
-        ];
-
         for _ in 0..1100 {
             let _ = chip8.run().unwrap();
         }
-        assert_eq!(
-            pass_fb, chip8.frame_buffer,
-            "Frame Buffer should match the one from a passing run after 5,000 cycles."
-        );
+        assert_frame_snapshot(&chip8, "flags_test_rom");
     }
     #[test]
     #[ignore]
+    #[cfg(feature = "std")]
     fn run_quirks_test_rom() {
         let mut chip8 = Chip8Sys::new_chip_8();
         let file_path = "../roms/5-quirks.ch8";
@@ -528,27 +734,9 @@ mod test {
 
         // This has "disp. wait slow x" in the output
         // Needs to be updated if/when I fix that.
-        let pass_fb: [u8; 256] = [
-            0, 0, 0, 0, 0, 0, 0, 0, 87, 6, 115, 119, 0, 59, 0, 0, 84, 5, 102, 98, 0, 42, 128, 20,
-            86, 6, 65, 66, 0, 42, 128, 24, 36, 5, 118, 114, 0, 58, 128, 16, 0, 0, 0, 0, 0, 0, 0, 0,
-            119, 119, 101, 0, 0, 59, 0, 0, 118, 117, 85, 0, 0, 42, 128, 20, 84, 85, 98, 0, 0, 42,
-            128, 24, 87, 87, 82, 0, 0, 58, 128, 16, 0, 0, 0, 0, 0, 0, 0, 0, 103, 54, 5, 39, 112,
-            26, 58, 128, 82, 101, 5, 82, 32, 50, 42, 148, 82, 22, 7, 114, 32, 10, 43, 136, 103,
-            100, 39, 87, 32, 51, 187, 148, 0, 0, 0, 0, 0, 0, 0, 0, 116, 118, 103, 99, 0, 59, 0, 0,
-            68, 37, 82, 84, 0, 42, 128, 20, 68, 38, 98, 85, 0, 42, 128, 24, 119, 116, 71, 83, 0,
-            58, 128, 16, 0, 0, 0, 0, 0, 0, 0, 0, 53, 119, 119, 99, 0, 59, 184, 0, 103, 36, 34, 84,
-            0, 42, 32, 20, 21, 38, 34, 85, 0, 43, 48, 24, 101, 116, 39, 83, 0, 58, 32, 16, 0, 0, 0,
-            0, 0, 0, 0, 0, 53, 118, 118, 48, 0, 59, 184, 0, 21, 117, 37, 64, 0, 42, 32, 20, 21, 86,
-            37, 80, 0, 43, 48, 24, 99, 84, 117, 48, 0, 58, 32, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0,
-        ];
-
         for _ in 0..5000 {
             let _ = chip8.run().unwrap();
         }
-        assert_eq!(
-            pass_fb, chip8.frame_buffer,
-            "Frame Buffer should match the one from a passing run after 5,000 cycles."
-        );
+        assert_frame_snapshot(&chip8, "quirks_test_rom");
     }
 }
@@ -1,3 +1,7 @@
+use crate::bus::Access;
+#[cfg(feature = "debugger")]
+use crate::guard::WriteWarning;
+
 #[derive(Debug)]
 pub enum Chip8Error {
     InvalidFirstByte(u8), // if the N of 0xN___ is invalid it will return this and the N provided
@@ -6,5 +10,27 @@ pub enum Chip8Error {
     Invalid0xENNN(u8, u8), // if the N in 0xE_NN is invalid it will return this and the N provided
     Invalid0xFNNN(u8, u8), // if the N in 0xF_NN is invalid it will return this and the N provided
     InvalidWaitRegister(u8), // If the register we're waiting for is somehow > 0xF
+    InvalidStackPointer(u8), // if a stack pointer or stack slot edited from outside is > 0xF
+    #[cfg(feature = "os-rng")]
     IssueGeneratingRandomNum(getrandom::Error), // if something goes wrong with the random number generator
+    #[cfg(feature = "serde")]
+    InvalidRomDatabase(String), // if a ROM database could not be parsed, holds the parser's message
+    InvalidKeypadKey(u8), // if a key binding targets a keypad key > 0xF
+    #[cfg(feature = "serde")]
+    InvalidKeymapProfile(String), // if a keymap profile could not be parsed, holds the parser's message
+    InvalidMemorySize(usize), // if a memory size is not a power of two from 1KB to 4KB
+    InvalidLoadAddress(u16),  // if programs would load over the font or past the end of memory
+    RomTooLarge(usize), // if a ROM does not fit in memory from the load address, holds its length
+    InvalidFont(usize), // if big font glyphs are not whole 10-byte glyphs or more than 16, holds the length
+    InvalidFontAddress(u16), // if a font does not fit in memory at its address
+    InvalidTimingModel, // if a call needs the COSMAC VIP timing model and another one is active
+    StateBufferTooSmall(usize), // if a save state buffer is too short, holds the length needed
+    InvalidSaveState(usize), // if a save state is truncated, foreign or out of range, holds the offset
+    Watchpoint(Access, u16), // if a watchpoint hook caught an access, holds the access and address
+    #[cfg(feature = "debugger")]
+    ProtectedWrite(WriteWarning), // if the write guard caught a write into code, the font or the ROM
+    #[cfg(feature = "debugger")]
+    InvalidSymbolFile(String), // if a symbol file could not be parsed, holds the line and problem
+    #[cfg(feature = "debugger")]
+    InvalidCheat(String), // if a cheat list or target could not be parsed, holds the problem
 }
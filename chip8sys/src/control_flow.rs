@@ -0,0 +1,548 @@
+//! Static control-flow and call-graph extraction.
+//!
+//! `ControlFlowGraph::build` walks the code reachable from the program start, splits it into basic
+//! blocks joined by fallthrough, jump, call, computed jump and skip edges, groups the blocks into
+//! subroutines (the program start plus every 2NNN target) and lists the ROM bytes no path reaches,
+//! which are usually sprites and other data. The graph can be written as Graphviz DOT or JSON.
+//!
+//! BNNN jumps to NNN + V0, which is only known at run time, so the walk follows NNN alone.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::instruction::{self, Instruction};
+use crate::platform::Platform;
+
+/// This enum lists how control moves from one block to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EdgeKind {
+    /// Execution runs on into the next block, including returning from a 2NNN call.
+    Fallthrough,
+    /// 1NNN jumps to the target.
+    Jump,
+    /// 2NNN calls the target.
+    Call,
+    /// BNNN jumps to the target plus V0.
+    JumpOffset,
+    /// A skip instruction skips over the next instruction to the target.
+    Skip,
+}
+
+impl EdgeKind {
+    /// This function returns the edge kind's name used in reports.
+    /// Arguments: none.
+    /// Returns: `fallthrough`, `jump`, `call`, `jump_offset` or `skip`.
+    pub fn name(self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Call => "call",
+            EdgeKind::JumpOffset => "jump_offset",
+            EdgeKind::Skip => "skip",
+        }
+    }
+}
+
+/// This struct is a run of instructions that is only entered at the top and left at the bottom.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BasicBlock {
+    /// This field stores the address of the first instruction.
+    pub start: u16,
+    /// This field stores the address just past the last instruction.
+    pub end: u16,
+    /// This field stores the (address, opcode) of each instruction.
+    pub instructions: Vec<(u16, u16)>,
+    /// This field stores the entry address of the subroutine the block belongs to.
+    pub subroutine: u16,
+}
+
+/// This struct is an edge between the start addresses of two blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Edge {
+    /// This field stores the block control leaves.
+    pub from: u16,
+    /// This field stores the block control enters.
+    pub to: u16,
+    /// This field stores how control gets there.
+    pub kind: EdgeKind,
+}
+
+/// This struct is a subroutine, or the main program, in the call graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Subroutine {
+    /// This field stores the entry address, the program start for the main program.
+    pub entry: u16,
+    /// This field stores the start addresses of the subroutine's blocks.
+    pub blocks: Vec<u16>,
+    /// This field stores the entry addresses of the subroutines it calls.
+    pub calls: Vec<u16>,
+}
+
+/// This struct is the control-flow graph of a ROM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ControlFlowGraph {
+    /// This field stores the program start address.
+    pub entry: u16,
+    /// This field stores the basic blocks in address order.
+    pub blocks: Vec<BasicBlock>,
+    /// This field stores the edges between blocks.
+    pub edges: Vec<Edge>,
+    /// This field stores the subroutines in address order, the main program included.
+    pub subroutines: Vec<Subroutine>,
+    /// This field stores the address ranges of ROM bytes no path reaches.
+    pub unreachable: Vec<Range<u16>>,
+}
+
+// The length of the instruction, F000 NNNN is the only four byte one
+fn instruction_len(opcode: u16) -> u16 {
+    if opcode == 0xF000 { 4 } else { 2 }
+}
+
+// Returns where control can go after the instruction at `address`
+pub(crate) fn successors(address: u16, opcode: u16, platform: Platform) -> Vec<(u16, EdgeKind)> {
+    let next = address.wrapping_add(instruction_len(opcode));
+    match Instruction::decode(opcode, platform) {
+        Instruction::Jump { nnn } => vec![(nnn, EdgeKind::Jump)],
+        Instruction::Call { nnn } => vec![(next, EdgeKind::Fallthrough), (nnn, EdgeKind::Call)],
+        Instruction::JumpOffset { nnn } => vec![(nnn, EdgeKind::JumpOffset)],
+        Instruction::Return => Vec::new(),
+        // 00FD exits the SUPER-CHIP interpreter
+        _ if opcode == 0x00FD => Vec::new(),
+        _ if instruction::extension(opcode).is_some() => vec![(next, EdgeKind::Fallthrough)],
+        // anything else that does not decode is most likely data
+        Instruction::Invalid { .. } => Vec::new(),
+        instruction if instruction.is_skip() => vec![
+            (next, EdgeKind::Fallthrough),
+            (next.wrapping_add(2), EdgeKind::Skip),
+        ],
+        _ => vec![(next, EdgeKind::Fallthrough)],
+    }
+}
+
+/// This function walks the control flow from the program start.
+/// Arguments:
+/// - rom_bytes: The ROM as loaded at the platform's program start.
+/// - platform: The platform to decode for.
+///
+/// Returns: The reachable (address, opcode) pairs in address order.
+pub fn reachable(rom_bytes: &[u8], platform: Platform) -> Vec<(u16, u16)> {
+    let start = platform.program_start() as usize;
+    let end = start + rom_bytes.len();
+    let mut visited = HashSet::new();
+    let mut pending = vec![start as u16];
+    let mut code = Vec::new();
+    while let Some(address) = pending.pop() {
+        let index = address as usize;
+        if index < start || index + 1 >= end || !visited.insert(address) {
+            continue;
+        }
+        let offset = index - start;
+        let opcode = u16::from_be_bytes([rom_bytes[offset], rom_bytes[offset + 1]]);
+        code.push((address, opcode));
+        pending.extend(
+            successors(address, opcode, platform)
+                .into_iter()
+                .map(|(target, _)| target),
+        );
+    }
+    code.sort_unstable();
+    code
+}
+
+impl ControlFlowGraph {
+    /// This function builds the control-flow graph of a ROM.
+    /// Arguments:
+    /// - rom_bytes: The ROM as loaded at the platform's program start.
+    /// - platform: The platform to decode for.
+    ///
+    /// Returns: The graph.
+    pub fn build(rom_bytes: &[u8], platform: Platform) -> ControlFlowGraph {
+        let entry = platform.program_start();
+        let code = reachable(rom_bytes, platform);
+
+        // a block starts at the entry and at every target of a branching instruction
+        let mut leaders = BTreeSet::from([entry]);
+        for &(address, opcode) in &code {
+            let next = address.wrapping_add(instruction_len(opcode));
+            let targets = successors(address, opcode, platform);
+            if targets != [(next, EdgeKind::Fallthrough)] {
+                leaders.extend(targets.into_iter().map(|(target, _)| target));
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for &(address, opcode) in &code {
+            let next = address.wrapping_add(instruction_len(opcode));
+            match blocks.last_mut() {
+                Some(block) if block.end == address && !leaders.contains(&address) => {
+                    block.instructions.push((address, opcode));
+                    block.end = next;
+                }
+                _ => blocks.push(BasicBlock {
+                    start: address,
+                    end: next,
+                    instructions: vec![(address, opcode)],
+                    subroutine: entry,
+                }),
+            }
+        }
+
+        let starts: HashSet<u16> = blocks.iter().map(|block| block.start).collect();
+        let mut edges = Vec::new();
+        for block in &blocks {
+            let &(address, opcode) = block.instructions.last().expect("blocks are never empty");
+            for (target, kind) in successors(address, opcode, platform) {
+                // targets outside the walked code (the font, the interpreter) get no block
+                if starts.contains(&target) {
+                    edges.push(Edge {
+                        from: block.start,
+                        to: target,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        let subroutines = assign_subroutines(entry, &mut blocks, &edges);
+        let unreachable = unreachable_ranges(entry, rom_bytes.len(), &code);
+        ControlFlowGraph {
+            entry,
+            blocks,
+            edges,
+            subroutines,
+            unreachable,
+        }
+    }
+
+    /// This function writes the graph as Graphviz DOT, one cluster per subroutine.
+    /// Arguments: none.
+    /// Returns: The DOT source.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n  node [shape=box, fontname=\"monospace\"];\n");
+        for subroutine in &self.subroutines {
+            let _ = writeln!(
+                dot,
+                "  subgraph cluster_{0:03X} {{\n    label=\"{1} {0:03X}\";",
+                subroutine.entry,
+                if subroutine.entry == self.entry {
+                    "main"
+                } else {
+                    "sub"
+                }
+            );
+            for block in self
+                .blocks
+                .iter()
+                .filter(|block| block.subroutine == subroutine.entry)
+            {
+                let mut label = String::new();
+                for (address, opcode) in &block.instructions {
+                    let _ = write!(label, "{address:03X}: {opcode:04X}\\l");
+                }
+                let _ = writeln!(dot, "    b{:03X} [label=\"{label}\"];", block.start);
+            }
+            dot.push_str("  }\n");
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\", style=dashed]",
+                EdgeKind::JumpOffset => " [label=\"+V0\", style=dotted]",
+                EdgeKind::Skip => " [label=\"skip\"]",
+            };
+            let _ = writeln!(dot, "  b{:03X} -> b{:03X}{style};", edge.from, edge.to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// This function writes the call graph as Graphviz DOT.
+    /// Arguments: none.
+    /// Returns: The DOT source, one node per subroutine.
+    pub fn call_graph_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph calls {\n  node [shape=box, fontname=\"monospace\"];\n");
+        for subroutine in &self.subroutines {
+            let _ = writeln!(dot, "  s{0:03X} [label=\"{0:03X}\"];", subroutine.entry);
+        }
+        for subroutine in &self.subroutines {
+            for callee in &subroutine.calls {
+                let _ = writeln!(dot, "  s{:03X} -> s{callee:03X};", subroutine.entry);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// This function writes the graph as JSON.
+    /// Arguments: none.
+    /// Returns: The JSON text.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the graph only holds numbers and names")
+    }
+}
+
+// Gives every block to the subroutine that reaches it first without following calls, and returns
+// the subroutines with their blocks and callees
+fn assign_subroutines(entry: u16, blocks: &mut [BasicBlock], edges: &[Edge]) -> Vec<Subroutine> {
+    let entries: BTreeSet<u16> = edges
+        .iter()
+        .filter(|edge| edge.kind == EdgeKind::Call)
+        .map(|edge| edge.to)
+        .chain([entry])
+        .collect();
+    let index: BTreeMap<u16, usize> = blocks
+        .iter()
+        .enumerate()
+        .map(|(position, block)| (block.start, position))
+        .collect();
+    let mut owner: BTreeMap<u16, u16> = entries.iter().map(|&start| (start, start)).collect();
+    // the main program claims first so code it falls into stays with it
+    let order = [entry]
+        .into_iter()
+        .chain(entries.iter().copied().filter(|&start| start != entry));
+    for subroutine in order {
+        let mut pending = vec![subroutine];
+        while let Some(start) = pending.pop() {
+            for edge in edges.iter().filter(|edge| edge.from == start) {
+                if edge.kind != EdgeKind::Call && !owner.contains_key(&edge.to) {
+                    owner.insert(edge.to, subroutine);
+                    pending.push(edge.to);
+                }
+            }
+        }
+    }
+
+    let mut subroutines: BTreeMap<u16, Subroutine> = entries
+        .iter()
+        .map(|&start| {
+            let subroutine = Subroutine {
+                entry: start,
+                blocks: Vec::new(),
+                calls: Vec::new(),
+            };
+            (start, subroutine)
+        })
+        .collect();
+    for (&start, &subroutine) in &owner {
+        if let Some(&position) = index.get(&start) {
+            blocks[position].subroutine = subroutine;
+            if let Some(entry) = subroutines.get_mut(&subroutine) {
+                entry.blocks.push(start);
+            }
+        }
+    }
+    for edge in edges.iter().filter(|edge| edge.kind == EdgeKind::Call) {
+        let caller = owner.get(&edge.from).copied().unwrap_or(entry);
+        if let Some(subroutine) = subroutines.get_mut(&caller)
+            && !subroutine.calls.contains(&edge.to)
+        {
+            subroutine.calls.push(edge.to);
+        }
+    }
+    subroutines
+        .into_values()
+        .map(|mut subroutine| {
+            subroutine.calls.sort_unstable();
+            subroutine
+        })
+        .collect()
+}
+
+// Returns the ROM address ranges not covered by a reachable instruction
+fn unreachable_ranges(entry: u16, rom_len: usize, code: &[(u16, u16)]) -> Vec<Range<u16>> {
+    let start = entry as usize;
+    let mut is_code = vec![false; rom_len];
+    for &(address, opcode) in code {
+        let offset = address as usize - start;
+        let len = (instruction_len(opcode) as usize).min(rom_len - offset);
+        is_code[offset..offset + len].fill(true);
+    }
+    let mut ranges: Vec<Range<u16>> = Vec::new();
+    for (offset, _) in is_code.iter().enumerate().filter(|(_, is_code)| !**is_code) {
+        let address = (start + offset) as u16;
+        match ranges.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => ranges.push(address..address + 1),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 200: 6000      V0 = 0
+    // 202: 2210      call 210
+    // 204: 3005      skip if V0 == 5
+    // 206: 1202      loop
+    // 208: 1208      halt
+    // 20A: 0F0F FFFF data
+    // 20E: 0000      padding
+    // 210: 7001      V0 += 1
+    // 212: 00EE      return
+    const ROM: [u8; 20] = [
+        0x60, 0x00, 0x22, 0x10, 0x30, 0x05, 0x12, 0x02, 0x12, 0x08, 0x0F, 0x0F, 0xFF, 0xFF, 0x00,
+        0x00, 0x70, 0x01, 0x00, 0xEE,
+    ];
+
+    #[test]
+    // Tests that blocks split at branch targets and stop before data
+    fn test_blocks() {
+        let graph = ControlFlowGraph::build(&ROM, Platform::Chip8);
+        let blocks: Vec<(u16, u16, u16)> = graph
+            .blocks
+            .iter()
+            .map(|block| (block.start, block.end, block.subroutine))
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                (0x200, 0x202, 0x200),
+                (0x202, 0x204, 0x200),
+                (0x204, 0x206, 0x200),
+                (0x206, 0x208, 0x200),
+                (0x208, 0x20A, 0x200),
+                (0x210, 0x214, 0x210),
+            ]
+        );
+        assert_eq!(
+            graph.blocks[5].instructions,
+            [(0x210, 0x7001), (0x212, 0x00EE)]
+        );
+    }
+
+    #[test]
+    // Tests the fallthrough, call, skip and jump edges
+    fn test_edges() {
+        let graph = ControlFlowGraph::build(&ROM, Platform::Chip8);
+        let edge = |from, to, kind| Edge { from, to, kind };
+        assert_eq!(
+            graph.edges,
+            [
+                edge(0x200, 0x202, EdgeKind::Fallthrough),
+                edge(0x202, 0x204, EdgeKind::Fallthrough),
+                edge(0x202, 0x210, EdgeKind::Call),
+                edge(0x204, 0x206, EdgeKind::Fallthrough),
+                edge(0x204, 0x208, EdgeKind::Skip),
+                edge(0x206, 0x202, EdgeKind::Jump),
+                edge(0x208, 0x208, EdgeKind::Jump),
+            ]
+        );
+    }
+
+    #[test]
+    // Tests that BNNN is followed to NNN alone
+    fn test_jump_offset_edge() {
+        // 6002 B206 FFFF 1206: jump to 0x206 + V0 over some data
+        let rom = [0x60, 0x02, 0xB2, 0x06, 0xFF, 0xFF, 0x12, 0x06];
+        let graph = ControlFlowGraph::build(&rom, Platform::Chip8);
+        assert_eq!(
+            graph.edges[0],
+            Edge {
+                from: 0x200,
+                to: 0x206,
+                kind: EdgeKind::JumpOffset,
+            }
+        );
+        assert_eq!(graph.unreachable.len(), 1);
+        assert_eq!(graph.unreachable[0], 0x204..0x206);
+    }
+
+    #[test]
+    // Tests that blocks are grouped into the main program and its callees
+    fn test_subroutines() {
+        let graph = ControlFlowGraph::build(&ROM, Platform::Chip8);
+        assert_eq!(
+            graph.subroutines,
+            [
+                Subroutine {
+                    entry: 0x200,
+                    blocks: vec![0x200, 0x202, 0x204, 0x206, 0x208],
+                    calls: vec![0x210],
+                },
+                Subroutine {
+                    entry: 0x210,
+                    blocks: vec![0x210],
+                    calls: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    // Tests that the walk only decodes reachable instructions, in address order
+    fn test_reachable() {
+        let code = reachable(&ROM, Platform::Chip8);
+        let addresses: Vec<u16> = code.iter().map(|(address, _)| *address).collect();
+        assert_eq!(addresses, [0x200, 0x202, 0x204, 0x206, 0x208, 0x210, 0x212]);
+        assert_eq!(code[1], (0x202, 0x2210));
+    }
+
+    #[test]
+    // Tests that data between code and padding after it is reported as one region
+    fn test_unreachable_data() {
+        let graph = ControlFlowGraph::build(&ROM, Platform::Chip8);
+        assert_eq!(graph.unreachable.len(), 1);
+        assert_eq!(graph.unreachable[0], 0x20A..0x210);
+    }
+
+    #[test]
+    // Tests that each gap is its own region, including a trailing odd byte, and that a fully
+    // reached ROM has none
+    fn test_unreachable_regions() {
+        // 1206 AAAA BBBB 1206 CC: jump over two words of data, halt, then a stray byte
+        let rom = [0x12, 0x06, 0xAA, 0xAA, 0xBB, 0xBB, 0x12, 0x06, 0xCC];
+        let graph = ControlFlowGraph::build(&rom, Platform::Chip8);
+        assert_eq!(graph.unreachable, [0x202..0x206, 0x208..0x209]);
+        let graph = ControlFlowGraph::build(&[0x12, 0x00], Platform::Chip8);
+        assert!(graph.unreachable.is_empty());
+    }
+
+    #[test]
+    // Tests the DOT export's clusters, block labels and edge styles
+    fn test_dot() {
+        let dot = ControlFlowGraph::build(&ROM, Platform::Chip8).to_dot();
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains("subgraph cluster_200 {\n    label=\"main 200\";"));
+        assert!(dot.contains("subgraph cluster_210 {\n    label=\"sub 210\";"));
+        assert!(dot.contains("b210 [label=\"210: 7001\\l212: 00EE\\l\"];"));
+        assert!(dot.contains("b202 -> b210 [label=\"call\", style=dashed];"));
+        assert!(dot.contains("b204 -> b208 [label=\"skip\"];"));
+        assert!(dot.contains("b200 -> b202;"));
+    }
+
+    #[test]
+    // Tests the call graph export
+    fn test_call_graph_dot() {
+        let dot = ControlFlowGraph::build(&ROM, Platform::Chip8).call_graph_dot();
+        assert!(dot.starts_with("digraph calls {"));
+        assert!(dot.contains("s210 [label=\"210\"];"));
+        assert!(dot.contains("s200 -> s210;"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    // Tests the JSON export's field names
+    fn test_json() {
+        let graph = ControlFlowGraph::build(&ROM, Platform::Chip8);
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["entry"], 0x200);
+        assert_eq!(json["edges"][2]["kind"], "call");
+        assert_eq!(json["unreachable"][0]["start"], 0x20A);
+        assert_eq!(json["subroutines"][1]["entry"], 0x210);
+    }
+}
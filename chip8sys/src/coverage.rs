@@ -0,0 +1,464 @@
+//! Code coverage and memory access heatmaps.
+//!
+//! With coverage on, the bus counts how often each of the 4096 addresses is executed, read and
+//! written. The counters can be drawn as a 64x64 heatmap with one pixel per address, or written as
+//! an lcov report for the usual coverage tools.
+//!
+//! lcov wants source lines, so the report is mapped through a symbol file from the assembler. Each
+//! line holds a hex address and either a label or a `file:line` source location, and `;` or `#`
+//! starts a comment:
+//!
+//! ```text
+//! 0x200 main
+//! 0x200 game.8o:12
+//! 0x202 game.8o:13
+//! ```
+//!
+//! Executed addresses the symbol file does not map are reported against the ROM itself, with the
+//! address as the line number.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::bus::{Access, MEMORY_SIZE};
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+
+/// This constant defines the width and height of the heatmap, one pixel per address.
+pub const HEATMAP_SIZE: usize = 64;
+
+/// This struct stores per-address execution, read and write counters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    // one counter per address
+    executions: Vec<u32>,
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+impl Default for Coverage {
+    /// This function returns coverage with every counter at zero.
+    /// Arguments: none.
+    /// Returns: Empty coverage.
+    fn default() -> Self {
+        Coverage {
+            executions: vec![0; MEMORY_SIZE],
+            reads: vec![0; MEMORY_SIZE],
+            writes: vec![0; MEMORY_SIZE],
+        }
+    }
+}
+
+impl Coverage {
+    /// This function returns how often each address was fetched as the start of an instruction.
+    /// Arguments: none.
+    /// Returns: 4096 counters indexed by address.
+    pub fn executions(&self) -> &[u32] {
+        &self.executions
+    }
+
+    /// This function returns how often each address was read by DXYN or FX65.
+    /// Arguments: none.
+    /// Returns: 4096 counters indexed by address.
+    pub fn reads(&self) -> &[u32] {
+        &self.reads
+    }
+
+    /// This function returns how often each address was written by FX33 or FX55.
+    /// Arguments: none.
+    /// Returns: 4096 counters indexed by address.
+    pub fn writes(&self) -> &[u32] {
+        &self.writes
+    }
+
+    // Called by the bus for every CPU access
+    pub(crate) fn count(&mut self, access: Access, address: u16) {
+        let counters = match access {
+            Access::Read => &mut self.reads,
+            Access::Write => &mut self.writes,
+            Access::Execute => &mut self.executions,
+        };
+        let counter = &mut counters[address as usize];
+        *counter = counter.saturating_add(1);
+    }
+
+    /// This function draws the counters as a 64x64 RGBA image, one pixel per address in rows of
+    /// 64 addresses. Writes are red, executions green and reads blue, each on a log scale.
+    /// Arguments: none.
+    /// Returns: 64 * 64 * 4 bytes, ready for a canvas `ImageData`.
+    pub fn heatmap_rgba(&self) -> Vec<u8> {
+        let channels = [&self.writes, &self.executions, &self.reads];
+        let scales = channels.map(|counters| {
+            let max = counters.iter().copied().max().unwrap_or(0);
+            (max as f32).ln_1p()
+        });
+        let mut image = Vec::with_capacity(MEMORY_SIZE * 4);
+        for address in 0..MEMORY_SIZE {
+            for (counters, scale) in channels.iter().zip(scales) {
+                let count = counters[address];
+                if count == 0 {
+                    image.push(0);
+                } else {
+                    // the busiest address is full brightness, anything touched at least dimly lit
+                    image.push((48. + 207. * (count as f32).ln_1p() / scale).round() as u8);
+                }
+            }
+            image.push(0xFF);
+        }
+        image
+    }
+
+    /// This function draws the heatmap as a binary PPM image.
+    /// Arguments: none.
+    /// Returns: The bytes of a 64x64 P6 image file.
+    pub fn heatmap_ppm(&self) -> Vec<u8> {
+        let mut image = format!("P6\n{HEATMAP_SIZE} {HEATMAP_SIZE}\n255\n").into_bytes();
+        for pixel in self.heatmap_rgba().chunks_exact(4) {
+            image.extend_from_slice(&pixel[..3]);
+        }
+        image
+    }
+
+    /// This function writes the execution counters as an lcov report.
+    /// Arguments:
+    /// - symbols: The assembler's symbol file, used to map addresses to source lines and labels.
+    /// - rom_name: The file name to report unmapped addresses against.
+    ///
+    /// Returns: The lcov tracefile text.
+    pub fn to_lcov(&self, symbols: &SymbolFile, rom_name: &str) -> String {
+        #[derive(Default)]
+        struct SourceFile<'a> {
+            lines: BTreeMap<u32, u32>,
+            functions: Vec<(u32, &'a str, u32)>,
+        }
+        let mut files: BTreeMap<&str, SourceFile<'_>> = BTreeMap::new();
+        for (&address, source) in &symbols.lines {
+            let hits = self.executions[address as usize];
+            let line = files
+                .entry(&source.file)
+                .or_default()
+                .lines
+                .entry(source.line)
+                .or_default();
+            // a line that assembles to several instructions ran as often as its busiest one
+            *line = (*line).max(hits);
+        }
+        for (address, &hits) in self.executions.iter().enumerate() {
+            if hits > 0 && !symbols.lines.contains_key(&(address as u16)) {
+                let file = files.entry(rom_name).or_default();
+                file.lines.insert(address as u32, hits);
+            }
+        }
+        for (address, name) in &symbols.labels {
+            let hits = self.executions[*address as usize];
+            let (file, line) = match symbols.lines.get(address) {
+                Some(source) => (source.file.as_str(), source.line),
+                None => (rom_name, *address as u32),
+            };
+            files
+                .entry(file)
+                .or_default()
+                .functions
+                .push((line, name.as_str(), hits));
+        }
+
+        let mut report = String::new();
+        for (name, file) in &files {
+            let _ = writeln!(report, "TN:\nSF:{name}");
+            for (line, function, _) in &file.functions {
+                let _ = writeln!(report, "FN:{line},{function}");
+            }
+            for (_, function, hits) in &file.functions {
+                let _ = writeln!(report, "FNDA:{hits},{function}");
+            }
+            let functions_hit = file.functions.iter().filter(|(.., hits)| *hits > 0);
+            let _ = writeln!(report, "FNF:{}", file.functions.len());
+            let _ = writeln!(report, "FNH:{}", functions_hit.count());
+            for (line, hits) in &file.lines {
+                let _ = writeln!(report, "DA:{line},{hits}");
+            }
+            let lines_hit = file.lines.values().filter(|hits| **hits > 0);
+            let _ = writeln!(report, "LF:{}", file.lines.len());
+            let _ = writeln!(report, "LH:{}", lines_hit.count());
+            report.push_str("end_of_record\n");
+        }
+        report
+    }
+}
+
+/// This struct stores a source location from a symbol file.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLine {
+    /// This field stores the source file name.
+    pub file: String,
+    /// This field stores the 1-based line number.
+    pub line: u32,
+}
+
+/// This struct maps addresses to labels and source lines, as written by an assembler.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolFile {
+    // labels in address order, an address can carry several
+    labels: Vec<(u16, String)>,
+    lines: BTreeMap<u16, SourceLine>,
+}
+
+impl SymbolFile {
+    /// This function parses a symbol file, see the module docs for the format.
+    /// Arguments:
+    /// - text: The symbol file contents.
+    ///
+    /// Returns: The symbols, or InvalidSymbolFile naming the first bad line.
+    pub fn parse(text: &str) -> Result<SymbolFile, Chip8Error> {
+        let mut symbols = SymbolFile::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split([';', '#']).next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str| {
+                Chip8Error::InvalidSymbolFile(format!("line {}: {message}", index + 1))
+            };
+            let (address, symbol) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| error("expected an address and a symbol"))?;
+            let digits = address.trim_start_matches("0x").trim_start_matches('$');
+            let address = u16::from_str_radix(digits, 16)
+                .ok()
+                .filter(|address| (*address as usize) < MEMORY_SIZE)
+                .ok_or_else(|| error("expected a hex address below 0x1000"))?;
+            let symbol = symbol.trim();
+            match symbol.rsplit_once(':') {
+                Some((file, line)) if line.parse::<u32>().is_ok() => {
+                    symbols.lines.insert(
+                        address,
+                        SourceLine {
+                            file: file.to_owned(),
+                            line: line.parse().unwrap_or_default(),
+                        },
+                    );
+                }
+                _ => symbols.labels.push((address, symbol.to_owned())),
+            }
+        }
+        symbols.labels.sort_by_key(|(address, _)| *address);
+        Ok(symbols)
+    }
+
+    /// This function returns the labels at an address.
+    /// Arguments:
+    /// - address: The address to look up.
+    ///
+    /// Returns: The labels, in file order.
+    pub fn labels(&self, address: u16) -> impl Iterator<Item = &str> {
+        self.labels
+            .iter()
+            .filter(move |(label_address, _)| *label_address == address)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// This function returns the source line an address was assembled from.
+    /// Arguments:
+    /// - address: The address to look up.
+    ///
+    /// Returns: The source line, or None when the address is not mapped.
+    pub fn source_line(&self, address: u16) -> Option<&SourceLine> {
+        self.lines.get(&address)
+    }
+}
+
+impl Chip8Sys {
+    /// This function turns coverage counting on or off. Turning it off discards the counters.
+    /// The counters survive `reset`, so coverage can be collected over several runs.
+    /// Arguments:
+    /// - is_enabled: True to count accesses.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_coverage(&mut self, is_enabled: bool) -> &mut Self {
+        match (is_enabled, &self.memory.coverage) {
+            (true, None) => self.memory.coverage = Some(Box::default()),
+            (false, Some(_)) => self.memory.coverage = None,
+            _ => {}
+        }
+        self
+    }
+
+    /// This function returns the coverage counters.
+    /// Arguments: none.
+    /// Returns: The counters, or None when coverage is off.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.memory.coverage.as_deref()
+    }
+
+    /// This function sets every coverage counter back to zero.
+    /// Arguments: none.
+    /// Returns: The updated Chip-8 system.
+    pub fn clear_coverage(&mut self) -> &mut Self {
+        if let Some(coverage) = &mut self.memory.coverage {
+            **coverage = Coverage::default();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 6003 A300 F065 7001 3005 1206 120C: load from 0x300, count V0 to 5, then halt
+    const ROM: [u8; 14] = [
+        0x60, 0x03, 0xA3, 0x00, 0xF0, 0x65, 0x70, 0x01, 0x30, 0x05, 0x12, 0x06, 0x12, 0x0C,
+    ];
+
+    fn run_rom() -> Chip8Sys {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_coverage(true).load_rom_bytes(&ROM);
+        chip8.memory[0x300] = 3;
+        chip8.tick(12).unwrap();
+        chip8
+    }
+
+    // The symbol file of ROM: two labels per mapped line and one label the ROM never reaches
+    const SYMBOLS: &str = "; symbols for count.8o
+        0x200 main
+        0x206 loop
+        0x20C done ; never reached
+        0x200 count.8o:2
+        0x202 count.8o:3
+        0x204 count.8o:3
+        0x206 count.8o:5";
+
+    #[test]
+    // Tests that fetches, reads and writes are counted per address
+    fn test_counters() {
+        let chip8 = run_rom();
+        let coverage = chip8.coverage().unwrap();
+        assert_eq!(
+            coverage.executions()[0x200..0x20E],
+            [1, 0, 1, 0, 1, 0, 2, 0, 2, 0, 1, 0, 4, 0]
+        );
+        assert_eq!(coverage.reads()[0x300], 1);
+        assert_eq!(coverage.reads().iter().sum::<u32>(), 1);
+        assert_eq!(coverage.writes().iter().sum::<u32>(), 0);
+    }
+
+    #[test]
+    // Tests that coverage is off by default and that turning it off discards the counters
+    fn test_off() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        assert!(chip8.coverage().is_none());
+        chip8.load_rom_bytes(&ROM);
+        chip8.tick(2).unwrap();
+        chip8.set_coverage(true);
+        assert_eq!(chip8.coverage().unwrap().executions()[0x200], 0);
+        chip8.set_coverage(false);
+        assert!(chip8.coverage().is_none());
+    }
+
+    #[test]
+    // Tests that executions are green, reads blue and untouched addresses black
+    fn test_heatmap() {
+        let chip8 = run_rom();
+        let heatmap = chip8.coverage().unwrap().heatmap_rgba();
+        assert_eq!(heatmap.len(), HEATMAP_SIZE * HEATMAP_SIZE * 4);
+        assert_eq!(heatmap[0x20C * 4..0x20C * 4 + 4], [0, 0xFF, 0, 0xFF]);
+        assert_eq!(heatmap[0x300 * 4..0x300 * 4 + 4], [0, 0, 0xFF, 0xFF]);
+        assert_eq!(heatmap[0x201 * 4..0x201 * 4 + 4], [0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    // Tests that the PPM image has a 64x64 header followed by the heatmap's RGB bytes
+    fn test_heatmap_ppm() {
+        let chip8 = run_rom();
+        let coverage = chip8.coverage().unwrap();
+        let header = b"P6\n64 64\n255\n";
+        let image = coverage.heatmap_ppm();
+        assert!(image.starts_with(header));
+        assert_eq!(image.len(), header.len() + HEATMAP_SIZE * HEATMAP_SIZE * 3);
+        assert_eq!(image[header.len() + 0x20C * 3..][..3], [0, 0xFF, 0]);
+    }
+
+    #[test]
+    // Tests that labels and source lines are told apart and comments are skipped
+    fn test_parse_symbols() {
+        let symbols = SymbolFile::parse(SYMBOLS).unwrap();
+        assert_eq!(symbols.labels(0x206).collect::<Vec<_>>(), ["loop"]);
+        assert_eq!(symbols.labels(0x20C).collect::<Vec<_>>(), ["done"]);
+        assert_eq!(symbols.labels(0x202).count(), 0);
+        assert_eq!(
+            symbols.source_line(0x204),
+            Some(&SourceLine {
+                file: "count.8o".to_owned(),
+                line: 3,
+            })
+        );
+        assert_eq!(symbols.source_line(0x20C), None);
+        let symbols = SymbolFile::parse("$300 sprite # data\n20A data.8o:x").unwrap();
+        assert_eq!(symbols.labels(0x300).collect::<Vec<_>>(), ["sprite"]);
+        // a location without a line number is a label
+        assert_eq!(symbols.labels(0x20A).collect::<Vec<_>>(), ["data.8o:x"]);
+    }
+
+    #[test]
+    // Tests that bad symbol file lines are reported with their line number
+    fn test_invalid_symbol_lines() {
+        for (text, message) in [
+            (
+                "0x200 main\nnope",
+                "line 2: expected an address and a symbol",
+            ),
+            (
+                "0x200 main\n\n0xZZ0 loop",
+                "line 3: expected a hex address below 0x1000",
+            ),
+            (
+                "0x1000 past_the_end",
+                "line 1: expected a hex address below 0x1000",
+            ),
+        ] {
+            assert!(matches!(
+                SymbolFile::parse(text),
+                Err(Chip8Error::InvalidSymbolFile(error)) if error == message
+            ));
+        }
+    }
+
+    #[test]
+    // Tests that mapped lines and labels are reported against their source file and the rest
+    // against the ROM, with the address as the line
+    fn test_lcov_symbol_mapping() {
+        let chip8 = run_rom();
+        let symbols = SymbolFile::parse(SYMBOLS).unwrap();
+        let report = chip8.coverage().unwrap().to_lcov(&symbols, "count.ch8");
+        assert_eq!(
+            report,
+            "TN:\nSF:count.8o\nFN:2,main\nFN:5,loop\nFNDA:1,main\nFNDA:2,loop\nFNF:2\nFNH:2\n\
+             DA:2,1\nDA:3,1\nDA:5,2\nLF:3\nLH:3\nend_of_record\n\
+             TN:\nSF:count.ch8\nFN:524,done\nFNDA:4,done\nFNF:1\nFNH:1\n\
+             DA:520,2\nDA:522,1\nDA:524,4\nLF:3\nLH:3\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    // Tests that without symbols every executed address is a line of the ROM
+    fn test_lcov_without_symbols() {
+        let chip8 = run_rom();
+        let report = chip8
+            .coverage()
+            .unwrap()
+            .to_lcov(&SymbolFile::default(), "count.ch8");
+        assert!(report.starts_with("TN:\nSF:count.ch8\nFNF:0\nFNH:0\nDA:512,1\n"));
+        assert!(report.ends_with("DA:524,4\nLF:7\nLH:7\nend_of_record\n"));
+    }
+
+    #[test]
+    // Tests that the counters survive a reset until they are cleared
+    fn test_reset_and_clear() {
+        let mut chip8 = run_rom();
+        chip8.reset();
+        assert_eq!(chip8.coverage().unwrap().executions()[0x200], 1);
+        chip8.clear_coverage();
+        assert_eq!(chip8.coverage().unwrap().executions()[0x200], 0);
+        assert!(chip8.coverage().is_some());
+    }
+}
@@ -0,0 +1,327 @@
+//! Breakpoints and stepping.
+//!
+//! Breakpoints stop the CPU before it runs the instruction at an address; watchpoints (see
+//! `bus::Watchpoint`) stop it before an instruction that touches memory. `run_until_break` runs
+//! until either fires, `step_over` runs a 2NNN call as one step and `step_out` runs until the current
+//! subroutine returns. Every run takes a cycle budget so a frontend can spread it over frames.
+//! Breakpoints survive `reset`.
+
+use crate::bus::Access;
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+use crate::halt::RunStatus;
+use crate::instruction::Instruction;
+
+/// This enum lists why a debugger run stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    /// The program counter reached a breakpoint, which has not run yet.
+    Breakpoint(u16),
+    /// A watchpoint caught the instruction at the program counter accessing the address; the
+    /// instruction has not run yet.
+    Watchpoint(Access, u16),
+    /// The step over or step out finished.
+    Stepped,
+    /// The program jumps to itself or loops without changing any state.
+    Halted,
+    /// The cycle budget ran out first.
+    CycleLimit,
+}
+
+/// This struct reports the end of a debugger run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Stop {
+    /// This field stores why the run stopped.
+    pub reason: StopReason,
+    /// This field stores how many cycles ran.
+    pub cycles: u32,
+}
+
+impl Chip8Sys {
+    /// This function adds a breakpoint.
+    /// Arguments:
+    /// - address: The address of the instruction to stop at.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn add_breakpoint(&mut self, address: u16) -> &mut Self {
+        if let Err(index) = self.breakpoints.binary_search(&address) {
+            self.breakpoints.insert(index, address);
+        }
+        self
+    }
+
+    /// This function removes a breakpoint.
+    /// Arguments:
+    /// - address: The address of the breakpoint.
+    ///
+    /// Returns: True if there was a breakpoint at the address.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        match self.breakpoints.binary_search(&address) {
+            Ok(index) => {
+                self.breakpoints.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// This function removes every breakpoint.
+    /// Arguments: none.
+    /// Returns: The updated Chip-8 system.
+    pub fn clear_breakpoints(&mut self) -> &mut Self {
+        self.breakpoints.clear();
+        self
+    }
+
+    /// This function returns the breakpoints.
+    /// Arguments: none.
+    /// Returns: The breakpoint addresses in ascending order.
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints
+    }
+
+    /// This function runs until a breakpoint or watchpoint fires or the program halts. Neither
+    /// fires on the first instruction, so calling it again continues from where the last run
+    /// stopped.
+    /// Arguments:
+    /// - max_cycles: The most cycles to run.
+    ///
+    /// Returns: Where and why the run stopped, or the error an instruction raised.
+    pub fn run_until_break(&mut self, max_cycles: u32) -> Result<Stop, Chip8Error> {
+        self.run_until(max_cycles, |_| false)
+    }
+
+    /// This function runs one instruction, or a whole subroutine when the instruction is a 2NNN
+    /// call, stopping early at breakpoints and watchpoints inside it.
+    /// Arguments:
+    /// - max_cycles: The most cycles to run.
+    ///
+    /// Returns: Where and why the run stopped, or the error an instruction raised.
+    pub fn step_over(&mut self, max_cycles: u32) -> Result<Stop, Chip8Error> {
+        let (pc, mask) = (self.program_counter as usize, self.memory.size() - 1);
+        let opcode = u16::from_be_bytes([self.memory[pc & mask], self.memory[(pc + 1) & mask]]);
+        let (return_address, stack_pointer) = (self.program_counter + 2, self.stack_pointer);
+        match Instruction::decode(opcode, self.platform) {
+            Instruction::Call { .. } => self.run_until(max_cycles, |chip8| {
+                chip8.program_counter == return_address && chip8.stack_pointer == stack_pointer
+            }),
+            _ => self.run_until(max_cycles.min(1), |_| true),
+        }
+    }
+
+    /// This function runs until the current subroutine returns, stopping early at breakpoints
+    /// and watchpoints. Outside a subroutine it runs like `run_until_break`.
+    /// Arguments:
+    /// - max_cycles: The most cycles to run.
+    ///
+    /// Returns: Where and why the run stopped, or the error an instruction raised.
+    pub fn step_out(&mut self, max_cycles: u32) -> Result<Stop, Chip8Error> {
+        let stack_pointer = self.stack_pointer;
+        self.run_until(max_cycles, |chip8| chip8.stack_pointer < stack_pointer)
+    }
+
+    // Runs until is_done holds after an instruction, a breakpoint or watchpoint fires, the
+    // program halts or the budget runs out
+    fn run_until(
+        &mut self,
+        max_cycles: u32,
+        mut is_done: impl FnMut(&Chip8Sys) -> bool,
+    ) -> Result<Stop, Chip8Error> {
+        for cycle in 0..max_cycles {
+            let stop = |reason| {
+                Ok(Stop {
+                    reason,
+                    cycles: cycle,
+                })
+            };
+            if cycle > 0 && self.breakpoints.contains(&self.program_counter) {
+                return stop(StopReason::Breakpoint(self.program_counter));
+            }
+            // a stopped instruction has not run, so the first one may be the one a watchpoint
+            // stopped last time
+            self.memory.is_resuming = cycle == 0;
+            let result = self.run();
+            self.memory.is_resuming = false;
+            match result {
+                Ok(()) => {}
+                Err(Chip8Error::Watchpoint(access, address)) => {
+                    return stop(StopReason::Watchpoint(access, address));
+                }
+                Err(error) => return Err(error),
+            }
+            let stop = |reason| {
+                Ok(Stop {
+                    reason,
+                    cycles: cycle + 1,
+                })
+            };
+            if is_done(self) {
+                return stop(StopReason::Stepped);
+            }
+            if self.status() == RunStatus::Halted {
+                return stop(StopReason::Halted);
+            }
+        }
+        Ok(Stop {
+            reason: StopReason::CycleLimit,
+            cycles: max_cycles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Watchpoint;
+
+    // 2206 6001 1204 6102 F055 00EE: call a subroutine that stores V0 at I, then halt
+    const PROGRAM: [u8; 12] = [
+        0x22, 0x06, 0x60, 0x01, 0x12, 0x04, 0x61, 0x02, 0xF0, 0x55, 0x00, 0xEE,
+    ];
+
+    fn program() -> Chip8Sys {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&PROGRAM);
+        chip8.register_i = 0x300;
+        chip8
+    }
+
+    #[test]
+    // Tests that breakpoints stop before the instruction, continue and survive a reset
+    fn test_breakpoints() {
+        let mut chip8 = program();
+        chip8.add_breakpoint(0x208).add_breakpoint(0x208);
+        assert_eq!(chip8.breakpoints(), [0x208]);
+        assert_eq!(
+            chip8.run_until_break(100).unwrap(),
+            Stop {
+                reason: StopReason::Breakpoint(0x208),
+                cycles: 2
+            }
+        );
+        assert_eq!(chip8.register[1], 2);
+        assert_eq!(
+            chip8.run_until_break(100).unwrap().reason,
+            StopReason::Halted
+        );
+        assert_eq!(
+            chip8.run_until_break(0).unwrap().reason,
+            StopReason::CycleLimit
+        );
+        chip8.reset();
+        assert!(chip8.remove_breakpoint(0x208));
+        assert!(!chip8.remove_breakpoint(0x208));
+    }
+
+    #[test]
+    // Tests that step over runs a whole call and step out finishes the subroutine
+    fn test_step_over_and_out() {
+        let mut chip8 = program();
+        assert_eq!(
+            chip8.step_over(100).unwrap(),
+            Stop {
+                reason: StopReason::Stepped,
+                cycles: 4
+            }
+        );
+        assert_eq!(chip8.program_counter, 0x202);
+        assert_eq!(chip8.step_over(100).unwrap().cycles, 1);
+        assert_eq!(chip8.register[0], 1);
+
+        let mut chip8 = program();
+        chip8.tick(2).unwrap();
+        assert_eq!(chip8.step_out(100).unwrap().reason, StopReason::Stepped);
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+
+    #[test]
+    // Tests that a watchpoint stops a run and reports the access
+    fn test_watchpoint_stop() {
+        let mut chip8 = program();
+        chip8
+            .memory
+            .add_hook(Watchpoint::new(0x300..=0x300, Access::Write));
+        assert_eq!(
+            chip8.run_until_break(100).unwrap(),
+            Stop {
+                reason: StopReason::Watchpoint(Access::Write, 0x300),
+                cycles: 2
+            }
+        );
+    }
+
+    #[test]
+    // Tests that a run resumed from a write watchpoint runs the store once, from where it stopped
+    fn test_resume_after_write_watchpoint() {
+        let mut chip8 = program();
+        chip8.register[0] = 0x42;
+        chip8
+            .memory
+            .add_hook(Watchpoint::new(0x300..=0x300, Access::Write));
+        chip8.run_until_break(100).unwrap();
+        assert_eq!(chip8.program_counter, 0x208);
+        assert_eq!(chip8.register_i, 0x300);
+        assert_eq!(chip8.register[1], 2);
+        assert_eq!(chip8.memory[0x300], 0);
+        assert_eq!(
+            chip8.step_over(100).unwrap(),
+            Stop {
+                reason: StopReason::Stepped,
+                cycles: 1
+            }
+        );
+        assert_eq!(chip8.program_counter, 0x20A);
+        assert_eq!(chip8.register_i, 0x301);
+        assert_eq!(chip8.register[..2], [0x42, 2]);
+        assert_eq!(chip8.memory[0x300..0x302], [0x42, 0]);
+        assert_eq!(
+            chip8.run_until_break(100).unwrap().reason,
+            StopReason::Halted
+        );
+        assert_eq!(chip8.register[0], 1);
+    }
+
+    #[test]
+    // Tests that an instruction stopped by a watchpoint and resumed is counted once by coverage
+    fn test_resume_counts_once() {
+        // A300 F055: store V0 at 0x300
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0xA3, 0x00, 0xF0, 0x55]);
+        chip8.set_coverage(true);
+        chip8
+            .memory
+            .add_hook(Watchpoint::new(0x300..=0x300, Access::Write));
+        assert_eq!(
+            chip8.run_until_break(100).unwrap().reason,
+            StopReason::Watchpoint(Access::Write, 0x300)
+        );
+        assert_eq!(chip8.coverage().unwrap().executions()[0x202], 0);
+        chip8.run_until_break(1).unwrap();
+        let coverage = chip8.coverage().unwrap();
+        assert_eq!(coverage.executions()[0x202], 1);
+        assert_eq!(coverage.writes()[0x300], 1);
+    }
+
+    #[test]
+    // Tests that an execute watchpoint stops before the instruction and a resumed run gets past it
+    fn test_resume_after_execute_watchpoint() {
+        let mut chip8 = program();
+        chip8
+            .memory
+            .add_hook(Watchpoint::new(0x206..=0x206, Access::Execute));
+        assert_eq!(
+            chip8.run_until_break(100).unwrap(),
+            Stop {
+                reason: StopReason::Watchpoint(Access::Execute, 0x206),
+                cycles: 1
+            }
+        );
+        assert_eq!(chip8.program_counter, 0x206);
+        assert_eq!(chip8.register[1], 0);
+        assert_eq!(
+            chip8.run_until_break(100).unwrap().reason,
+            StopReason::Halted
+        );
+        assert_eq!(chip8.register[1], 2);
+    }
+}
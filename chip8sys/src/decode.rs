@@ -1,20 +1,52 @@
 use crate::chip8::{Chip8Sys, TimerMode};
 use crate::chip8error::Chip8Error;
-use getrandom;
+use crate::platform::Platform;
+use crate::timing::{self, TimingModel};
 
 impl Chip8Sys {
     // This will run the next command in program_counter is pointing to in Chip8Sys.memory
     pub fn run(&mut self) -> Result<(), Chip8Error> {
         // check to see if we're waiting for a key press
         if self.check_waiting() {
+            // the VIP sits in its keypad loop until the next frame
+            self.idle_until_interrupt();
             return Ok(());
         }
-        // Delay and sound timers
-        if self.timer_mode() == TimerMode::Cycle {
+        // A failed instruction, including one a memory hook stopped, leaves the CPU as it was so
+        // it can be run again. Instructions make all their bus accesses before changing anything
+        // else, so only the timers and the program counter need putting back here; the bus drops
+        // the instruction's coverage counts and write guard marks itself.
+        let timers = (
+            self.delay_timer,
+            self.sound_timer,
+            self.dt_cycle_ct,
+            self.is_playing_sound,
+        );
+        let program_counter = self.program_counter;
+        self.memory.begin_instruction();
+        let result = self.execute();
+        self.memory.end_instruction(result.is_ok());
+        if result.is_err() {
+            (
+                self.delay_timer,
+                self.sound_timer,
+                self.dt_cycle_ct,
+                self.is_playing_sound,
+            ) = timers;
+            self.program_counter = program_counter;
+        }
+        result
+    }
+
+    // Runs one instruction: ticks the timers, then fetches, decodes and executes
+    fn execute(&mut self) -> Result<(), Chip8Error> {
+        // Delay and sound timers, the VIP timing model ticks them from its 60Hz interrupt instead
+        if self.timer_mode() == TimerMode::Cycle && self.timing_model() == TimingModel::Instruction
+        {
             // Only decrement delay time if it's been 6 cycles to match original slow clock of
             // chip-8
             if self.delay_timer > 0 {
-                if self.dt_cycle_ct % 6 == 0 {
+                if self.dt_cycle_ct.is_multiple_of(6) {
                     self.delay_timer -= 1;
                 }
                 self.dt_cycle_ct += 1;
@@ -27,12 +59,17 @@ impl Chip8Sys {
             }
         }
         // fetch section
-        let instruction = self.memory[self.program_counter as usize];
+        let pc_before = self.program_counter;
+        #[cfg(feature = "debugger")]
+        let clock_before = self.vip_clock;
+        let opcode = self.memory.fetch(pc_before)?;
+        let cycles = timing::instruction_cycles(self, opcode);
+        let [instruction, instruction_low] = opcode.to_be_bytes();
 
         let a: u8 = (0xF0 & instruction) >> 0x4;
         let b: u8 = 0x0F & instruction;
 
-        let instruction = self.memory[(self.program_counter + 1) as usize];
+        let instruction = instruction_low;
 
         let c: u8 = (0xF0 & instruction) >> 0x4;
         let d: u8 = 0x0F & instruction;
@@ -52,9 +89,17 @@ impl Chip8Sys {
                 // println!("Hit 0x0");
                 match instruction {
                     // Clear display
-                    0x00E0 => self.frame_buffer = [0x00; 256],
+                    0xE0 if b == 0x0 => self.clear_display(),
+                    // Hi-res CHIP-8 clears its 64x64 display with 0x0230
+                    0x30 if b == 0x2 && self.platform() == Platform::HiresChip8 => {
+                        self.clear_display()
+                    }
+                    // CHIP-8X cycles the background colour with 0x02A0
+                    0xA0 if b == 0x2 && self.platform() == Platform::Chip8X => {
+                        self.cycle_background_color()
+                    }
                     // Return from Subroutine
-                    0x00EE => {
+                    0xEE if b == 0x0 => {
                         self.program_counter = self.stack[self.stack_pointer as usize];
                         // I don't think this is necessary but I can't pop on an array in rust.
                         self.stack[self.stack_pointer as usize] = 0;
@@ -86,6 +131,13 @@ impl Chip8Sys {
                     self.program_counter += 2;
                 }
             }
+            0x5 if d == 1 && self.platform() == Platform::Chip8X => {
+                // CHIP-8X 0x5XY1 - add reg[Y] to reg[X] one nibble at a time, each modulo 8
+                let reg_x = self.register[b as usize];
+                let reg_y = self.register[c as usize];
+                self.register[b as usize] = ((reg_x & 0x70) + (reg_y & 0x70)) & 0x70
+                    | ((reg_x & 0x7) + (reg_y & 0x7)) & 0x7;
+            }
             0x5 => {
                 // println!("Hit 0x5 - Skip if reg[X] == reg[Y]");
                 if self.register[b as usize] == self.register[c as usize] {
@@ -112,24 +164,21 @@ impl Chip8Sys {
                 }
                 1 => {
                     // println!("Hit 0x8XY1 - Set reg[X] to reg[X] OR reg[Y]");
-                    self.register[b as usize] =
-                        self.register[b as usize] | self.register[c as usize];
+                    self.register[b as usize] |= self.register[c as usize];
                     if self.is_register_f_reset() {
                         self.register[0xF] = 0;
                     }
                 }
                 2 => {
                     // println!("Hit 0x8XY2 - Set reg[X] to reg[X] AND reg[Y]");
-                    self.register[b as usize] =
-                        self.register[b as usize] & self.register[c as usize];
+                    self.register[b as usize] &= self.register[c as usize];
                     if self.is_register_f_reset() {
                         self.register[0xF] = 0;
                     }
                 }
                 3 => {
                     // println!("Hit 0x8XY3 - Set reg[X] to reg[X] XOR reg[Y]");
-                    self.register[b as usize] =
-                        self.register[b as usize] ^ self.register[c as usize];
+                    self.register[b as usize] ^= self.register[c as usize];
                     if self.is_register_f_reset() {
                         self.register[0xF] = 0;
                     }
@@ -153,7 +202,7 @@ impl Chip8Sys {
                         // calculate the two's compliment of reg[x]
                         let two_comp = (!self.register[c as usize]) as u16 + 1;
                         self.register[b as usize] =
-                            ((self.register[b as usize] as u16 + two_comp as u16) & 0xFF) as u8;
+                            ((self.register[b as usize] as u16 + two_comp) & 0xFF) as u8;
                         self.register[0xF] = 0;
                     } else {
                         // otherwise we can just do it normal and set VF
@@ -184,7 +233,7 @@ impl Chip8Sys {
                         // calculate the two's compliment of reg[x]
                         let two_comp = (!self.register[b as usize]) as u16 + 1;
                         self.register[b as usize] =
-                            ((self.register[c as usize] as u16 + two_comp as u16) & 0xFF) as u8;
+                            ((self.register[c as usize] as u16 + two_comp) & 0xFF) as u8;
                         self.register[0xF] = 0;
                     } else {
                         // otherwise we can just do it normal and set VF
@@ -221,21 +270,25 @@ impl Chip8Sys {
                 self.register_i = Chip8Sys::nnn(b, c, d);
                 // println!("reg I = {:02X}", self.register_i);
             }
+            0xB if self.platform() == Platform::Chip8X => {
+                // CHIP-8X replaces the offset jump with the colour instructions
+                if d == 0 {
+                    self.set_zone_colors(b, c);
+                } else {
+                    self.set_sprite_colors(b, c, d);
+                }
+            }
             0xB => {
                 // 0xBNNN
                 self.program_counter = self.register[0] as u16 + Chip8Sys::nnn(b, c, d);
             }
             0xC => {
                 // println!("Hit 0xCXNN - Set Vx to Random bite then AND with NN");
-                let rng = match getrandom::u32() {
-                    Ok(r) => r,
-                    Err(e) => return Err(Chip8Error::IssueGeneratingRandomNum(e)),
-                };
-                self.register[b as usize] = ((rng & 0xFF) as u8) & Chip8Sys::nn(c, d);
+                self.register[b as usize] = self.random_byte()? & Chip8Sys::nn(c, d);
             }
             0xD => {
                 // println!("Hit 0xD - Draw");
-                self.draw(b, c, d);
+                self.draw(b, c, d)?;
             }
             0xE => {
                 // println!("Hit 0xE - key press");
@@ -265,6 +318,10 @@ impl Chip8Sys {
                             self.program_counter += 2;
                         }
                     }
+                    // CHIP-8X 0xEXF2 & 0xEXF5 read the second keypad, which isn't emulated so
+                    // its keys are never pressed
+                    0xF2 if self.platform() == Platform::Chip8X => (),
+                    0xF5 if self.platform() == Platform::Chip8X => self.program_counter += 2,
                     _ => return Err(Chip8Error::Invalid0xENNN(c, d)),
                 }
             }
@@ -277,10 +334,7 @@ impl Chip8Sys {
                     }
                     0x0A => {
                         // println!(" - Wait for key press");
-                        match self.wait(b) {
-                            Ok(k) => k,
-                            Err(e) => return Err(e),
-                        }
+                        self.wait(b)?
                     }
                     0x15 => {
                         // // println!(" - Set Delay Timer with Reg[x]'s value");
@@ -294,111 +348,132 @@ impl Chip8Sys {
                     }
                     0x1E => {
                         // println!(" - Set I to I + Reg[x]");
-                        self.register_i =
-                            (self.register_i + self.register[b as usize] as u16) & 0xFFFF;
+                        self.register_i += self.register[b as usize] as u16;
                     }
                     0x29 => {
                         // println!(" - Set I to location of sprite for digit Reg[x]");
-                        self.register_i = 0x050 + b as u16 * 5;
+                        self.register_i = self.font.small_glyph_address(self.register[b as usize]);
+                    }
+                    // SUPER-CHIP 0xFX30 points at the big glyph, when the font has them
+                    0x30 => {
+                        let digit = self.register[b as usize];
+                        let Some(address) = self.font.big_glyph_address(digit) else {
+                            return Err(Chip8Error::Invalid0xFNNN(c, d));
+                        };
+                        self.register_i = address;
                     }
                     0x33 => {
                         // println!(" - store the 100s, 10s, and 1s place of reg[x] into memory location I, I+1, and I+2 respectively");
                         let value = self.register[b as usize];
-                        let places = (
-                            (value as f32 / 100.).floor() as u8,
-                            (value as f32 / 10.).floor() as u8,
-                            (value as f32 / 1.).floor() as u8,
-                        );
-                        self.memory[self.register_i as usize] = places.0;
-                        self.memory[self.register_i as usize + 1] = places.1 - places.0 * 10;
-                        self.memory[self.register_i as usize + 2] = places.2 - places.1 * 10;
+                        let places = (value / 100, value / 10, value);
+                        let digits = [places.0, places.1 - places.0 * 10, places.2 - places.1 * 10];
+                        self.memory.write_bytes(self.register_i, &digits)?;
                     }
                     0x55 => {
                         // println!(" - store registers reg[0] to reg[x] to memory starting at the location stored in register I");
-                        for count in 0..=b {
-                            self.memory[(self.register_i + count as u16) as usize] =
-                                self.register[count as usize];
-                        }
+                        self.memory
+                            .write_bytes(self.register_i, &self.register[..=b as usize])?;
                         if self.is_inc_index() {
                             self.register_i = self.register_i + b as u16 + 1;
                         }
                     }
                     0x65 => {
                         // println!(" - read register reg[0] to reg[x] out of memory starting at the location stored in register I");
-                        for count in 0..=b {
-                            self.register[count as usize] =
-                                self.memory[(self.register_i + count as u16) as usize];
-                        }
+                        // read everything first so a hook error leaves the registers alone
+                        let mut values = [0; 16];
+                        let values = &mut values[..=b as usize];
+                        self.memory.read_bytes(self.register_i, values)?;
+                        self.register[..=b as usize].copy_from_slice(values);
                         if self.is_inc_index() {
                             self.register_i = self.register_i + b as u16 + 1;
                         }
                     }
+                    // CHIP-8X 0xFXF8 & 0xFXFB talk to the I/O port, which has nothing attached
+                    0xF8 | 0xFB if self.platform() == Platform::Chip8X => (),
                     _ => return Err(Chip8Error::Invalid0xFNNN(c, d)),
                 }
             }
             _ => return Err(Chip8Error::InvalidFirstByte(a)),
         }
+        let cycles = self.executed_cycles(opcode, cycles, pc_before);
+        self.charge_cycles(cycles);
+        self.detect_halt(pc_before, opcode);
+        #[cfg(feature = "debugger")]
+        self.profile_instruction(opcode, cycles, clock_before);
         Ok(())
     }
     // Helper function to handle the Draw command logic 0xDXYN
-    fn draw(&mut self, x: u8, y: u8, n: u8) {
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Chip8Error> {
         // println!("Drawing {:02X} {:02X} {:02X}", x, y, n);
         // get the x and y location out of the x and y registers
-        let x_loc;
-        let mut y_loc;
+        let width = self.display_width();
+        let height = self.display_height();
+        // number of u8 chunks that make up one row of the frame_buffer
+        let row_chunks = width / 8;
         //if self.is_wrap_draw() {
-        x_loc = self.register[x as usize] % 64;
-        y_loc = self.register[y as usize] % 32;
+        let x_loc = self.register[x as usize] as usize % width;
+        let mut y_loc = self.register[y as usize] as usize % height;
         //} else {
         //    x_loc = self.register[x as usize] & 0b0111_1111;
         //    y_loc = self.register[y as usize] & 0b0011_1111;
         // }
         // println!("at {}, {}", x_loc, y_loc);
-        // pull the sprite's location in memory out using register I as the starting location
-        let mut sprite_location = self.register_i;
-        for _ in 0..n {
-            // get the sprite's pixels from memory
-            let sprite_pxs = self.memory[sprite_location as usize];
+        // pull the sprite out of memory using register I as the starting location, before
+        // touching the display; rows clipped off the bottom are never read
+        let rows = if self.is_wrap_draw() {
+            n as usize
+        } else {
+            (n as usize).min(height - y_loc)
+        };
+        let mut sprite = [0; 15];
+        self.memory
+            .read_bytes(self.register_i, &mut sprite[..rows])?;
+        if self.timing_model() == TimingModel::CosmacVip {
+            // the VIP interpreter waits for the display interrupt before drawing
+            self.idle_until_interrupt();
+        }
+        for &sprite_pxs in &sprite[..rows] {
             // calculate the u8 (chunk) of the frame_buffer we'll be updating
-            let fb_chunk_index = (y_loc * 8) + (x_loc as f32 / 8.).floor() as u8;
+            let fb_chunk_index = (y_loc * row_chunks) + x_loc / 8;
             // calculate the overflow (next) chunk of the frame frame_buffer
-            let fb_chunk_index_next;
-            let is_edge_x = (x_loc as f32 / 8.).floor() == 7.;
-            if is_edge_x {
+            let is_edge_x = x_loc / 8 == row_chunks - 1;
+            let fb_chunk_index_next = if is_edge_x {
                 // In the case of drawing at the edge we want to wrap around on the same row
-                fb_chunk_index_next = fb_chunk_index - 7;
+                fb_chunk_index + 1 - row_chunks
             } else {
-                fb_chunk_index_next = fb_chunk_index + 1;
-            }
+                fb_chunk_index + 1
+            };
             // Calculate the offset based on x's location
             let offset = x_loc % 8;
-            let fb_chunk_index_original = self.frame_buffer[fb_chunk_index as usize];
-            let fb_chunk_index_next_original = self.frame_buffer[fb_chunk_index_next as usize];
+            let fb_chunk_index_original = self.frame_buffer[fb_chunk_index];
+            let fb_chunk_index_next_original = self.frame_buffer[fb_chunk_index_next];
             // Draw the bits using xor
-            self.frame_buffer[fb_chunk_index as usize] ^= sprite_pxs >> offset;
+            self.frame_buffer[fb_chunk_index] ^= sprite_pxs >> offset;
             // Update the flag if fb was 1 and became 0
-            let flag_for_index = !(self.frame_buffer[fb_chunk_index as usize]
-                & fb_chunk_index_original)
+            let flag_for_index = !(self.frame_buffer[fb_chunk_index] & fb_chunk_index_original)
                 & fb_chunk_index_original;
             // println!("flag_for_index: ");
             // println!("original: {:08b}", fb_chunk_index_original);
-            // println!("new     : {:08b}", self.frame_buffer[fb_chunk_index as usize]);
+            // println!("new     : {:08b}", self.frame_buffer[fb_chunk_index]);
             // println!("flag    : {:08b}", flag_for_index);
             // default to false so we don't mess with the flag during clipping
             let mut flag_for_next = 0;
             // Only do this if we're wrapping or we're clipping but not at the edge
             // println!("clipping {}, is_edge_x {}",!self.is_wrap_draw(), !is_edge_x);
             if self.is_wrap_draw() | (!self.is_wrap_draw() & !is_edge_x) {
-                self.frame_buffer[fb_chunk_index_next as usize] ^=
+                self.frame_buffer[fb_chunk_index_next] ^=
                     (((sprite_pxs as u16) << (8 - offset)) & 0xFF) as u8;
-                flag_for_next = !(self.frame_buffer[fb_chunk_index_next as usize]
+                flag_for_next = !(self.frame_buffer[fb_chunk_index_next]
                     & fb_chunk_index_next_original)
                     & fb_chunk_index_next_original;
             }
             // println!("flag_for_next: ");
             // println!("original: {:08b}", fb_chunk_index_next_original);
-            // println!("new     : {:08b}", self.frame_buffer[fb_chunk_index_next as usize]);
+            // println!("new     : {:08b}", self.frame_buffer[fb_chunk_index_next]);
             // println!("flag    : {:08b}", flag_for_next);
+            // empty or clipped sprite rows leave the display clean
+            self.is_display_dirty |= self.frame_buffer[fb_chunk_index] != fb_chunk_index_original
+                || self.frame_buffer[fb_chunk_index_next] != fb_chunk_index_next_original;
             if (flag_for_index != 0) | (flag_for_next != 0) {
                 // println!("VF SET: index: {}, next: {}", flag_for_index, flag_for_next);
                 self.register[0xF] = 1;
@@ -407,20 +482,20 @@ impl Chip8Sys {
             y_loc += 1;
 
             // If we just drew on the last line of the screen start drawing at the top
-            if y_loc == 32 {
+            if y_loc == height {
                 // if we're clipping just stop drawing
                 if !self.is_wrap_draw() {
                     break;
                 }
                 y_loc = 0;
             }
-            sprite_location += 1;
         }
         /*
            for byte in self.frame_buffer.iter() {
            // println!("{:08b}",byte);
            }
         // */
+        Ok(())
     }
     // helper function to get the last 3 nibbles of a command
     // commands coming in as 0x?NNN will use this
@@ -435,9 +510,12 @@ impl Chip8Sys {
 }
 
 #[cfg(test)]
+// the original tests discard run()'s unit result with `let _ =`
+#[allow(clippy::let_unit_value)]
 pub mod test {
 
     use super::*;
+    use crate::chip8::{Chip8Quirks, FRAMEBUFFER_MAX_LEN};
 
     #[test]
     // Test that the nibbles going to nn() build a byte of NN
@@ -456,9 +534,51 @@ pub mod test {
     // Tests clear screen; 0x00E0
     fn test_clear_screen() {
         let mut chip8 = single_instruction_chip_8(0x00E0);
-        chip8.frame_buffer = [0xAA; 256];
+        chip8.frame_buffer = [0xAA; FRAMEBUFFER_MAX_LEN];
         let _ = chip8.run().unwrap();
-        assert_eq!([0x00; 256], chip8.frame_buffer);
+        assert_eq!([0x00; FRAMEBUFFER_MAX_LEN], chip8.frame_buffer);
+    }
+
+    #[test]
+    // Tests that 0NNN calls with E0 or EE as their low byte are ignored rather than run as
+    // 00E0 and 00EE, the way Instruction::decode reads them
+    fn test_sys_call_ignored() {
+        let mut chip8 = single_instruction_chip_8(0x01E0);
+        chip8.frame_buffer = [0xAA; FRAMEBUFFER_MAX_LEN];
+        chip8.run().unwrap();
+        assert_eq!([0xAA; FRAMEBUFFER_MAX_LEN], chip8.frame_buffer);
+
+        let mut chip8 = single_instruction_chip_8(0x03EE);
+        chip8.stack_pointer = 1;
+        chip8.run().unwrap();
+        assert_eq!(chip8.stack_pointer, 1);
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+
+    #[test]
+    // Tests that only DXYN and 00E0 calls that change pixels mark the display dirty
+    fn test_display_dirty() {
+        let mut chip8 = single_instruction_chip_8(0x00E0);
+        assert!(chip8.is_display_dirty());
+        chip8.clear_display_dirty();
+        chip8.run().unwrap();
+        assert!(!chip8.is_display_dirty());
+
+        // I points at 0x000, which holds no sprite data
+        let mut chip8 = single_instruction_chip_8(0xD001);
+        chip8.clear_display_dirty();
+        chip8.run().unwrap();
+        assert!(!chip8.is_display_dirty());
+        chip8.program_counter = 0x200;
+        chip8.register_i = 0x050;
+        chip8.run().unwrap();
+        assert!(chip8.is_display_dirty());
+
+        chip8.clear_display_dirty();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0x00, 0xE0]);
+        chip8.program_counter = 0x200;
+        chip8.run().unwrap();
+        assert!(chip8.is_display_dirty());
     }
 
     #[test]
@@ -1150,21 +1270,8 @@ pub mod test {
         // set register I to reference the sprite for 0 in memory 0x050
         chip8.register_i = 0x050;
         let _ = chip8.run().unwrap();
-        // make the expected frame empty
-        let mut expected_frame_buffer = [0; 256];
-        // manually load the 0 sprite into the right spots
-        expected_frame_buffer[65] = 0b0000_0001;
-        expected_frame_buffer[66] = 0b1110_0000;
-        expected_frame_buffer[73] = 0b0000_0001;
-        expected_frame_buffer[74] = 0b0010_0000;
-        expected_frame_buffer[81] = 0b0000_0001;
-        expected_frame_buffer[82] = 0b0010_0000;
-        expected_frame_buffer[89] = 0b0000_0001;
-        expected_frame_buffer[90] = 0b0010_0000;
-        expected_frame_buffer[97] = 0b0000_0001;
-        expected_frame_buffer[98] = 0b1110_0000;
-
-        assert_eq!(chip8.frame_buffer, expected_frame_buffer);
+        // the 0 sprite should be drawn at (15, 8)
+        crate::snapshot::assert_frame_snapshot(&chip8, "draw_zero_sprite");
     }
     #[test]
     #[should_panic]
@@ -1267,7 +1374,7 @@ pub mod test {
     // Tests load vx with delay timer value; 0xFX07
     fn test_load_x_with_delay_timer() {
         let reg_x = 0xA;
-        let mut chip8 = single_instruction_chip_8(0xF000 | reg_x << 8 | 07);
+        let mut chip8 = single_instruction_chip_8(0xF000 | reg_x << 8 | 0x07);
         chip8.delay_timer = 50;
         let _ = chip8.run().unwrap();
         assert_eq!(
@@ -1289,11 +1396,13 @@ pub mod test {
     }
 
     #[test]
-    // Tests that if a key is pressed it is stored into register[x]; 0xFX0A
+    // Tests that with the COSMAC VIP quirks a key is stored into register[x] once it is pressed
+    // and released; 0xFX0A
     fn test_wait_for_key_press_store_key() {
         let reg_x = 0xA;
         let pressed_key = 0x8;
         let mut chip8 = single_instruction_chip_8(0xF000 | reg_x << 8 | 0x0A);
+        chip8.set_quirks(Chip8Quirks::cosmac_vip());
         let _ = chip8.run().unwrap();
         assert_eq!(
             chip8.register[reg_x as usize], 0,
@@ -1301,10 +1410,82 @@ pub mod test {
         );
         chip8.keys[pressed_key as usize] = true;
         let _ = chip8.run().unwrap();
+        assert!(
+            chip8.is_waiting_for_key(),
+            "Chip-8 0xFX0A should keep waiting while the key is held."
+        );
+        assert_eq!(chip8.waiting_register(), Some(reg_x as u8));
+        chip8.keys[pressed_key as usize] = false;
+        chip8.run().unwrap();
+        assert_eq!(
+            chip8.register[reg_x as usize], pressed_key,
+            "Chip-8 0xFX0A released key should have been stored in register[x]."
+        );
+    }
+
+    #[test]
+    // Tests that by default 0xFX0A completes on the press, while the key is still held
+    fn test_wait_for_key_press_only() {
+        let reg_x = 0xA;
+        let pressed_key = 0x8;
+        let mut chip8 = single_instruction_chip_8(0xF000 | reg_x << 8 | 0x0A);
+        assert!(!chip8.is_wait_for_release());
+        chip8.run().unwrap();
+        chip8.keys[pressed_key as usize] = true;
+        chip8.run().unwrap();
         assert_eq!(
             chip8.register[reg_x as usize], pressed_key,
             "Chip-8 0xFX0A pressed key should have been stored in register[x]."
         );
+        assert!(
+            !chip8.is_waiting_for_key(),
+            "Chip-8 0xFX0A should have stopped waiting."
+        );
+    }
+
+    #[test]
+    // Tests that a key held before 0xFX0A starts does not complete it
+    fn test_wait_for_key_press_ignores_held_key() {
+        let reg_x = 0xA;
+        let mut chip8 = single_instruction_chip_8(0xF000 | reg_x << 8 | 0x0A);
+        chip8.keys[0x3] = true;
+        chip8.run().unwrap();
+        chip8.run().unwrap();
+        assert!(
+            chip8.is_waiting_for_key(),
+            "Chip-8 0xFX0A should wait for a new press."
+        );
+        chip8.keys[0x3] = false;
+        chip8.run().unwrap();
+        assert!(
+            chip8.is_waiting_for_key(),
+            "Chip-8 0xFX0A should not complete on the release of a key held before it started."
+        );
+    }
+
+    #[test]
+    // Tests that 0xFX0A stores into VF and that a tap between instructions is not lost
+    fn test_wait_for_key_press_vf_and_tap() {
+        let pressed_key = 0x4;
+        let mut chip8 = single_instruction_chip_8(0xFF0A);
+        chip8.run().unwrap();
+        chip8
+            .key_down(pressed_key)
+            .unwrap()
+            .key_up(pressed_key)
+            .unwrap();
+        chip8.run().unwrap();
+        assert_eq!(
+            chip8.register[0xF], pressed_key,
+            "Chip-8 0xFF0A tapped key should have been stored in register[F]."
+        );
+        assert!(
+            matches!(
+                chip8.key_down(0x10),
+                Err(Chip8Error::InvalidKeypadKey(0x10))
+            ),
+            "Chip-8 key_down should reject keys above 0xF."
+        );
     }
 
     #[test]
@@ -1321,6 +1502,8 @@ pub mod test {
         chip8.keys[pressed_key as usize] = true;
         chip8.keys[(pressed_key + 2) as usize] = true;
         let _ = chip8.run().unwrap();
+        chip8.keys = [false; 16];
+        chip8.run().unwrap();
         assert_eq!(
             chip8.register[reg_x as usize], pressed_key,
             "Chip-8 0xFX0A the lower of the two pressed key should have been stored in register[x]."
@@ -1333,7 +1516,7 @@ pub mod test {
     // actually hitting the match and acting on it.
     fn test_wait_for_key_press_immediate_next_action() {
         let reg_x = 0xA;
-        let fill_screen = [0xAA; 256];
+        let fill_screen = [0xAA; FRAMEBUFFER_MAX_LEN];
         let pressed_key = 0x8;
         let mut chip8 = single_instruction_chip_8(0xF000 | reg_x << 8 | 0x0A);
         // set the second instruction to clear screen
@@ -1354,8 +1537,10 @@ pub mod test {
         );
         chip8.keys[pressed_key as usize] = true;
         let _ = chip8.run().unwrap();
+        chip8.keys[pressed_key as usize] = false;
+        chip8.run().unwrap();
         assert_eq!(
-            chip8.frame_buffer, [0; 256],
+            chip8.frame_buffer, [0; FRAMEBUFFER_MAX_LEN],
             "Chip-8 0xFX0A pressed key should have moved to the next instruction and cleared the screen."
         );
     }
@@ -1412,7 +1597,9 @@ pub mod test {
             0x96, 0x9B,
         ];
         for (count, loc) in sprite_locs.iter().enumerate() {
-            let mut chip8 = single_instruction_chip_8(0xF000 | (count as u16) << 8 | 0x29);
+            // F529: the digit is the value of V5
+            let mut chip8 = single_instruction_chip_8(0xF529);
+            chip8.register[5] = count as u8;
             let _ = chip8.run().unwrap();
             assert_eq!(
                 &chip8.register_i, loc,
@@ -1457,14 +1644,14 @@ pub mod test {
         let mut chip8 = single_instruction_chip_8(0xF000 | (reg_x as u16) << 8 | 0x55);
         // load up some values for register 0 to X
         for count in 0..=reg_x {
-            chip8.register[count as usize] = (val + count) & 0xFF;
+            chip8.register[count as usize] = val + count;
         }
         chip8.register_i = mem;
         let _ = chip8.run().unwrap();
         for count in 0..=reg_x {
             assert_eq!(
                 chip8.memory[(mem + count as u16) as usize],
-                (val + count) & 0xFF,
+                val + count,
                 "Chip-8 0xFX55 should have set register {:02X} to {:02X}",
                 count,
                 val + count
@@ -1481,14 +1668,14 @@ pub mod test {
         let mut chip8 = single_instruction_chip_8(0xF000 | (reg_x as u16) << 8 | 0x65);
         // load up some values for register 0 to X
         for count in 0..=reg_x {
-            chip8.memory[(mem + count as u16) as usize] = (val + count) & 0xFF;
+            chip8.memory[(mem + count as u16) as usize] = val + count;
         }
-        chip8.register_i = mem as u16;
+        chip8.register_i = mem;
         let _ = chip8.run().unwrap();
         for count in 0..=reg_x {
             assert_eq!(
                 chip8.register[count as usize],
-                (val + count) & 0xFF,
+                val + count,
                 "Chip-8 0xFX65 should have set register {:02X} to {:02X}",
                 count,
                 val + count
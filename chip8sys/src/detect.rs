@@ -0,0 +1,444 @@
+//! Heuristic platform detection for ROMs that are not in the database.
+//!
+//! The analyzer walks the code reachable from the program start with `Instruction::decode`,
+//! collects evidence (extension opcodes, shift and load/store idioms, VF reads after logic
+//! instructions) and turns it into a ranked list of platform guesses.
+
+use crate::chip8::Chip8Quirks;
+use crate::control_flow::reachable;
+use crate::instruction::{self, Extension, Instruction};
+use crate::platform::{HIRES_SIGNATURE, Platform};
+use crate::rom_db::platform_quirks;
+
+// How many straight-line instructions are inspected after a logic or load/store instruction
+const LOOKAHEAD: usize = 4;
+
+/// This enum lists how much a guess can be trusted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// The guess is a fallback with little supporting evidence.
+    Low,
+    /// The guess is supported by usage patterns.
+    Medium,
+    /// The guess is supported by opcodes or signatures unique to the platform.
+    High,
+}
+
+impl Confidence {
+    // Buckets a 0-100 score
+    fn from_score(score: u8) -> Confidence {
+        match score {
+            75.. => Confidence::High,
+            45.. => Confidence::Medium,
+            _ => Confidence::Low,
+        }
+    }
+}
+
+/// This struct stores one ranked platform guess.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlatformGuess {
+    /// This field stores a human readable name of the guessed platform.
+    pub name: &'static str,
+    /// This field stores the platform id used by the CHIP-8 database, e.g. `superchip`.
+    pub platform_id: &'static str,
+    /// This field stores the emulated platform, or None when the guess is not emulated.
+    pub platform: Option<Platform>,
+    /// This field stores the suggested quirk settings.
+    pub quirks: Chip8Quirks,
+    /// This field stores the score from 0 to 100 the guesses are ranked by.
+    pub score: u8,
+    /// This field stores the confidence bucket of the score.
+    pub confidence: Confidence,
+}
+
+/// This struct stores the facts the analyzer collected about a ROM.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Evidence {
+    /// This field stores the number of reachable instructions.
+    pub reachable_instructions: usize,
+    /// This field is set when the ROM starts with the hi-res CHIP-8 signature.
+    pub hires_signature: bool,
+    /// This field stores the number of reachable SUPER-CHIP only opcodes.
+    pub super_chip_opcodes: usize,
+    /// This field stores the number of reachable XO-CHIP only opcodes.
+    pub xo_chip_opcodes: usize,
+    /// This field stores the number of CHIP-8X only opcodes reachable from 0x300.
+    pub chip8x_opcodes: usize,
+    /// This field stores the number of `8X06`/`8X0E` shifts that ignore VY.
+    pub shifts_in_place: usize,
+    /// This field stores the number of shifts that name a distinct VY.
+    pub shifts_from_vy: usize,
+    /// This field stores the number of FX55/FX65 followed by another use of I without reloading it.
+    pub index_reused_after_store: usize,
+    /// This field stores the number of FX55/FX65 followed by an ANNN before I is used again.
+    pub index_reloaded_after_store: usize,
+    /// This field stores the number of 8XY1/8XY2/8XY3 followed by a read of VF.
+    pub vf_read_after_logic: usize,
+}
+
+/// This struct stores the result of analyzing a ROM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    /// This field stores the collected evidence.
+    pub evidence: Evidence,
+    /// This field stores the platform guesses, most likely first.
+    pub guesses: Vec<PlatformGuess>,
+}
+
+impl Analysis {
+    /// This function returns the most likely platform guess.
+    /// Arguments: none.
+    /// Returns: The highest ranked guess, or None when nothing matched.
+    pub fn best(&self) -> Option<&PlatformGuess> {
+        self.guesses.first()
+    }
+}
+
+/// This function guesses the platform and quirks of a ROM from its code.
+/// Arguments:
+/// - rom_bytes: The ROM bytes as they would be loaded at the program start.
+///
+/// Returns: The evidence and the ranked platform guesses.
+pub fn analyze(rom_bytes: &[u8]) -> Analysis {
+    let code = reachable(rom_bytes, Platform::Chip8);
+    let mut evidence = Evidence {
+        reachable_instructions: code.len(),
+        hires_signature: rom_bytes.starts_with(&HIRES_SIGNATURE),
+        ..Evidence::default()
+    };
+    for (index, &(_, opcode)) in code.iter().enumerate() {
+        match instruction::extension(opcode) {
+            Some(Extension::SuperChip) => evidence.super_chip_opcodes += 1,
+            Some(Extension::XoChip) => evidence.xo_chip_opcodes += 1,
+            None => {}
+        }
+        match Instruction::decode(opcode, Platform::Chip8) {
+            Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } if x != y => {
+                if y == 0 {
+                    evidence.shifts_in_place += 1;
+                } else {
+                    evidence.shifts_from_vy += 1;
+                }
+            }
+            Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. } => {
+                match next_index_use(following(&code, index)) {
+                    Some(true) => evidence.index_reloaded_after_store += 1,
+                    Some(false) => evidence.index_reused_after_store += 1,
+                    None => {}
+                }
+            }
+            Instruction::Or { .. } | Instruction::And { .. } | Instruction::Xor { .. }
+                if reads_vf_next(following(&code, index)) =>
+            {
+                evidence.vf_read_after_logic += 1;
+            }
+            _ => {}
+        }
+    }
+    // CHIP-8X programs live at 0x300, so their jumps only make sense from there
+    evidence.chip8x_opcodes = reachable(rom_bytes, Platform::Chip8X)
+        .iter()
+        .filter(|&&(_, opcode)| is_chip8x_only(opcode))
+        .count();
+    Analysis {
+        guesses: rank(&evidence),
+        evidence,
+    }
+}
+
+// Returns the decoded straight-line instructions after the instruction at `index`
+fn following(code: &[(u16, u16)], index: usize) -> impl Iterator<Item = Instruction> + '_ {
+    code[index + 1..]
+        .iter()
+        .zip(code[index..].iter())
+        .take_while(|((address, _), (previous, _))| *address == previous + 2)
+        .map(|(&(_, opcode), _)| Instruction::decode(opcode, Platform::Chip8))
+        .take(LOOKAHEAD)
+}
+
+// Some(true) if I is reloaded before its next use, Some(false) if it is used as is
+fn next_index_use(instructions: impl Iterator<Item = Instruction>) -> Option<bool> {
+    for instruction in instructions {
+        match instruction {
+            Instruction::LoadIndex { .. }
+            | Instruction::LoadFont { .. }
+            | Instruction::LoadBigFont { .. } => return Some(true),
+            Instruction::Draw { .. }
+            | Instruction::StoreBcd { .. }
+            | Instruction::StoreRegisters { .. }
+            | Instruction::LoadRegisters { .. }
+            | Instruction::AddIndex { .. } => return Some(false),
+            _ if is_control_flow(instruction) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+// True if VF is read before it is overwritten or control flow leaves the block
+fn reads_vf_next(instructions: impl Iterator<Item = Instruction>) -> bool {
+    for instruction in instructions {
+        if reads_vf(instruction) {
+            return true;
+        }
+        if writes_vf(instruction) || is_control_flow(instruction) {
+            return false;
+        }
+    }
+    false
+}
+
+fn reads_vf(instruction: Instruction) -> bool {
+    match instruction {
+        Instruction::SkipEqImmediate { x, .. }
+        | Instruction::SkipNeImmediate { x, .. }
+        | Instruction::AddImmediate { x, .. }
+        | Instruction::SkipKeyPressed { x }
+        | Instruction::SkipKeyNotPressed { x }
+        | Instruction::SetDelay { x }
+        | Instruction::SetSound { x }
+        | Instruction::AddIndex { x }
+        | Instruction::LoadFont { x }
+        | Instruction::LoadBigFont { x }
+        | Instruction::StoreBcd { x }
+        | Instruction::StoreRegisters { x } => x == 0xF,
+        Instruction::Move { y, .. } => y == 0xF,
+        Instruction::SkipEqRegister { x, y }
+        | Instruction::SkipNeRegister { x, y }
+        | Instruction::Or { x, y }
+        | Instruction::And { x, y }
+        | Instruction::Xor { x, y }
+        | Instruction::AddRegister { x, y }
+        | Instruction::SubRegister { x, y }
+        | Instruction::ShiftRight { x, y }
+        | Instruction::SubReverse { x, y }
+        | Instruction::ShiftLeft { x, y }
+        | Instruction::Draw { x, y, .. } => x == 0xF || y == 0xF,
+        _ => false,
+    }
+}
+
+fn writes_vf(instruction: Instruction) -> bool {
+    match instruction {
+        Instruction::AddRegister { .. }
+        | Instruction::SubRegister { .. }
+        | Instruction::ShiftRight { .. }
+        | Instruction::SubReverse { .. }
+        | Instruction::ShiftLeft { .. }
+        | Instruction::Draw { .. }
+        | Instruction::LoadRegisters { x: 0xF } => true,
+        Instruction::LoadImmediate { x, .. }
+        | Instruction::Move { x, .. }
+        | Instruction::Or { x, .. }
+        | Instruction::And { x, .. }
+        | Instruction::Xor { x, .. }
+        | Instruction::Random { x, .. }
+        | Instruction::LoadDelay { x }
+        | Instruction::WaitKey { x } => x == 0xF,
+        _ => false,
+    }
+}
+
+fn is_control_flow(instruction: Instruction) -> bool {
+    instruction.is_skip()
+        || matches!(
+            instruction,
+            Instruction::Jump { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return
+                | Instruction::JumpOffset { .. }
+        )
+}
+
+// Opcodes that only have a meaning on CHIP-8X; the BXYN colour opcodes are left out since
+// they are ordinary BNNN jumps elsewhere
+fn is_chip8x_only(opcode: u16) -> bool {
+    matches!(
+        Instruction::decode(opcode, Platform::Chip8X),
+        Instruction::CycleBackground
+            | Instruction::AddNibbles { .. }
+            | Instruction::SkipKey2Pressed { .. }
+            | Instruction::SkipKey2NotPressed { .. }
+            | Instruction::Output { .. }
+            | Instruction::Input { .. }
+    )
+}
+
+// Scores each candidate platform from the evidence and sorts them, most likely first
+fn rank(evidence: &Evidence) -> Vec<PlatformGuess> {
+    let mut original = 50;
+    let mut modern = 40;
+    let mut hires = 0;
+    let mut chip8x = 0;
+    let mut super_chip = 0;
+    let mut xo_chip = 0;
+
+    let is_shift_in_place = evidence.shifts_in_place > evidence.shifts_from_vy;
+    if is_shift_in_place {
+        original -= 10;
+        modern += 10;
+        super_chip += 10;
+    } else if evidence.shifts_from_vy > evidence.shifts_in_place {
+        original += 10;
+        modern -= 10;
+    }
+    // SUPER-CHIP 1.1 leaves I alone, so programs written for it reload I
+    if evidence.index_reused_after_store > evidence.index_reloaded_after_store {
+        super_chip -= 15;
+    }
+    // the VIP clears VF after logic instructions, only later interpreters keep it
+    let is_vf_kept = evidence.vf_read_after_logic > 0;
+    if is_vf_kept {
+        original -= 10;
+        modern += 10;
+    }
+    if evidence.chip8x_opcodes > 0 {
+        chip8x = 40 + 15 * evidence.chip8x_opcodes.min(4) as i32;
+        original -= 20;
+        modern -= 20;
+    }
+    if evidence.super_chip_opcodes > 0 {
+        super_chip += 60 + 10 * evidence.super_chip_opcodes.min(4) as i32;
+        original -= 40;
+        modern -= 40;
+        chip8x -= 40;
+    }
+    if evidence.xo_chip_opcodes > 0 {
+        // XO-CHIP includes the SUPER-CHIP opcodes
+        xo_chip = 60 + 10 * evidence.xo_chip_opcodes.min(4) as i32;
+        super_chip -= 20;
+        original -= 40;
+        modern -= 40;
+        chip8x -= 40;
+    }
+    if evidence.hires_signature {
+        hires = 95;
+        original -= 30;
+        modern -= 30;
+    }
+
+    let candidates = [
+        (
+            "CHIP-8 (COSMAC VIP)",
+            "originalChip8",
+            Some(Platform::Chip8),
+            original,
+        ),
+        (
+            "CHIP-8 (modern)",
+            "modernChip8",
+            Some(Platform::Chip8),
+            modern,
+        ),
+        (
+            "Hi-res CHIP-8",
+            "originalChip8",
+            Some(Platform::HiresChip8),
+            hires,
+        ),
+        ("CHIP-8X", "chip8x", Some(Platform::Chip8X), chip8x),
+        ("SUPER-CHIP", "superchip", None, super_chip),
+        ("XO-CHIP", "xochip", None, xo_chip),
+    ];
+    let mut guesses: Vec<PlatformGuess> = candidates
+        .into_iter()
+        .filter(|&(_, _, _, score)| score > 0)
+        .map(|(name, platform_id, platform, score)| {
+            let mut quirks = platform_quirks(platform_id)
+                .unwrap_or_default()
+                .to_quirks(platform_id);
+            // observed idioms override the defaults of platforms that can be emulated
+            if platform.is_some() {
+                quirks.shift_uses_vx |= is_shift_in_place;
+                quirks.reset_vf_on_logic &= !is_vf_kept;
+            }
+            let score = score.min(100) as u8;
+            PlatformGuess {
+                name,
+                platform_id,
+                platform,
+                quirks,
+                score,
+                confidence: Confidence::from_score(score),
+            }
+        })
+        .collect();
+    guesses.sort_by_key(|guess| std::cmp::Reverse(guess.score));
+    guesses
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that a plain CHIP-8 ROM is guessed as COSMAC VIP CHIP-8
+    fn test_analyze_plain_chip8() {
+        // 6005 A20A D005 1206, sprite data
+        let rom = [
+            0x60, 0x05, 0xA2, 0x0A, 0xD0, 0x05, 0x12, 0x06, 0x00, 0x00, 0xF0,
+        ];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.evidence.reachable_instructions, 4);
+        let best = analysis.best().expect("should have guessed a platform");
+        assert_eq!(best.platform_id, "originalChip8");
+        assert_eq!(best.platform, Some(Platform::Chip8));
+        assert_eq!(best.confidence, Confidence::Medium);
+    }
+
+    #[test]
+    // Tests that reachable SUPER-CHIP opcodes rank SUPER-CHIP first
+    fn test_analyze_super_chip() {
+        // 00FF 8106 8206 F175 1208
+        let rom = [0x00, 0xFF, 0x81, 0x06, 0x82, 0x06, 0xF1, 0x75, 0x12, 0x08];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.evidence.super_chip_opcodes, 2);
+        assert_eq!(analysis.evidence.shifts_in_place, 2);
+        let best = analysis.best().expect("should have guessed a platform");
+        assert_eq!(best.platform_id, "superchip");
+        assert_eq!(best.platform, None);
+        assert_eq!(best.confidence, Confidence::High);
+        assert!(best.quirks.shift_uses_vx);
+    }
+
+    #[test]
+    // Tests that unreachable data does not count as extension opcodes
+    fn test_analyze_skips_data() {
+        // 1200 followed by bytes that look like XO-CHIP opcodes
+        let rom = [0x12, 0x00, 0xF0, 0x00, 0x51, 0x22];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.evidence.reachable_instructions, 1);
+        assert_eq!(analysis.evidence.xo_chip_opcodes, 0);
+    }
+
+    #[test]
+    // Tests that CHIP-8X opcodes are found when the ROM is walked from 0x300
+    fn test_analyze_chip8x() {
+        // 02A0 5011 1304
+        let rom = [0x02, 0xA0, 0x50, 0x11, 0x13, 0x04];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.evidence.chip8x_opcodes, 2);
+        let best = analysis.best().expect("should have guessed a platform");
+        assert_eq!(best.platform, Some(Platform::Chip8X));
+    }
+
+    #[test]
+    // Tests that the hi-res signature wins and that VF reads after logic clear the VF quirk
+    fn test_analyze_hires_and_vf_usage() {
+        // 1260: the hi-res CHIP-8 start jump
+        let rom = [0x12, 0x60];
+        let analysis = analyze(&rom);
+        assert_eq!(
+            analysis.best().map(|guess| guess.platform),
+            Some(Some(Platform::HiresChip8))
+        );
+
+        // 8013 3F01 1200
+        let rom = [0x80, 0x13, 0x3F, 0x01, 0x12, 0x00];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.evidence.vf_read_after_logic, 1);
+        let best = analysis.best().expect("should have guessed a platform");
+        assert!(!best.quirks.reset_vf_on_logic);
+    }
+}
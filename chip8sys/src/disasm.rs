@@ -0,0 +1,85 @@
+//! Disassembly of emulated memory.
+//!
+//! `Chip8Sys::disassemble` decodes the instruction words in a memory range the way the active
+//! platform would run them, for listings in a debugger. Every word is decoded, so data such as
+//! sprites shows up as whatever instructions its bytes spell.
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::chip8::Chip8Sys;
+use crate::instruction::Instruction;
+
+/// This struct is one line of a disassembly listing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    /// This field stores the address of the instruction word.
+    pub address: u16,
+    /// This field stores the 16-bit instruction word.
+    pub opcode: u16,
+    /// This field stores the decoded instruction.
+    pub instruction: Instruction,
+}
+
+impl fmt::Display for DisassemblyLine {
+    // Prints `0200: 00E0  CLS`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04X}: {:04X}  {}",
+            self.address, self.opcode, self.instruction
+        )
+    }
+}
+
+impl Chip8Sys {
+    /// This function disassembles memory without running memory hooks.
+    /// Arguments:
+    /// - addresses: The addresses to list, read as 2-byte words from the start. Addresses past
+    ///   the end of memory wrap around like the CPU's.
+    ///
+    /// Returns: One line per instruction word.
+    pub fn disassemble(&self, addresses: Range<u16>) -> Vec<DisassemblyLine> {
+        let mask = self.memory.size() - 1;
+        addresses
+            .step_by(2)
+            .map(|address| {
+                let high = self.memory[address as usize & mask];
+                let low = self.memory[(address as usize + 1) & mask];
+                let opcode = u16::from_be_bytes([high, low]);
+                DisassemblyLine {
+                    address,
+                    opcode,
+                    instruction: Instruction::decode(opcode, self.platform),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that a loaded ROM disassembles from the program start
+    fn test_disassemble() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x00, 0xE0, 0xA2, 0x0A, 0xD0, 0x15, 0x12, 0x04]);
+        let listing = chip8
+            .disassemble(0x200..0x208)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listing,
+            [
+                "0200: 00E0  CLS",
+                "0202: A20A  LD I, 0x20A",
+                "0204: D015  DRW V0, V1, 5",
+                "0206: 1204  JP 0x204",
+            ]
+        );
+        assert_eq!(chip8.disassemble(0xFFE..0x1000)[0].opcode, 0x0000);
+    }
+}
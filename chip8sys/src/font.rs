@@ -0,0 +1,443 @@
+//! Hexadecimal font sets.
+//!
+//! Every interpreter kept sprites for the digits 0-F in memory for FX29 to point at, but the
+//! glyph shapes and their address differed between machines, and ROMs that draw the font bytes
+//! directly show it. SUPER-CHIP added 8x10 glyphs for FX30, which Octo extended to 0-F. A `Font`
+//! holds one small set, an optional big set and the address they are stored at; the big glyphs
+//! follow the small ones.
+
+use core::ops::Range;
+
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+
+/// This constant defines the length of a small glyph: 5 rows of 4 pixels.
+pub const SMALL_GLYPH_LEN: usize = 5;
+/// This constant defines the length of a big glyph: 10 rows of 8 pixels.
+pub const BIG_GLYPH_LEN: usize = 10;
+/// This constant defines the length of a small font: 16 glyphs.
+pub const SMALL_FONT_LEN: usize = 16 * SMALL_GLYPH_LEN;
+/// This constant defines the length of the largest big font: 16 glyphs.
+pub const BIG_FONT_LEN: usize = 16 * BIG_GLYPH_LEN;
+/// This constant defines the address the font is stored at unless configured otherwise.
+pub const DEFAULT_FONT_ADDRESS: u16 = 0x050;
+
+// CHIP-48, also used by SUPER-CHIP and Octo
+const CHIP48_FONT: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// The COSMAC VIP interpreter's glyphs
+const COSMAC_VIP_FONT: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// The DREAM 6800's 3-pixel wide glyphs
+const DREAM_6800_FONT: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// The ETI-660's 3-pixel wide glyphs
+const ETI_660_FONT: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// SUPER-CHIP 1.1's big digits, 0-9 only
+const SUPER_CHIP_BIG_FONT: [u8; 10 * BIG_GLYPH_LEN] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+// Octo's big glyphs, 0-F
+const OCTO_BIG_FONT: [u8; BIG_FONT_LEN] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// This enum lists the built-in small font sets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FontSet {
+    /// The CHIP-48 glyphs, shared by SUPER-CHIP and Octo.
+    #[default]
+    Chip48,
+    /// The COSMAC VIP interpreter's glyphs.
+    CosmacVip,
+    /// The DREAM 6800's narrow glyphs.
+    Dream6800,
+    /// The ETI-660's narrow glyphs.
+    Eti660,
+}
+
+impl FontSet {
+    /// This function returns the glyphs of the set.
+    /// Arguments: none.
+    /// Returns: Sixteen 5-byte glyphs, 0 first.
+    pub fn glyphs(self) -> &'static [u8; SMALL_FONT_LEN] {
+        match self {
+            FontSet::Chip48 => &CHIP48_FONT,
+            FontSet::CosmacVip => &COSMAC_VIP_FONT,
+            FontSet::Dream6800 => &DREAM_6800_FONT,
+            FontSet::Eti660 => &ETI_660_FONT,
+        }
+    }
+}
+
+/// This enum lists the built-in big font sets for FX30.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BigFontSet {
+    /// SUPER-CHIP 1.1's digits 0-9.
+    SuperChip,
+    /// Octo's glyphs 0-F.
+    Octo,
+}
+
+impl BigFontSet {
+    /// This function returns the glyphs of the set.
+    /// Arguments: none.
+    /// Returns: The 10-byte glyphs, 0 first.
+    pub fn glyphs(self) -> &'static [u8] {
+        match self {
+            BigFontSet::SuperChip => &SUPER_CHIP_BIG_FONT,
+            BigFontSet::Octo => &OCTO_BIG_FONT,
+        }
+    }
+}
+
+/// This struct is the font an interpreter keeps in memory for FX29 and FX30.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Font {
+    pub(crate) small: [u8; SMALL_FONT_LEN],
+    // the first big_glyphs * BIG_GLYPH_LEN bytes are used
+    pub(crate) big: [u8; BIG_FONT_LEN],
+    pub(crate) big_glyphs: u8,
+    pub(crate) address: u16,
+}
+
+impl Default for Font {
+    /// This function returns the CHIP-48 font at 0x050 without big glyphs.
+    /// Arguments: none.
+    /// Returns: The default font.
+    fn default() -> Self {
+        Font::new(FontSet::default())
+    }
+}
+
+impl Font {
+    /// This function builds a font from a built-in set, stored at 0x050 without big glyphs.
+    /// Arguments:
+    /// - set: The small font set.
+    ///
+    /// Returns: The font.
+    pub fn new(set: FontSet) -> Font {
+        Font::custom(*set.glyphs())
+    }
+
+    /// This function builds a font from user-supplied glyphs, stored at 0x050 without big glyphs.
+    /// Arguments:
+    /// - glyphs: Sixteen 5-byte glyphs, 0 first.
+    ///
+    /// Returns: The font.
+    pub fn custom(glyphs: [u8; SMALL_FONT_LEN]) -> Font {
+        Font {
+            small: glyphs,
+            big: [0; BIG_FONT_LEN],
+            big_glyphs: 0,
+            address: DEFAULT_FONT_ADDRESS,
+        }
+    }
+
+    /// This function adds a built-in big font set.
+    /// Arguments:
+    /// - set: The big font set.
+    ///
+    /// Returns: The font with big glyphs.
+    pub fn with_big(self, set: BigFontSet) -> Font {
+        self.with_custom_big(set.glyphs())
+            .expect("built-in big fonts have whole glyphs")
+    }
+
+    /// This function adds user-supplied big glyphs.
+    /// Arguments:
+    /// - glyphs: Up to sixteen 10-byte glyphs, 0 first.
+    ///
+    /// Returns: The font with big glyphs, or InvalidFont with the length when it is not a whole
+    /// number of glyphs or longer than sixteen.
+    pub fn with_custom_big(mut self, glyphs: &[u8]) -> Result<Font, Chip8Error> {
+        if !glyphs.len().is_multiple_of(BIG_GLYPH_LEN) || glyphs.len() > BIG_FONT_LEN {
+            return Err(Chip8Error::InvalidFont(glyphs.len()));
+        }
+        self.big = [0; BIG_FONT_LEN];
+        self.big[..glyphs.len()].copy_from_slice(glyphs);
+        self.big_glyphs = (glyphs.len() / BIG_GLYPH_LEN) as u8;
+        Ok(self)
+    }
+
+    /// This function moves the font.
+    /// Arguments:
+    /// - address: The address of the first small glyph.
+    ///
+    /// Returns: The moved font.
+    pub fn at(mut self, address: u16) -> Font {
+        self.address = address;
+        self
+    }
+
+    /// This function returns where the font is stored.
+    /// Arguments: none.
+    /// Returns: The addresses of the small glyphs followed by the big ones.
+    pub fn range(&self) -> Range<u16> {
+        self.address..self.address.saturating_add(self.len() as u16)
+    }
+
+    // The number of bytes the font takes
+    fn len(&self) -> usize {
+        SMALL_FONT_LEN + self.big_glyphs as usize * BIG_GLYPH_LEN
+    }
+
+    /// This function returns the address FX29 loads into I.
+    /// Arguments:
+    /// - digit: The digit, only the low nibble is used.
+    ///
+    /// Returns: The address of the small glyph.
+    pub fn small_glyph_address(&self, digit: u8) -> u16 {
+        self.address
+            .wrapping_add((digit & 0xF) as u16 * SMALL_GLYPH_LEN as u16)
+    }
+
+    /// This function returns the address FX30 loads into I. Like SUPER-CHIP, digits past the
+    /// last big glyph point past the font.
+    /// Arguments:
+    /// - digit: The digit, only the low nibble is used.
+    ///
+    /// Returns: The address of the big glyph, or None when the font has no big glyphs.
+    pub fn big_glyph_address(&self, digit: u8) -> Option<u16> {
+        let offset = SMALL_FONT_LEN + (digit & 0xF) as usize * BIG_GLYPH_LEN;
+        (self.big_glyphs > 0).then(|| self.address.wrapping_add(offset as u16))
+    }
+
+    // Checks that the whole font fits below the end of memory
+    pub(crate) fn check_fits(&self, memory_size: usize) -> Result<(), Chip8Error> {
+        if self.address as usize + self.len() > memory_size {
+            return Err(Chip8Error::InvalidFontAddress(self.address));
+        }
+        Ok(())
+    }
+
+    // The bytes to store at the font's address
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        let big_len = self.len() - SMALL_FONT_LEN;
+        self.small.iter().chain(&self.big[..big_len]).copied()
+    }
+}
+
+impl Chip8Sys {
+    /// This function returns the font FX29 and FX30 point at.
+    /// Arguments: none.
+    /// Returns: The font.
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// This function stores a font in memory and points FX29 and FX30 at it. The font survives
+    /// `reset`; the bytes of the previous font are left in memory.
+    /// Arguments:
+    /// - font: The font.
+    ///
+    /// Returns: The updated Chip-8 system, or InvalidFontAddress when the font does not fit in
+    /// memory.
+    pub fn set_font(&mut self, font: Font) -> Result<&mut Self, Chip8Error> {
+        font.check_fits(self.memory.size())?;
+        self.font = font;
+        self.load_font();
+        Ok(self)
+    }
+
+    // Copies the font into memory and tells the write guard where it is
+    pub(crate) fn load_font(&mut self) {
+        let range = self.font.range();
+        for (address, byte) in range.clone().zip(self.font.bytes()) {
+            self.memory[address as usize] = byte;
+        }
+        #[cfg(feature = "debugger")]
+        self.memory.guard.set_font(range);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decode::test::single_instruction_chip_8;
+
+    // The VIP font with SUPER-CHIP big glyphs, moved to 0x100
+    fn moved_font() -> Font {
+        Font::new(FontSet::CosmacVip)
+            .with_big(BigFontSet::SuperChip)
+            .at(0x100)
+    }
+
+    #[test]
+    // Tests that FX29 points at a small glyph of a moved font
+    fn test_moved_small_glyph() {
+        // F129: I = small glyph for V1
+        let mut chip8 = single_instruction_chip_8(0xF129);
+        chip8.set_font(moved_font()).unwrap();
+        chip8.register[1] = 4;
+        chip8.run().unwrap();
+        assert_eq!(chip8.register_i, 0x100 + 4 * 5);
+        assert_eq!(chip8.memory[0x100 + 4 * 5], 0xA0);
+    }
+
+    #[test]
+    // Tests that FX30 points at a big glyph stored after the small ones
+    fn test_moved_big_glyph() {
+        // F130: I = big glyph for V1
+        let mut chip8 = single_instruction_chip_8(0xF130);
+        chip8.set_font(moved_font()).unwrap();
+        chip8.register[1] = 3;
+        chip8.run().unwrap();
+        assert_eq!(chip8.register_i, 0x100 + 80 + 30);
+        assert_eq!(chip8.memory[0x100 + 80 + 30], 0x3C);
+    }
+
+    #[test]
+    // Tests that a font set with set_font is kept and stored again by a reset
+    fn test_font_survives_reset() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_font(moved_font()).unwrap();
+        chip8.memory[0x100 + 4 * 5] = 0;
+        chip8.reset();
+        assert_eq!(chip8.font(), &moved_font());
+        assert_eq!(chip8.memory[0x100 + 4 * 5], 0xA0);
+    }
+
+    #[test]
+    // Tests that the font range covers the small glyphs and only the big glyphs it has
+    fn test_font_range() {
+        assert_eq!(Font::default().range(), 0x050..0x0A0);
+        assert_eq!(moved_font().range(), 0x100..0x100 + 80 + 100);
+        let font = Font::default().with_custom_big(&[0xFF; 20]).unwrap();
+        assert_eq!(font.range(), 0x050..0x0A0 + 20);
+    }
+
+    #[test]
+    // Tests that FX30 points past the last big glyph like SUPER-CHIP does
+    fn test_big_glyph_past_set() {
+        let font = moved_font();
+        assert_eq!(font.big_glyph_address(0xA), Some(0x100 + 80 + 100));
+        assert_eq!(font.big_glyph_address(0x1A), font.big_glyph_address(0xA));
+    }
+
+    #[test]
+    // Tests that FX30 is rejected when the font has no big glyphs
+    fn test_no_big_glyphs() {
+        let mut chip8 = single_instruction_chip_8(0xF130);
+        assert!(matches!(chip8.run(), Err(Chip8Error::Invalid0xFNNN(3, 0))));
+    }
+
+    #[test]
+    // Tests that a font past the end of memory is rejected and not stored
+    fn test_font_past_memory() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        assert!(matches!(
+            chip8.set_font(Font::default().at(0xFC0)),
+            Err(Chip8Error::InvalidFontAddress(0xFC0))
+        ));
+        assert_eq!(chip8.font(), &Font::default());
+        assert_eq!(chip8.memory[0xFC0], 0);
+    }
+
+    #[test]
+    // Tests that custom big glyphs must be whole glyphs and at most sixteen
+    fn test_invalid_custom_big() {
+        assert!(matches!(
+            Font::default().with_custom_big(&[0; 15]),
+            Err(Chip8Error::InvalidFont(15))
+        ));
+        assert!(matches!(
+            Font::default().with_custom_big(&[0; BIG_FONT_LEN + BIG_GLYPH_LEN]),
+            Err(Chip8Error::InvalidFont(170))
+        ));
+    }
+}
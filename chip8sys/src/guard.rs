@@ -0,0 +1,406 @@
+//! Write guards for self-modifying code.
+//!
+//! Buggy ROMs can clobber their own code or the font with FX33 and FX55, which usually only shows
+//! up as a garbled screen much later. With a guard mode set the bus remembers which addresses have
+//! been executed and written, and flags CPU writes into executed code or the font, either as
+//! warnings to collect or as a `Chip8Error`. The loaded ROM image can also be made read-only.
+
+use std::ops::Range;
+
+use crate::bus::MEMORY_SIZE;
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+
+// Warnings past this are dropped so a ROM rewriting itself every frame cannot grow the list
+const MAX_WARNINGS: usize = 256;
+
+/// This enum selects how flagged writes are reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WriteGuardMode {
+    /// Writes are not tracked or checked.
+    #[default]
+    Off,
+    /// Flagged writes go ahead and are collected as warnings.
+    Warn,
+    /// Flagged writes stop `run` with `Chip8Error::ProtectedWrite`.
+    Error,
+}
+
+/// This enum lists the memory regions a write can be flagged for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProtectedRegion {
+    /// An address that has already been executed.
+    Code,
+    /// The font, at 0x050-0x09F unless moved with `set_font`.
+    Font,
+    /// The loaded ROM image while it is read-only.
+    Rom,
+}
+
+/// This struct describes a flagged CPU write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WriteWarning {
+    /// This field stores the address of the instruction that wrote.
+    pub program_counter: u16,
+    /// This field stores the address written.
+    pub address: u16,
+    /// This field stores the byte written.
+    pub value: u8,
+    /// This field stores the region the address belongs to.
+    pub region: ProtectedRegion,
+}
+
+// Tracks executed and written addresses and checks writes against them
+#[derive(Clone, Debug)]
+pub(crate) struct WriteGuard {
+    mode: WriteGuardMode,
+    is_rom_read_only: bool,
+    // where the last ROM was loaded
+    rom: Range<u16>,
+    // where the font is, see font.rs
+    font: Range<u16>,
+    // one bit per address
+    executed: [u64; MEMORY_SIZE / 64],
+    written: [u64; MEMORY_SIZE / 64],
+    // the address of the instruction being run
+    program_counter: u16,
+    warnings: Vec<WriteWarning>,
+    // what the running instruction changed, taken back if it fails
+    journal: Journal,
+}
+
+// The addresses an instruction marked and the state before it, see Bus::begin_instruction
+#[derive(Clone, Debug, Default)]
+struct Journal {
+    is_open: bool,
+    executed: Vec<u16>,
+    written: Vec<u16>,
+    program_counter: u16,
+    warnings: usize,
+}
+
+impl Default for WriteGuard {
+    fn default() -> Self {
+        WriteGuard {
+            mode: WriteGuardMode::Off,
+            is_rom_read_only: false,
+            rom: 0..0,
+            font: 0..0,
+            executed: [0; MEMORY_SIZE / 64],
+            written: [0; MEMORY_SIZE / 64],
+            program_counter: 0,
+            warnings: Vec::new(),
+            journal: Journal::default(),
+        }
+    }
+}
+
+impl WriteGuard {
+    // Keeps the settings but forgets the ROM, the tracked addresses and the warnings
+    pub(crate) fn settings(&self) -> WriteGuard {
+        WriteGuard {
+            mode: self.mode,
+            is_rom_read_only: self.is_rom_read_only,
+            font: self.font.clone(),
+            ..WriteGuard::default()
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.mode != WriteGuardMode::Off || self.is_rom_read_only
+    }
+
+    pub(crate) fn set_rom(&mut self, rom: Range<u16>) {
+        self.rom = rom;
+    }
+
+    pub(crate) fn set_font(&mut self, font: Range<u16>) {
+        self.font = font;
+    }
+
+    // Starts journaling an instruction's changes
+    pub(crate) fn begin_instruction(&mut self) {
+        self.journal.is_open = true;
+        self.journal.executed.clear();
+        self.journal.written.clear();
+        self.journal.program_counter = self.program_counter;
+        self.journal.warnings = self.warnings.len();
+    }
+
+    // Keeps the instruction's changes, or takes them back when it failed
+    pub(crate) fn end_instruction(&mut self, is_completed: bool) {
+        self.journal.is_open = false;
+        if is_completed {
+            return;
+        }
+        for &address in &self.journal.executed {
+            clear_bit(&mut self.executed, address);
+        }
+        for &address in &self.journal.written {
+            clear_bit(&mut self.written, address);
+        }
+        self.program_counter = self.journal.program_counter;
+        self.warnings.truncate(self.journal.warnings);
+    }
+
+    // Called on every fetch
+    pub(crate) fn mark_executed(&mut self, address: u16) {
+        self.program_counter = address;
+        if self.is_enabled() {
+            for address in [address, (address + 1) % MEMORY_SIZE as u16] {
+                if set_bit(&mut self.executed, address) && self.journal.is_open {
+                    self.journal.executed.push(address);
+                }
+            }
+        }
+    }
+
+    // Called before a CPU write, returns whether the byte should be stored
+    pub(crate) fn check_write(&mut self, address: u16, value: u8) -> Result<bool, Chip8Error> {
+        if !self.is_enabled() {
+            return Ok(true);
+        }
+        if set_bit(&mut self.written, address) && self.journal.is_open {
+            self.journal.written.push(address);
+        }
+        let region = if self.is_rom_read_only && self.rom.contains(&address) {
+            ProtectedRegion::Rom
+        } else if self.font.contains(&address) {
+            ProtectedRegion::Font
+        } else if get_bit(&self.executed, address) {
+            ProtectedRegion::Code
+        } else {
+            return Ok(true);
+        };
+        let warning = WriteWarning {
+            program_counter: self.program_counter,
+            address,
+            value,
+            region,
+        };
+        match self.mode {
+            WriteGuardMode::Error => return Err(Chip8Error::ProtectedWrite(warning)),
+            WriteGuardMode::Warn if self.warnings.len() < MAX_WARNINGS => {
+                self.warnings.push(warning);
+            }
+            WriteGuardMode::Warn | WriteGuardMode::Off => {}
+        }
+        Ok(region != ProtectedRegion::Rom)
+    }
+}
+
+// Returns whether the bit was clear before
+fn set_bit(bits: &mut [u64], address: u16) -> bool {
+    let is_clear = !get_bit(bits, address);
+    bits[address as usize / 64] |= 1 << (address % 64);
+    is_clear
+}
+
+fn clear_bit(bits: &mut [u64], address: u16) {
+    bits[address as usize / 64] &= !(1 << (address % 64));
+}
+
+fn get_bit(bits: &[u64], address: u16) -> bool {
+    bits[address as usize / 64] & 1 << (address % 64) != 0
+}
+
+impl Chip8Sys {
+    /// This function sets how CPU writes into executed code, the font or a read-only ROM are
+    /// reported. Tracking starts from the next instruction.
+    /// Arguments:
+    /// - mode: The guard mode.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_write_guard(&mut self, mode: WriteGuardMode) -> &mut Self {
+        self.memory.guard.mode = mode;
+        self
+    }
+
+    /// This function returns how flagged writes are reported.
+    /// Arguments: none.
+    /// Returns: The guard mode.
+    pub fn write_guard(&self) -> WriteGuardMode {
+        self.memory.guard.mode
+    }
+
+    /// This function makes the loaded ROM image read-only, CPU writes into it are dropped.
+    /// Arguments:
+    /// - is_read_only: True to protect the ROM image.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_rom_read_only(&mut self, is_read_only: bool) -> &mut Self {
+        self.memory.guard.is_rom_read_only = is_read_only;
+        self
+    }
+
+    /// This function reports whether the loaded ROM image is read-only.
+    /// Arguments: none.
+    /// Returns: True when CPU writes into the ROM image are dropped.
+    pub fn is_rom_read_only(&self) -> bool {
+        self.memory.guard.is_rom_read_only
+    }
+
+    /// This function returns the warnings collected in `WriteGuardMode::Warn`.
+    /// Arguments: none.
+    /// Returns: The warnings, oldest first, at most 256.
+    pub fn write_warnings(&self) -> &[WriteWarning] {
+        &self.memory.guard.warnings
+    }
+
+    /// This function removes and returns the collected warnings.
+    /// Arguments: none.
+    /// Returns: The warnings, oldest first.
+    pub fn take_write_warnings(&mut self) -> Vec<WriteWarning> {
+        std::mem::take(&mut self.memory.guard.warnings)
+    }
+
+    /// This function reports whether the CPU has executed an address while guarding was on.
+    /// Arguments:
+    /// - address: The address to check.
+    ///
+    /// Returns: True when the address was part of an executed instruction.
+    pub fn is_executed(&self, address: u16) -> bool {
+        get_bit(&self.memory.guard.executed, address % MEMORY_SIZE as u16)
+    }
+
+    /// This function reports whether the CPU has written an address while guarding was on.
+    /// Arguments:
+    /// - address: The address to check.
+    ///
+    /// Returns: True when FX33 or FX55 wrote the address.
+    pub fn is_written(&self, address: u16) -> bool {
+        get_bit(&self.memory.guard.written, address % MEMORY_SIZE as u16)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 6005 A202 F055 1206: overwrite its own first instruction, then halt
+    const SELF_MODIFYING: [u8; 8] = [0x60, 0x05, 0xA2, 0x00, 0xF0, 0x55, 0x12, 0x06];
+
+    // Loads a ROM into a guarded system and runs its first instructions
+    fn run_guarded(mode: WriteGuardMode, rom_bytes: &[u8], cycles: u32) -> Chip8Sys {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_write_guard(mode);
+        chip8.load_rom_bytes(rom_bytes);
+        chip8.tick(cycles).unwrap();
+        chip8
+    }
+
+    #[test]
+    // Tests that a write into executed code goes ahead and is collected as a warning
+    fn test_warn_on_code_write() {
+        let chip8 = run_guarded(WriteGuardMode::Warn, &SELF_MODIFYING, 3);
+        assert_eq!(
+            chip8.memory[0x200], 0x05,
+            "Warn mode should let the write through"
+        );
+        assert_eq!(
+            chip8.write_warnings(),
+            [WriteWarning {
+                program_counter: 0x204,
+                address: 0x200,
+                value: 0x05,
+                region: ProtectedRegion::Code,
+            }]
+        );
+    }
+
+    #[test]
+    // Tests that a write into the font is flagged and that taking the warnings clears them
+    fn test_warn_on_font_write() {
+        // 6001 A050 F055: write into the font
+        let mut chip8 = run_guarded(
+            WriteGuardMode::Warn,
+            &[0x60, 0x01, 0xA0, 0x50, 0xF0, 0x55],
+            3,
+        );
+        let warnings = chip8.take_write_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].region, ProtectedRegion::Font);
+        assert!(chip8.write_warnings().is_empty());
+    }
+
+    #[test]
+    // Tests that Warn mode keeps at most MAX_WARNINGS warnings and keeps the oldest
+    fn test_warnings_capped() {
+        // 6005 A200 F055 1202: write into the first instruction forever
+        let rom = [0x60, 0x05, 0xA2, 0x00, 0xF0, 0x55, 0x12, 0x02];
+        let chip8 = run_guarded(WriteGuardMode::Warn, &rom, 1 + 3 * 300);
+        assert_eq!(chip8.memory[0x200], 0x05);
+        assert_eq!(chip8.write_warnings().len(), MAX_WARNINGS);
+        assert!(
+            chip8
+                .write_warnings()
+                .iter()
+                .all(|warning| warning.address == 0x200 && warning.program_counter == 0x204)
+        );
+    }
+
+    #[test]
+    // Tests that only the bytes of run instructions and written addresses are marked
+    fn test_executed_and_written() {
+        let chip8 = run_guarded(WriteGuardMode::Warn, &SELF_MODIFYING, 3);
+        assert!(chip8.is_executed(0x200) && chip8.is_executed(0x201));
+        assert!(chip8.is_executed(0x205) && !chip8.is_executed(0x206));
+        assert!(chip8.is_written(0x200) && !chip8.is_written(0x201));
+    }
+
+    #[test]
+    // Tests that nothing is tracked or flagged while the guard is off
+    fn test_off() {
+        let chip8 = run_guarded(WriteGuardMode::Off, &SELF_MODIFYING, 3);
+        assert_eq!(chip8.memory[0x200], 0x05);
+        assert!(chip8.write_warnings().is_empty());
+        assert!(!chip8.is_executed(0x200) && !chip8.is_written(0x200));
+    }
+
+    #[test]
+    // Tests that Error mode stops the CPU before the write lands
+    fn test_error_mode() {
+        let mut chip8 = run_guarded(WriteGuardMode::Error, &SELF_MODIFYING, 2);
+        assert!(matches!(
+            chip8.run(),
+            Err(Chip8Error::ProtectedWrite(WriteWarning {
+                program_counter: 0x204,
+                address: 0x200,
+                region: ProtectedRegion::Code,
+                ..
+            }))
+        ));
+        assert_eq!(
+            chip8.memory[0x200], 0x60,
+            "Error mode should block the write"
+        );
+        assert!(chip8.write_warnings().is_empty());
+    }
+
+    #[test]
+    // Tests that a read-only ROM silently drops writes into code that has not run yet
+    fn test_read_only_rom() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_rom_read_only(true);
+        // 6005 A204 F055 1206: write into ROM that has not run yet
+        chip8.load_rom_bytes(&[0x60, 0x05, 0xA2, 0x04, 0xF0, 0x55, 0x12, 0x06]);
+        chip8.tick(3).unwrap();
+        assert_eq!(
+            chip8.memory[0x204], 0xF0,
+            "the ROM image should be read-only"
+        );
+        assert!(chip8.write_warnings().is_empty());
+    }
+
+    #[test]
+    // Tests that a reset keeps the guard settings but forgets the tracked addresses
+    fn test_reset() {
+        let mut chip8 = run_guarded(WriteGuardMode::Warn, &SELF_MODIFYING, 3);
+        chip8.set_rom_read_only(true);
+        chip8.reset();
+        assert_eq!(chip8.write_guard(), WriteGuardMode::Warn);
+        assert!(chip8.is_rom_read_only());
+        assert!(!chip8.is_executed(0x200) && !chip8.is_written(0x200));
+        assert!(chip8.write_warnings().is_empty());
+    }
+}
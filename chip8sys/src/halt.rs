@@ -0,0 +1,202 @@
+//! Halt and idle detection.
+//!
+//! CHIP-8 programs have no halt instruction, they end by jumping to themselves or spin in a loop
+//! polling the keypad. The detector watches backward jumps: when a loop comes back to its first
+//! instruction with the CPU state unchanged and nothing but the keypad or the delay timer could
+//! change it, the program is halted or idle.
+
+use crate::chip8::Chip8Sys;
+use crate::instruction::Instruction;
+
+/// This enum describes whether the program is making progress.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RunStatus {
+    /// The program is running.
+    #[default]
+    Running,
+    /// The program is waiting for input, on FX0A or in a loop polling the keypad.
+    Idle,
+    /// The program jumps to itself or loops without changing any state, only a reset helps.
+    Halted,
+}
+
+// The CPU state a loop has to change to make progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LoopState {
+    register: [u8; 16],
+    register_i: u16,
+    stack_pointer: u8,
+    stack: [u16; 16],
+    delay_timer: u8,
+    keys: u16,
+}
+
+/// This struct tracks the innermost loop for `Chip8Sys::status`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct LoopDetector {
+    // the loop's first and last instruction
+    head: u16,
+    tail: u16,
+    // the state when the loop head was last entered, None when no loop is tracked
+    state: Option<LoopState>,
+    // set when the loop body tests the keypad
+    is_reading_keys: bool,
+    status: RunStatus,
+}
+
+impl Chip8Sys {
+    /// This function reports whether the program is making progress.
+    /// Frontends can stop ticking while the status is not `Running` and resume on the next key
+    /// event; key events reset the status to `Running`.
+    /// Arguments: none.
+    /// Returns: The run status.
+    pub fn status(&self) -> RunStatus {
+        if self.is_waiting_for_key() {
+            return RunStatus::Idle;
+        }
+        self.loop_detector.status
+    }
+
+    // Called after each instruction with the address and opcode it ran
+    pub(crate) fn detect_halt(&mut self, pc_before: u16, opcode: u16) {
+        let keys = self.keys_mask();
+        let detector = &mut self.loop_detector;
+        let instruction = Instruction::decode(opcode, self.platform);
+        let is_jump = matches!(
+            instruction,
+            Instruction::Jump { .. } | Instruction::JumpOffset { .. }
+        );
+        if is_jump && self.program_counter == pc_before {
+            detector.status = RunStatus::Halted;
+            detector.state = None;
+            return;
+        }
+        match instruction {
+            // anything that changes memory, the display or the timers is progress
+            Instruction::ClearScreen
+            | Instruction::HiresClearScreen
+            | Instruction::CycleBackground
+            | Instruction::ZoneColor { .. }
+            | Instruction::SpriteColor { .. }
+            | Instruction::Draw { .. }
+            | Instruction::StoreBcd { .. }
+            | Instruction::StoreRegisters { .. }
+            | Instruction::SetDelay { .. }
+            | Instruction::SetSound { .. }
+            | Instruction::Output { .. } => {
+                *detector = LoopDetector::default();
+                return;
+            }
+            Instruction::SkipKeyPressed { .. }
+            | Instruction::SkipKeyNotPressed { .. }
+            | Instruction::SkipKey2Pressed { .. }
+            | Instruction::SkipKey2NotPressed { .. }
+            | Instruction::Input { .. } => detector.is_reading_keys = true,
+            _ => {}
+        }
+        if self.program_counter > pc_before {
+            // leaving the tracked loop forward ends it
+            if detector.state.is_some() && self.program_counter > detector.tail {
+                *detector = LoopDetector::default();
+            }
+            return;
+        }
+        let state = LoopState {
+            register: self.register,
+            register_i: self.register_i,
+            stack_pointer: self.stack_pointer,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            keys,
+        };
+        if detector.state == Some(state) && detector.head == self.program_counter {
+            detector.status = if detector.is_reading_keys {
+                RunStatus::Idle
+            } else if self.delay_timer > 0 {
+                // the loop is most likely waiting for the timer
+                RunStatus::Running
+            } else {
+                RunStatus::Halted
+            };
+            return;
+        }
+        *detector = LoopDetector {
+            head: self.program_counter,
+            tail: pc_before,
+            state: Some(state),
+            is_reading_keys: false,
+            status: RunStatus::Running,
+        };
+    }
+
+    // Input can end an idle loop, so the frontend should start ticking again
+    pub(crate) fn wake_on_input(&mut self) {
+        if self.loop_detector.status == RunStatus::Idle {
+            self.loop_detector = LoopDetector::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decode::test::single_instruction_chip_8;
+
+    #[test]
+    // Tests that a jump to itself halts straight away
+    fn test_self_jump_halts() {
+        let mut chip8 = single_instruction_chip_8(0x1200);
+        assert_eq!(chip8.status(), RunStatus::Running);
+        chip8.run().unwrap();
+        assert_eq!(
+            chip8.status(),
+            RunStatus::Halted,
+            "Chip-8 0x1NNN to itself should halt"
+        );
+    }
+
+    #[test]
+    // Tests that a loop without state changes halts and a counting loop does not
+    fn test_tight_loop_halts() {
+        // 6005 8000 1202: a loop that copies V0 onto itself
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x60, 0x05, 0x80, 0x00, 0x12, 0x02]);
+        chip8.tick(5).unwrap();
+        assert_eq!(chip8.status(), RunStatus::Halted);
+
+        // 7001 1200: count forever
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]);
+        chip8.tick(100).unwrap();
+        assert_eq!(chip8.status(), RunStatus::Running);
+    }
+
+    #[test]
+    // Tests that keypad polling loops and FX0A are idle until a key arrives
+    fn test_key_polling_is_idle() {
+        // 6005 E09E 1202 1206: loop until key 5 is pressed, then halt
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x12, 0x06]);
+        chip8.tick(7).unwrap();
+        assert_eq!(chip8.status(), RunStatus::Idle);
+        chip8.key_down(0x5).unwrap();
+        assert_eq!(chip8.status(), RunStatus::Running);
+        chip8.tick(3).unwrap();
+        assert_eq!(chip8.status(), RunStatus::Halted);
+
+        let mut chip8 = single_instruction_chip_8(0xF00A);
+        chip8.run().unwrap();
+        assert_eq!(chip8.status(), RunStatus::Idle);
+    }
+
+    #[test]
+    // Tests that a loop waiting on the delay timer keeps running
+    fn test_timer_loop_runs() {
+        // 6010 F015 F007 3000 1204: wait for the delay timer to run out
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_timer_mode(crate::chip8::TimerMode::External);
+        chip8.load_rom_bytes(&[0x60, 0x10, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04]);
+        chip8.tick(20).unwrap();
+        assert_eq!(chip8.status(), RunStatus::Running);
+    }
+}
@@ -0,0 +1,363 @@
+//! A side-effect free instruction decoder.
+//!
+//! `Chip8Sys::run` executes opcodes straight from their nibbles. Tools that only need to look at
+//! code (analysis, disassembly) use `Instruction::decode` instead, which follows the same rules
+//! for the selected platform. Instructions print as Cowgod-style assembly, e.g. `DRW V1, V2, 15`.
+
+use core::fmt;
+
+use crate::platform::Platform;
+
+/// This enum lists the decoded CHIP-8 instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// 00E0 - clear the display.
+    ClearScreen,
+    /// 00EE - return from a subroutine.
+    Return,
+    /// 0NNN - call a machine code routine (ignored by the emulator).
+    System { nnn: u16 },
+    /// 0230 - clear the 64x64 display (hi-res CHIP-8).
+    HiresClearScreen,
+    /// 02A0 - cycle the background colour (CHIP-8X).
+    CycleBackground,
+    /// 1NNN - jump to NNN.
+    Jump { nnn: u16 },
+    /// 2NNN - call the subroutine at NNN.
+    Call { nnn: u16 },
+    /// 3XNN - skip if VX == NN.
+    SkipEqImmediate { x: u8, nn: u8 },
+    /// 4XNN - skip if VX != NN.
+    SkipNeImmediate { x: u8, nn: u8 },
+    /// 5XY0 - skip if VX == VY.
+    SkipEqRegister { x: u8, y: u8 },
+    /// 5XY1 - add VY to VX nibble by nibble (CHIP-8X).
+    AddNibbles { x: u8, y: u8 },
+    /// 6XNN - VX = NN.
+    LoadImmediate { x: u8, nn: u8 },
+    /// 7XNN - VX += NN.
+    AddImmediate { x: u8, nn: u8 },
+    /// 8XY0 - VX = VY.
+    Move { x: u8, y: u8 },
+    /// 8XY1 - VX |= VY.
+    Or { x: u8, y: u8 },
+    /// 8XY2 - VX &= VY.
+    And { x: u8, y: u8 },
+    /// 8XY3 - VX ^= VY.
+    Xor { x: u8, y: u8 },
+    /// 8XY4 - VX += VY with carry.
+    AddRegister { x: u8, y: u8 },
+    /// 8XY5 - VX -= VY with borrow.
+    SubRegister { x: u8, y: u8 },
+    /// 8XY6 - shift right.
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7 - VX = VY - VX with borrow.
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE - shift left.
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0 - skip if VX != VY.
+    SkipNeRegister { x: u8, y: u8 },
+    /// ANNN - I = NNN.
+    LoadIndex { nnn: u16 },
+    /// BNNN - jump to NNN + V0.
+    JumpOffset { nnn: u16 },
+    /// BXY0 - colour 8x4 zones (CHIP-8X).
+    ZoneColor { x: u8, y: u8 },
+    /// BXYN - colour the area of an N row sprite (CHIP-8X).
+    SpriteColor { x: u8, y: u8, n: u8 },
+    /// CXNN - VX = random & NN.
+    Random { x: u8, nn: u8 },
+    /// DXYN - draw an N row sprite at (VX, VY).
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E - skip if key VX is pressed.
+    SkipKeyPressed { x: u8 },
+    /// EXA1 - skip if key VX is not pressed.
+    SkipKeyNotPressed { x: u8 },
+    /// EXF2 - skip if key VX on the second keypad is pressed (CHIP-8X).
+    SkipKey2Pressed { x: u8 },
+    /// EXF5 - skip if key VX on the second keypad is not pressed (CHIP-8X).
+    SkipKey2NotPressed { x: u8 },
+    /// FX07 - VX = delay timer.
+    LoadDelay { x: u8 },
+    /// FX0A - wait for a key and store it in VX.
+    WaitKey { x: u8 },
+    /// FX15 - delay timer = VX.
+    SetDelay { x: u8 },
+    /// FX18 - sound timer = VX.
+    SetSound { x: u8 },
+    /// FX1E - I += VX.
+    AddIndex { x: u8 },
+    /// FX29 - I = font sprite for VX.
+    LoadFont { x: u8 },
+    /// FX30 - I = big font sprite for VX (SUPER-CHIP).
+    LoadBigFont { x: u8 },
+    /// FX33 - store the BCD of VX at I.
+    StoreBcd { x: u8 },
+    /// FX55 - store V0..=VX at I.
+    StoreRegisters { x: u8 },
+    /// FX65 - load V0..=VX from I.
+    LoadRegisters { x: u8 },
+    /// FXF8 - output VX to the I/O port (CHIP-8X).
+    Output { x: u8 },
+    /// FXFB - wait for input from the I/O port (CHIP-8X).
+    Input { x: u8 },
+    /// An opcode the platform does not define.
+    Invalid { opcode: u16 },
+}
+
+/// This enum lists the instruction set extensions beyond the original CHIP-8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// SUPER-CHIP 1.0/1.1 instructions.
+    SuperChip,
+    /// XO-CHIP instructions.
+    XoChip,
+}
+
+impl Instruction {
+    /// This function decodes an opcode the way `Chip8Sys::run` executes it on a platform.
+    /// Arguments:
+    /// - opcode: The 16-bit instruction word.
+    /// - platform: The platform whose instruction set applies.
+    ///
+    /// Returns: The decoded instruction.
+    pub fn decode(opcode: u16, platform: Platform) -> Instruction {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+        let is_chip8x = platform == Platform::Chip8X;
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Instruction::ClearScreen,
+                0x00EE => Instruction::Return,
+                0x0230 if platform == Platform::HiresChip8 => Instruction::HiresClearScreen,
+                0x02A0 if is_chip8x => Instruction::CycleBackground,
+                _ => Instruction::System { nnn },
+            },
+            0x1000 => Instruction::Jump { nnn },
+            0x2000 => Instruction::Call { nnn },
+            0x3000 => Instruction::SkipEqImmediate { x, nn },
+            0x4000 => Instruction::SkipNeImmediate { x, nn },
+            0x5000 if is_chip8x && n == 1 => Instruction::AddNibbles { x, y },
+            // the low nibble is ignored, like run() does
+            0x5000 => Instruction::SkipEqRegister { x, y },
+            0x6000 => Instruction::LoadImmediate { x, nn },
+            0x7000 => Instruction::AddImmediate { x, nn },
+            0x8000 => match n {
+                0x0 => Instruction::Move { x, y },
+                0x1 => Instruction::Or { x, y },
+                0x2 => Instruction::And { x, y },
+                0x3 => Instruction::Xor { x, y },
+                0x4 => Instruction::AddRegister { x, y },
+                0x5 => Instruction::SubRegister { x, y },
+                0x6 => Instruction::ShiftRight { x, y },
+                0x7 => Instruction::SubReverse { x, y },
+                0xE => Instruction::ShiftLeft { x, y },
+                _ => Instruction::Invalid { opcode },
+            },
+            0x9000 => Instruction::SkipNeRegister { x, y },
+            0xA000 => Instruction::LoadIndex { nnn },
+            0xB000 if is_chip8x && n == 0 => Instruction::ZoneColor { x, y },
+            0xB000 if is_chip8x => Instruction::SpriteColor { x, y, n },
+            0xB000 => Instruction::JumpOffset { nnn },
+            0xC000 => Instruction::Random { x, nn },
+            0xD000 => Instruction::Draw { x, y, n },
+            0xE000 => match nn {
+                0x9E => Instruction::SkipKeyPressed { x },
+                0xA1 => Instruction::SkipKeyNotPressed { x },
+                0xF2 if is_chip8x => Instruction::SkipKey2Pressed { x },
+                0xF5 if is_chip8x => Instruction::SkipKey2NotPressed { x },
+                _ => Instruction::Invalid { opcode },
+            },
+            _ => match nn {
+                0x07 => Instruction::LoadDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddIndex { x },
+                0x29 => Instruction::LoadFont { x },
+                0x30 => Instruction::LoadBigFont { x },
+                0x33 => Instruction::StoreBcd { x },
+                0x55 => Instruction::StoreRegisters { x },
+                0x65 => Instruction::LoadRegisters { x },
+                0xF8 if is_chip8x => Instruction::Output { x },
+                0xFB if is_chip8x => Instruction::Input { x },
+                _ => Instruction::Invalid { opcode },
+            },
+        }
+    }
+
+    /// This function reports whether the instruction may skip the next instruction.
+    /// Arguments: none.
+    /// Returns: True for the conditional skip instructions.
+    pub fn is_skip(self) -> bool {
+        matches!(
+            self,
+            Instruction::SkipEqImmediate { .. }
+                | Instruction::SkipNeImmediate { .. }
+                | Instruction::SkipEqRegister { .. }
+                | Instruction::SkipNeRegister { .. }
+                | Instruction::SkipKeyPressed { .. }
+                | Instruction::SkipKeyNotPressed { .. }
+                | Instruction::SkipKey2Pressed { .. }
+                | Instruction::SkipKey2NotPressed { .. }
+        )
+    }
+}
+
+impl fmt::Display for Instruction {
+    // Prints the instruction as assembly, registers as VX and constants in hex
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::System { nnn } => write!(f, "SYS 0x{nnn:03X}"),
+            Instruction::HiresClearScreen => write!(f, "HCLS"),
+            Instruction::CycleBackground => write!(f, "BGC"),
+            Instruction::Jump { nnn } => write!(f, "JP 0x{nnn:03X}"),
+            Instruction::Call { nnn } => write!(f, "CALL 0x{nnn:03X}"),
+            Instruction::SkipEqImmediate { x, nn } => write!(f, "SE V{x:X}, 0x{nn:02X}"),
+            Instruction::SkipNeImmediate { x, nn } => write!(f, "SNE V{x:X}, 0x{nn:02X}"),
+            Instruction::SkipEqRegister { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            Instruction::AddNibbles { x, y } => write!(f, "ADDN V{x:X}, V{y:X}"),
+            Instruction::LoadImmediate { x, nn } => write!(f, "LD V{x:X}, 0x{nn:02X}"),
+            Instruction::AddImmediate { x, nn } => write!(f, "ADD V{x:X}, 0x{nn:02X}"),
+            Instruction::Move { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Instruction::Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            Instruction::And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Instruction::Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            Instruction::AddRegister { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Instruction::SubRegister { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            Instruction::SkipNeRegister { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            Instruction::LoadIndex { nnn } => write!(f, "LD I, 0x{nnn:03X}"),
+            Instruction::JumpOffset { nnn } => write!(f, "JP V0, 0x{nnn:03X}"),
+            Instruction::ZoneColor { x, y } => write!(f, "COL V{x:X}, V{y:X}"),
+            Instruction::SpriteColor { x, y, n } => write!(f, "COL V{x:X}, V{y:X}, {n}"),
+            Instruction::Random { x, nn } => write!(f, "RND V{x:X}, 0x{nn:02X}"),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            Instruction::SkipKeyPressed { x } => write!(f, "SKP V{x:X}"),
+            Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{x:X}"),
+            Instruction::SkipKey2Pressed { x } => write!(f, "SKP2 V{x:X}"),
+            Instruction::SkipKey2NotPressed { x } => write!(f, "SKNP2 V{x:X}"),
+            Instruction::LoadDelay { x } => write!(f, "LD V{x:X}, DT"),
+            Instruction::WaitKey { x } => write!(f, "LD V{x:X}, K"),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{x:X}"),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{x:X}"),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{x:X}"),
+            Instruction::LoadFont { x } => write!(f, "LD F, V{x:X}"),
+            Instruction::LoadBigFont { x } => write!(f, "LD HF, V{x:X}"),
+            Instruction::StoreBcd { x } => write!(f, "LD B, V{x:X}"),
+            Instruction::StoreRegisters { x } => write!(f, "LD [I], V{x:X}"),
+            Instruction::LoadRegisters { x } => write!(f, "LD V{x:X}, [I]"),
+            Instruction::Output { x } => write!(f, "OUT V{x:X}"),
+            Instruction::Input { x } => write!(f, "IN V{x:X}"),
+            Instruction::Invalid { opcode } => write!(f, "DW 0x{opcode:04X}"),
+        }
+    }
+}
+
+/// This function reports which unsupported extension an opcode belongs to.
+/// Only opcodes that are unused or meaningless on the original CHIP-8 are classified.
+/// Arguments:
+/// - opcode: The 16-bit instruction word.
+///
+/// Returns: The extension, or None for opcodes that are not extension-only.
+pub fn extension(opcode: u16) -> Option<Extension> {
+    if is_super_chip(opcode) {
+        Some(Extension::SuperChip)
+    } else if is_xo_chip(opcode) {
+        Some(Extension::XoChip)
+    } else {
+        None
+    }
+}
+
+// 00CN, 00FB-00FF, DXY0, FX30, FX75 and FX85
+fn is_super_chip(opcode: u16) -> bool {
+    matches!(opcode, 0x00C1..=0x00CF | 0x00FB..=0x00FF)
+        || opcode & 0xF00F == 0xD000
+        || matches!(opcode & 0xF0FF, 0xF030 | 0xF075 | 0xF085)
+}
+
+// 00DN, 5XY2, 5XY3, F000, F002, FX01 and FX3A
+fn is_xo_chip(opcode: u16) -> bool {
+    matches!(opcode, 0x00D1..=0x00DF | 0xF000 | 0xF002)
+        || matches!(opcode & 0xF00F, 0x5002 | 0x5003)
+        || matches!(opcode & 0xF0FF, 0xF001 | 0xF03A)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that the decoder splits opcodes into their operands
+    fn test_decode_operands() {
+        assert_eq!(
+            Instruction::decode(0xD12F, Platform::Chip8),
+            Instruction::Draw { x: 1, y: 2, n: 0xF }
+        );
+        assert_eq!(
+            Instruction::decode(0x2ABC, Platform::Chip8),
+            Instruction::Call { nnn: 0xABC }
+        );
+        assert_eq!(
+            Instruction::decode(0x8A0B, Platform::Chip8),
+            Instruction::Invalid { opcode: 0x8A0B }
+        );
+    }
+
+    #[test]
+    // Tests that platform specific opcodes only decode on their platform
+    fn test_decode_platform_opcodes() {
+        assert_eq!(
+            Instruction::decode(0xB120, Platform::Chip8),
+            Instruction::JumpOffset { nnn: 0x120 }
+        );
+        assert_eq!(
+            Instruction::decode(0xB120, Platform::Chip8X),
+            Instruction::ZoneColor { x: 1, y: 2 }
+        );
+        assert_eq!(
+            Instruction::decode(0x0230, Platform::HiresChip8),
+            Instruction::HiresClearScreen
+        );
+        assert_eq!(
+            Instruction::decode(0x0230, Platform::Chip8),
+            Instruction::System { nnn: 0x230 }
+        );
+        assert_eq!(
+            Instruction::decode(0x01E0, Platform::Chip8),
+            Instruction::System { nnn: 0x1E0 }
+        );
+    }
+
+    #[test]
+    // Tests the assembly text of a few instructions
+    fn test_display() {
+        let text = |opcode| Instruction::decode(opcode, Platform::Chip8).to_string();
+        assert_eq!(text(0x00E0), "CLS");
+        assert_eq!(text(0xD12F), "DRW V1, V2, 15");
+        assert_eq!(text(0x6A0C), "LD VA, 0x0C");
+        assert_eq!(text(0xB300), "JP V0, 0x300");
+        assert_eq!(text(0xF355), "LD [I], V3");
+        assert_eq!(text(0x8A0B), "DW 0x8A0B");
+    }
+
+    #[test]
+    // Tests the classification of extension-only opcodes
+    fn test_extension() {
+        assert_eq!(extension(0x00FF), Some(Extension::SuperChip));
+        assert_eq!(extension(0xF375), Some(Extension::SuperChip));
+        assert_eq!(extension(0xD120), Some(Extension::SuperChip));
+        assert_eq!(extension(0x5122), Some(Extension::XoChip));
+        assert_eq!(extension(0xF000), Some(Extension::XoChip));
+        assert_eq!(extension(0x00E0), None);
+        assert_eq!(extension(0xD125), None);
+    }
+}
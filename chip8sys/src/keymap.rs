@@ -0,0 +1,468 @@
+//! Keyboard to keypad mapping for frontends.
+//!
+//! Bindings are keyed by DOM `KeyboardEvent.code` strings such as `KeyQ` or `Numpad7`. Codes name
+//! physical key positions, so the QWERTY, AZERTY and Dvorak layouts all bind the same 4x4 block
+//! on the left of the keyboard and only differ in the labels shown to the player.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::chip8::Chip8KeyMask;
+use crate::chip8error::Chip8Error;
+
+// The 4x4 block used by the letter layouts, indexed by keypad key
+const BLOCK_CODES: [&str; 16] = [
+    "KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE", "KeyA", "KeyS", "KeyD", "KeyZ",
+    "KeyC", "Digit4", "KeyR", "KeyF", "KeyV",
+];
+// The number pad, with the digits on their own keys and A-F on the operator keys
+const NUMPAD_CODES: [&str; 16] = [
+    "Numpad0",
+    "Numpad1",
+    "Numpad2",
+    "Numpad3",
+    "Numpad4",
+    "Numpad5",
+    "Numpad6",
+    "Numpad7",
+    "Numpad8",
+    "Numpad9",
+    "NumpadDivide",
+    "NumpadMultiply",
+    "NumpadSubtract",
+    "NumpadAdd",
+    "NumpadEnter",
+    "NumpadDecimal",
+];
+
+/// This enum lists the built-in keyboard layouts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum KeyLayout {
+    /// This layout binds `1234/QWER/ASDF/ZXCV` on a QWERTY keyboard.
+    #[default]
+    Qwerty,
+    /// This layout binds `1234/AZER/QSDF/WXCV` on an AZERTY keyboard.
+    Azerty,
+    /// This layout binds `1234/',.P/AOEU/;QJK` on a Dvorak keyboard.
+    Dvorak,
+    /// This layout binds the number pad, with A-F on `/ * - + Enter .`.
+    Numpad,
+}
+
+impl KeyLayout {
+    /// This constant lists every built-in layout.
+    pub const ALL: [KeyLayout; 4] = [
+        KeyLayout::Qwerty,
+        KeyLayout::Azerty,
+        KeyLayout::Dvorak,
+        KeyLayout::Numpad,
+    ];
+
+    /// This function returns the layout name used in profiles.
+    /// Arguments: none.
+    /// Returns: The lowercase layout name.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyLayout::Qwerty => "qwerty",
+            KeyLayout::Azerty => "azerty",
+            KeyLayout::Dvorak => "dvorak",
+            KeyLayout::Numpad => "numpad",
+        }
+    }
+
+    /// This function looks up a layout by name, ignoring case.
+    /// Arguments:
+    /// - name: The layout name, e.g. `azerty`.
+    ///
+    /// Returns: The layout, or None for an unknown name.
+    pub fn from_name(name: &str) -> Option<KeyLayout> {
+        KeyLayout::ALL
+            .into_iter()
+            .find(|layout| layout.name().eq_ignore_ascii_case(name))
+    }
+
+    /// This function returns the `KeyboardEvent.code` bound to each keypad key.
+    /// Arguments: none.
+    /// Returns: The codes, indexed by keypad key 0x0-0xF.
+    pub fn codes(self) -> [&'static str; 16] {
+        match self {
+            KeyLayout::Qwerty | KeyLayout::Azerty | KeyLayout::Dvorak => BLOCK_CODES,
+            KeyLayout::Numpad => NUMPAD_CODES,
+        }
+    }
+
+    /// This function returns the key cap labels of the bound keys.
+    /// Arguments: none.
+    /// Returns: The labels, indexed by keypad key 0x0-0xF.
+    pub fn labels(self) -> [&'static str; 16] {
+        match self {
+            KeyLayout::Qwerty => [
+                "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+            ],
+            KeyLayout::Azerty => [
+                "X", "1", "2", "3", "A", "Z", "E", "Q", "S", "D", "W", "C", "4", "R", "F", "V",
+            ],
+            KeyLayout::Dvorak => [
+                "Q", "1", "2", "3", "'", ",", ".", "A", "O", "E", ";", "J", "4", "P", "U", "K",
+            ],
+            KeyLayout::Numpad => [
+                "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "/", "*", "-", "+", "Enter", ".",
+            ],
+        }
+    }
+}
+
+/// This struct maps `KeyboardEvent.code` strings to keypad keys.
+/// A code binds to one keypad key, a keypad key may have several codes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Keymap {
+    bindings: BTreeMap<String, u8>,
+}
+
+impl Keymap {
+    /// This function creates a keymap with the bindings of a built-in layout.
+    /// Arguments:
+    /// - layout: The layout to start from.
+    ///
+    /// Returns: The keymap.
+    pub fn from_layout(layout: KeyLayout) -> Keymap {
+        let bindings = layout
+            .codes()
+            .iter()
+            .enumerate()
+            .map(|(key, code)| (code.to_string(), key as u8))
+            .collect();
+        Keymap { bindings }
+    }
+
+    /// This function binds a key code to a keypad key, replacing its previous binding.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: The updated keymap, or InvalidKeypadKey when the key is > 0xF.
+    pub fn bind(&mut self, code: &str, key: u8) -> Result<&mut Self, Chip8Error> {
+        validate_key(key)?;
+        self.bindings.insert(code.to_owned(), key);
+        Ok(self)
+    }
+
+    /// This function removes the binding of a key code.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    ///
+    /// Returns: The keypad key it was bound to, if any.
+    pub fn unbind(&mut self, code: &str) -> Option<u8> {
+        self.bindings.remove(code)
+    }
+
+    /// This function returns the keypad key bound to a key code.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    ///
+    /// Returns: The keypad key, or None when the code is unbound.
+    pub fn key(&self, code: &str) -> Option<u8> {
+        self.bindings.get(code).copied()
+    }
+
+    /// This function returns the key codes bound to a keypad key.
+    /// Arguments:
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: The bound codes in sorted order.
+    pub fn codes(&self, key: u8) -> Vec<&str> {
+        self.bindings
+            .iter()
+            .filter(|&(_, &bound)| bound == key)
+            .map(|(code, _)| code.as_str())
+            .collect()
+    }
+
+    // Applies a set of bindings on top of this keymap
+    fn extend(&mut self, bindings: &BTreeMap<String, u8>) -> Result<(), Chip8Error> {
+        for (code, &key) in bindings {
+            self.bind(code, key)?;
+        }
+        Ok(())
+    }
+}
+
+/// This struct stores a user's key configuration: a layout, custom bindings and per-ROM
+/// overrides keyed by the ROM's SHA-1. It serializes to JSON so frontends can persist it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct KeymapProfile {
+    /// This field stores the base layout.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub layout: KeyLayout,
+    /// This field stores bindings applied on top of the layout for every ROM.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bindings: BTreeMap<String, u8>,
+    /// This field stores bindings for single ROMs, keyed by the lowercase hex SHA-1 of the ROM.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rom_bindings: BTreeMap<String, BTreeMap<String, u8>>,
+}
+
+impl KeymapProfile {
+    /// This function creates a profile without custom bindings.
+    /// Arguments:
+    /// - layout: The base layout.
+    ///
+    /// Returns: The profile.
+    pub fn new(layout: KeyLayout) -> KeymapProfile {
+        KeymapProfile {
+            layout,
+            ..KeymapProfile::default()
+        }
+    }
+
+    /// This function parses a profile from JSON.
+    /// Arguments:
+    /// - json: The JSON produced by `to_json`.
+    ///
+    /// Returns: The profile, otherwise InvalidKeymapProfile or InvalidKeypadKey.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<KeymapProfile, Chip8Error> {
+        let mut profile: KeymapProfile = serde_json::from_str(json)
+            .map_err(|error| Chip8Error::InvalidKeymapProfile(error.to_string()))?;
+        profile.rom_bindings = profile
+            .rom_bindings
+            .into_iter()
+            .map(|(sha1, bindings)| (sha1.to_ascii_lowercase(), bindings))
+            .collect();
+        // resolving every keymap validates all keys
+        for sha1 in profile.rom_bindings.keys() {
+            profile.keymap_for_rom(sha1)?;
+        }
+        profile.keymap()?;
+        Ok(profile)
+    }
+
+    /// This function serializes the profile to JSON.
+    /// Arguments: none.
+    /// Returns: The JSON string, otherwise InvalidKeymapProfile.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, Chip8Error> {
+        serde_json::to_string(self)
+            .map_err(|error| Chip8Error::InvalidKeymapProfile(error.to_string()))
+    }
+
+    /// This function adds a binding for every ROM.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: The updated profile, or InvalidKeypadKey when the key is > 0xF.
+    pub fn bind(&mut self, code: &str, key: u8) -> Result<&mut Self, Chip8Error> {
+        validate_key(key)?;
+        self.bindings.insert(code.to_owned(), key);
+        Ok(self)
+    }
+
+    /// This function adds a binding for a single ROM.
+    /// Arguments:
+    /// - sha1: The hex SHA-1 of the ROM, see `rom_db::sha1_hex`.
+    /// - code: The `KeyboardEvent.code` string.
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: The updated profile, or InvalidKeypadKey when the key is > 0xF.
+    pub fn bind_for_rom(
+        &mut self,
+        sha1: &str,
+        code: &str,
+        key: u8,
+    ) -> Result<&mut Self, Chip8Error> {
+        validate_key(key)?;
+        self.rom_bindings
+            .entry(sha1.to_ascii_lowercase())
+            .or_default()
+            .insert(code.to_owned(), key);
+        Ok(self)
+    }
+
+    /// This function resolves the keymap used when no ROM specific bindings apply.
+    /// Arguments: none.
+    /// Returns: The layout with the profile bindings applied.
+    pub fn keymap(&self) -> Result<Keymap, Chip8Error> {
+        let mut keymap = Keymap::from_layout(self.layout);
+        keymap.extend(&self.bindings)?;
+        Ok(keymap)
+    }
+
+    /// This function resolves the keymap for a ROM.
+    /// Arguments:
+    /// - sha1: The hex SHA-1 of the ROM.
+    ///
+    /// Returns: The layout with the profile and ROM bindings applied.
+    pub fn keymap_for_rom(&self, sha1: &str) -> Result<Keymap, Chip8Error> {
+        let mut keymap = self.keymap()?;
+        if let Some(bindings) = self.rom_bindings.get(&sha1.to_ascii_lowercase()) {
+            keymap.extend(bindings)?;
+        }
+        Ok(keymap)
+    }
+}
+
+/// This struct tracks which key codes are held and turns them into a keypad mask.
+/// Holding two codes bound to the same keypad key keeps it pressed until both are released.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyboardState {
+    keymap: Keymap,
+    pressed: BTreeSet<String>,
+}
+
+impl KeyboardState {
+    /// This function creates a keyboard state with no keys held.
+    /// Arguments:
+    /// - keymap: The keymap to translate codes with.
+    ///
+    /// Returns: The keyboard state.
+    pub fn new(keymap: Keymap) -> KeyboardState {
+        KeyboardState {
+            keymap,
+            pressed: BTreeSet::new(),
+        }
+    }
+
+    /// This function returns the active keymap.
+    /// Arguments: none.
+    /// Returns: The keymap.
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    /// This function replaces the keymap, keeping the held codes.
+    /// Arguments:
+    /// - keymap: The new keymap.
+    ///
+    /// Returns: none.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// This function records a key press.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    ///
+    /// Returns: True when the code is bound to a keypad key.
+    pub fn key_down(&mut self, code: &str) -> bool {
+        if self.keymap.key(code).is_none() {
+            return false;
+        }
+        self.pressed.insert(code.to_owned());
+        true
+    }
+
+    /// This function records a key release.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    ///
+    /// Returns: True when the code is bound to a keypad key.
+    pub fn key_up(&mut self, code: &str) -> bool {
+        self.pressed.remove(code);
+        self.keymap.key(code).is_some()
+    }
+
+    /// This function releases every held key, e.g. when the page loses focus.
+    /// Arguments: none.
+    /// Returns: none.
+    pub fn release_all(&mut self) {
+        self.pressed.clear();
+    }
+
+    /// This function returns the keypad state of the held keys.
+    /// Arguments: none.
+    /// Returns: A bitmask where bit N indicates whether keypad key N is pressed.
+    pub fn mask(&self) -> Chip8KeyMask {
+        self.pressed
+            .iter()
+            .filter_map(|code| self.keymap.key(code))
+            .fold(0, |mask, key| mask | (1 << key))
+    }
+}
+
+// Keypad keys are a single hex digit
+fn validate_key(key: u8) -> Result<(), Chip8Error> {
+    if key > 0xF {
+        return Err(Chip8Error::InvalidKeypadKey(key));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that the QWERTY layout matches the 4x4 block in ABOUT.md
+    fn test_qwerty_layout() {
+        let keymap = Keymap::from_layout(KeyLayout::Qwerty);
+        assert_eq!(keymap.key("Digit1"), Some(0x1));
+        assert_eq!(keymap.key("Digit4"), Some(0xC));
+        assert_eq!(keymap.key("KeyR"), Some(0xD));
+        assert_eq!(keymap.key("KeyX"), Some(0x0));
+        assert_eq!(keymap.key("KeyV"), Some(0xF));
+        assert_eq!(keymap.key("KeyP"), None);
+        assert_eq!(KeyLayout::from_name("Numpad"), Some(KeyLayout::Numpad));
+    }
+
+    #[test]
+    // Tests that bindings validate keys and that a code maps to a single key
+    fn test_bind() {
+        let mut keymap = Keymap::from_layout(KeyLayout::Numpad);
+        assert!(matches!(
+            keymap.bind("Space", 0x10),
+            Err(Chip8Error::InvalidKeypadKey(0x10))
+        ));
+        keymap
+            .bind("Space", 0x5)
+            .unwrap()
+            .bind("Numpad5", 0x6)
+            .unwrap();
+        assert_eq!(keymap.codes(0x5), vec!["Space"]);
+        assert_eq!(keymap.codes(0x6), vec!["Numpad5", "Numpad6"]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    // Tests that ROM bindings override profile bindings and survive a JSON round trip
+    fn test_profile_round_trip() {
+        let mut profile = KeymapProfile::new(KeyLayout::Azerty);
+        profile.bind("Space", 0x5).unwrap();
+        profile.bind_for_rom("ABCDEF", "Space", 0x6).unwrap();
+        let profile = KeymapProfile::from_json(&profile.to_json().unwrap()).unwrap();
+        assert_eq!(profile.layout, KeyLayout::Azerty);
+        assert_eq!(profile.keymap().unwrap().key("Space"), Some(0x5));
+        assert_eq!(
+            profile.keymap_for_rom("abcdef").unwrap().key("Space"),
+            Some(0x6)
+        );
+        assert!(matches!(
+            KeymapProfile::from_json(r#"{"bindings":{"Space":16}}"#),
+            Err(Chip8Error::InvalidKeypadKey(16))
+        ));
+    }
+
+    #[test]
+    // Tests that a keypad key stays pressed while any of its codes is held
+    fn test_keyboard_state() {
+        let mut keymap = Keymap::from_layout(KeyLayout::Qwerty);
+        keymap.bind("ArrowUp", 0x5).unwrap();
+        let mut keyboard = KeyboardState::new(keymap);
+        assert!(keyboard.key_down("KeyW"));
+        assert!(keyboard.key_down("ArrowUp"));
+        assert!(!keyboard.key_down("KeyP"));
+        assert_eq!(keyboard.mask(), 1 << 0x5);
+        keyboard.key_up("KeyW");
+        assert_eq!(keyboard.mask(), 1 << 0x5);
+        keyboard.key_up("ArrowUp");
+        assert_eq!(keyboard.mask(), 0);
+    }
+}
@@ -1,9 +1,45 @@
 #![crate_name = "chip8sys"]
 #![crate_type = "lib"]
+// Without `std` the core builds for bare-metal targets; tests always link std.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-extern crate getrandom;
-
+#[cfg(feature = "audio")]
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod batch;
+pub mod builder;
+pub mod bus;
+#[cfg(feature = "debugger")]
+pub mod cheat;
 pub mod chip8;
 pub mod chip8error;
+#[cfg(feature = "disasm")]
+pub mod control_flow;
+#[cfg(feature = "debugger")]
+pub mod coverage;
+#[cfg(feature = "debugger")]
+pub mod debug;
 mod decode;
+#[cfg(feature = "disasm")]
+pub mod detect;
+#[cfg(feature = "disasm")]
+pub mod disasm;
+pub mod font;
+#[cfg(feature = "debugger")]
+pub mod guard;
+pub mod halt;
+pub mod instruction;
+#[cfg(feature = "std")]
+pub mod keymap;
+pub mod machine;
+pub mod memory_map;
+pub mod platform;
+#[cfg(feature = "debugger")]
+pub mod profile;
+pub mod random;
+#[cfg(feature = "std")]
+pub mod rom_db;
 mod roms; // used for testing, may not be needed long term
+#[cfg(test)]
+mod snapshot;
+pub mod timing;
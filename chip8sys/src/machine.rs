@@ -0,0 +1,777 @@
+//! A platform-neutral interface to an emulated machine.
+//!
+//! Frontends that drive a `Machine` instead of a `Chip8Sys` only need the keypad mask, a frame
+//! loop, the display planes and the sound state, so a variant core can be swapped in at runtime
+//! without touching them. Save states are fixed-length byte images written into a caller-provided
+//! buffer, so they work without an allocator.
+
+use crate::bus::MEMORY_SIZE;
+use crate::chip8::{Chip8KeyMask, Chip8Quirks, Chip8Sys, FRAMEBUFFER_MAX_LEN, TimerMode};
+use crate::chip8error::Chip8Error;
+use crate::font::{BIG_FONT_LEN, BIG_GLYPH_LEN, Font, SMALL_FONT_LEN};
+use crate::halt::LoopDetector;
+use crate::memory_map::MemoryMap;
+use crate::platform::{CHIP8X_COLOR_COLUMNS, CHIP8X_COLOR_ROWS, Platform};
+use crate::random::{Rng, SplitMix64};
+use crate::timing::TimingModel;
+
+// Identifies a Chip8Sys save state and its layout version
+const STATE_MAGIC: [u8; 4] = *b"C8SS";
+const STATE_VERSION: u8 = 2;
+
+/// This constant defines the length in bytes of a `Chip8Sys` save state.
+pub const CHIP8_STATE_LEN: usize = STATE_MAGIC.len()
+    + 7 // version, platform, detected platform, quirks, timer mode, timing model, sound flag
+    + 2 // installed memory size
+    + SMALL_FONT_LEN + BIG_FONT_LEN + 1 + 2 // font glyphs, big glyph count and address
+    + 2 * 3 // load and entry addresses, each a flag and an address
+    + MEMORY_SIZE
+    + 16 // V0-VF
+    + 2 * 2 // I and the program counter
+    + 1 // stack pointer
+    + 16 * 2 // stack
+    + 2 // delay and sound timers
+    + 16 // delay timer cycle count
+    + FRAMEBUFFER_MAX_LEN
+    + 4 * 2 // keys, previous keys, key presses and key releases
+    + 2 // FX0A register and pressed key
+    + CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS
+    + 1 // background colour
+    + 8 * 4 + 4 // VIP clock
+    + 1 + 8 * 2; // seeded generator
+
+/// This struct is a read-only view of a machine's display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Framebuffer<'a> {
+    /// This field stores the display width in pixels.
+    pub width: usize,
+    /// This field stores the display height in pixels.
+    pub height: usize,
+    /// This field stores the number of bit planes; a pixel's colour index has one bit per plane.
+    pub planes: usize,
+    /// This field stores the planes one after another, each packed 8 pixels per byte with the
+    /// most significant bit leftmost, row by row.
+    pub pixels: &'a [u8],
+}
+
+impl Framebuffer<'_> {
+    /// This function returns the length of one plane in bytes.
+    /// Arguments: none.
+    /// Returns: The plane length.
+    pub fn plane_len(&self) -> usize {
+        self.width * self.height / 8
+    }
+
+    /// This function returns one bit plane.
+    /// Arguments:
+    /// - plane: The plane index, 0 for the first plane.
+    ///
+    /// Returns: The packed plane, or None when the machine has fewer planes.
+    pub fn plane(&self, plane: usize) -> Option<&[u8]> {
+        if plane >= self.planes {
+            return None;
+        }
+        self.pixels
+            .get(plane * self.plane_len()..(plane + 1) * self.plane_len())
+    }
+
+    /// This function returns the colour index of a pixel.
+    /// Arguments:
+    /// - x: The column, 0 on the left.
+    /// - y: The row, 0 at the top.
+    ///
+    /// Returns: The colour index with bit N taken from plane N, 0 when the pixel is off the
+    /// display.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let bit = y * self.width + x;
+        (0..self.planes).fold(0, |color, plane| {
+            let byte = self.pixels[plane * self.plane_len() + bit / 8];
+            color | (((byte >> (7 - bit % 8)) & 1) << plane)
+        })
+    }
+}
+
+/// This struct describes what a machine's speaker is doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioState {
+    /// This field stores whether the sound timer is running.
+    pub is_playing: bool,
+    /// This field stores a 1-bit sample pattern for machines with a programmable buzzer, like
+    /// XO-CHIP, or None for a plain tone.
+    pub pattern: Option<[u8; 16]>,
+    /// This field stores the pattern playback pitch as XO-CHIP's pitch register, where 64 plays
+    /// the pattern at 4000 bits per second.
+    pub pitch: u8,
+}
+
+/// This trait is what a frontend needs to host a CHIP-8 family machine.
+pub trait Machine {
+    /// This function resets the machine while keeping its configuration.
+    /// Arguments: none.
+    /// Returns: none.
+    fn reset(&mut self);
+
+    /// This function loads a program into memory.
+    /// Arguments:
+    /// - rom_bytes: The ROM image.
+    ///
+    /// Returns: none.
+    fn load_rom_bytes(&mut self, rom_bytes: &[u8]);
+
+    /// This function executes a single instruction.
+    /// Arguments: none.
+    /// Returns: A result indicating whether execution succeeded.
+    fn step(&mut self) -> Result<(), Chip8Error>;
+
+    /// This function runs one 60Hz frame, including its timer tick.
+    /// Arguments:
+    /// - cycles: The instructions to run in the frame, for machines that are not cycle timed.
+    ///
+    /// Returns: A result indicating whether execution succeeded.
+    fn run_frame(&mut self, cycles: u32) -> Result<(), Chip8Error>;
+
+    /// This function sets the keypad state from a 16-bit mask.
+    /// Arguments:
+    /// - mask: A bitmask where bit N indicates whether key N is pressed.
+    ///
+    /// Returns: none.
+    fn set_keys_mask(&mut self, mask: Chip8KeyMask);
+
+    /// This function returns the current display.
+    /// Arguments: none.
+    /// Returns: The display planes and their size.
+    fn framebuffer(&self) -> Framebuffer<'_>;
+
+    /// This function returns the speaker state.
+    /// Arguments: none.
+    /// Returns: The speaker state.
+    fn audio(&self) -> AudioState;
+
+    /// This function returns the length of a save state.
+    /// Arguments: none.
+    /// Returns: The number of bytes `save_state` writes.
+    fn state_len(&self) -> usize;
+
+    /// This function writes the machine state into a buffer.
+    /// Arguments:
+    /// - state: The buffer, at least `state_len` bytes long.
+    ///
+    /// Returns: The number of bytes written, or StateBufferTooSmall.
+    fn save_state(&self, state: &mut [u8]) -> Result<usize, Chip8Error>;
+
+    /// This function restores the machine state written by `save_state`.
+    /// Arguments:
+    /// - state: The saved state.
+    ///
+    /// Returns: Ok on success, otherwise InvalidSaveState with the offset of the bad byte; the
+    /// machine is unchanged on error.
+    fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error>;
+}
+
+impl Machine for Chip8Sys {
+    fn reset(&mut self) {
+        Chip8Sys::reset(self);
+    }
+
+    fn load_rom_bytes(&mut self, rom_bytes: &[u8]) {
+        Chip8Sys::load_rom_bytes(self, rom_bytes);
+    }
+
+    fn step(&mut self) -> Result<(), Chip8Error> {
+        self.run()
+    }
+
+    /// This function runs one 60Hz frame. Under `TimingModel::Instruction` it runs `cycles`
+    /// instructions and ends the frame with a timer tick; under `TimingModel::CosmacVip` it
+    /// runs up to the next display interrupt and ignores `cycles`.
+    /// Arguments:
+    /// - cycles: The instructions to run under `TimingModel::Instruction`.
+    ///
+    /// Returns: A result indicating whether execution succeeded.
+    fn run_frame(&mut self, cycles: u32) -> Result<(), Chip8Error> {
+        match self.timing_model {
+            TimingModel::Instruction => {
+                self.tick(cycles)?;
+                if self.timer_mode() == TimerMode::External {
+                    self.tick_timers(1);
+                } else {
+                    // the timers already ran inside the instructions
+                    self.end_frame();
+                }
+            }
+            TimingModel::CosmacVip => {
+                let interrupts = self.vip_clock.interrupts;
+                while self.vip_clock.interrupts == interrupts {
+                    self.run()?;
+                }
+                if self.timer_mode() == TimerMode::External {
+                    self.tick_timers(1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_keys_mask(&mut self, mask: Chip8KeyMask) {
+        Chip8Sys::set_keys_mask(self, mask);
+    }
+
+    fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer {
+            width: self.display_width(),
+            height: self.display_height(),
+            planes: 1,
+            pixels: self.framebuffer_packed(),
+        }
+    }
+
+    fn audio(&self) -> AudioState {
+        AudioState {
+            is_playing: self.is_sound_playing(),
+            pattern: None,
+            pitch: 64,
+        }
+    }
+
+    fn state_len(&self) -> usize {
+        CHIP8_STATE_LEN
+    }
+
+    /// This function writes the CPU, RAM, memory map, display, keypad, timers, platform,
+    /// quirks, VIP clock and a seeded random sequence into a buffer. Memory hooks, the write guard, coverage,
+    /// profiling and cheats are debugger settings and are not saved.
+    /// Arguments:
+    /// - state: The buffer, at least `CHIP8_STATE_LEN` bytes long.
+    ///
+    /// Returns: `CHIP8_STATE_LEN`, or StateBufferTooSmall.
+    fn save_state(&self, state: &mut [u8]) -> Result<usize, Chip8Error> {
+        let Some(state) = state.get_mut(..CHIP8_STATE_LEN) else {
+            return Err(Chip8Error::StateBufferTooSmall(CHIP8_STATE_LEN));
+        };
+        let mut writer = StateWriter { state, offset: 0 };
+        writer.bytes(&STATE_MAGIC);
+        writer.u8(STATE_VERSION);
+        writer.u8(platform_id(self.platform));
+        writer.bool(self.is_platform_detected);
+        writer.u8(quirk_bits(self.quirks()));
+        writer.bool(self.timer_mode() == TimerMode::External);
+        writer.bool(self.timing_model == TimingModel::CosmacVip);
+        writer.bool(self.is_playing_sound);
+        let map = self.memory_map();
+        writer.u16(map.memory_size as u16);
+        writer.bytes(&map.font.small);
+        writer.bytes(&map.font.big);
+        writer.u8(map.font.big_glyphs);
+        writer.u16(map.font.address);
+        writer.optional_address(map.load_address);
+        writer.optional_address(map.entry_address);
+        writer.bytes(&self.memory[..]);
+        writer.bytes(&self.register);
+        writer.u16(self.register_i);
+        writer.u16(self.program_counter);
+        writer.u8(self.stack_pointer);
+        for address in self.stack {
+            writer.u16(address);
+        }
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        writer.bytes(&self.dt_cycle_ct.to_be_bytes());
+        writer.bytes(&self.frame_buffer);
+        writer.u16(self.keys_mask());
+        writer.u16(key_mask(&self.previous_keys));
+        writer.u16(self.key_presses);
+        writer.u16(self.key_releases);
+        writer.u8(self.wait_for_key_press.unwrap_or(NO_KEY));
+        writer.u8(self.wait_pressed_key.unwrap_or(NO_KEY));
+        writer.bytes(&self.color_map);
+        writer.u8(self.background_color);
+        writer.u64(self.vip_clock.total_cycles);
+        writer.u64(self.vip_clock.interrupts);
+        writer.u32(self.vip_clock.cycles_to_interrupt);
+        writer.u64(self.vip_clock.target_cycles);
+        writer.u64(self.vip_clock.micros_remainder);
+        match &self.rng {
+            Rng::Seeded(generator) => {
+                writer.bool(true);
+                writer.u64(generator.seed());
+                writer.u64(generator.state());
+            }
+            _ => {
+                writer.bool(false);
+                writer.u64(0);
+                writer.u64(0);
+            }
+        }
+        Ok(writer.offset)
+    }
+
+    /// This function restores a state written by `save_state`. The halt detector restarts, a
+    /// seeded random sequence continues where it was saved and any other generator is kept.
+    /// Arguments:
+    /// - state: The saved state.
+    ///
+    /// Returns: Ok on success, otherwise InvalidSaveState with the offset of the bad byte.
+    fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+        // everything is parsed before the system is touched so a bad state changes nothing
+        let mut reader = StateReader { state, offset: 0 };
+        if reader.bytes(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(Chip8Error::InvalidSaveState(0));
+        }
+        reader.expect(|version| version == STATE_VERSION)?;
+        let platform = reader.map(platform_from_id)?;
+        let is_platform_detected = reader.bool()?;
+        let quirks = reader.map(quirks_from_bits)?;
+        let timer_mode = match reader.bool()? {
+            true => TimerMode::External,
+            false => TimerMode::Cycle,
+        };
+        let timing_model = match reader.bool()? {
+            true => TimingModel::CosmacVip,
+            false => TimingModel::Instruction,
+        };
+        let is_playing_sound = reader.bool()?;
+        let map_offset = reader.offset;
+        let map = MemoryMap {
+            memory_size: reader.u16()? as usize,
+            font: Font {
+                small: reader.array()?,
+                big: reader.array()?,
+                big_glyphs: reader
+                    .expect(|glyphs| glyphs as usize <= BIG_FONT_LEN / BIG_GLYPH_LEN)?,
+                address: reader.u16()?,
+            },
+            load_address: reader.optional_address()?,
+            entry_address: reader.optional_address()?,
+        };
+        map.check(platform)
+            .map_err(|_| Chip8Error::InvalidSaveState(map_offset))?;
+        let memory = reader.bytes(MEMORY_SIZE)?;
+        let register = reader.array::<16>()?;
+        let register_i = reader.u16()?;
+        let program_counter = reader.u16()?;
+        let stack_pointer = reader.expect(|pointer| pointer <= 16)?;
+        let mut stack = [0; 16];
+        for address in &mut stack {
+            *address = reader.u16()?;
+        }
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let dt_cycle_ct = u128::from_be_bytes(reader.array()?);
+        let frame_buffer = reader.array::<FRAMEBUFFER_MAX_LEN>()?;
+        let keys = reader.u16()?;
+        let previous_keys = reader.u16()?;
+        let key_presses = reader.u16()?;
+        let key_releases = reader.u16()?;
+        let wait_for_key_press = reader.optional_key()?;
+        let wait_pressed_key = reader.optional_key()?;
+        let color_map = reader.array::<{ CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS }>()?;
+        let background_color = reader.u8()?;
+        let total_cycles = reader.u64()?;
+        let interrupts = reader.u64()?;
+        let cycles_to_interrupt = reader.u32()?;
+        let target_cycles = reader.u64()?;
+        let micros_remainder = reader.u64()?;
+        let is_seeded = reader.bool()?;
+        let (seed, rng_state) = (reader.u64()?, reader.u64()?);
+
+        self.platform = platform;
+        self.is_platform_detected = is_platform_detected;
+        self.set_quirks(quirks);
+        self.set_timer_mode(timer_mode);
+        self.timing_model = timing_model;
+        self.is_playing_sound = is_playing_sound;
+        self.set_memory_map(map)
+            .expect("the memory map was checked");
+        self.memory.copy_from_slice(memory);
+        self.register = register;
+        self.register_i = register_i;
+        self.program_counter = program_counter;
+        self.stack_pointer = stack_pointer;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.dt_cycle_ct = dt_cycle_ct;
+        self.frame_buffer = frame_buffer;
+        self.is_display_dirty = true;
+        self.keys = key_array(keys);
+        self.previous_keys = key_array(previous_keys);
+        self.key_presses = key_presses;
+        self.key_releases = key_releases;
+        self.wait_for_key_press = wait_for_key_press;
+        self.wait_pressed_key = wait_pressed_key;
+        self.color_map = color_map;
+        self.background_color = background_color;
+        self.vip_clock.total_cycles = total_cycles;
+        self.vip_clock.interrupts = interrupts;
+        self.vip_clock.cycles_to_interrupt = cycles_to_interrupt;
+        self.vip_clock.target_cycles = target_cycles;
+        self.vip_clock.micros_remainder = micros_remainder;
+        if is_seeded {
+            self.rng = Rng::Seeded(SplitMix64::resume(seed, rng_state));
+        }
+        self.loop_detector = LoopDetector::default();
+        Ok(())
+    }
+}
+
+// Stands in for "no key" in the FX0A fields
+const NO_KEY: u8 = 0xFF;
+
+fn platform_id(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::HiresChip8 => 1,
+        Platform::Chip8X => 2,
+    }
+}
+
+fn platform_from_id(id: u8) -> Option<Platform> {
+    match id {
+        0 => Some(Platform::Chip8),
+        1 => Some(Platform::HiresChip8),
+        2 => Some(Platform::Chip8X),
+        _ => None,
+    }
+}
+
+// One bit per quirk, in Chip8Quirks field order
+fn quirk_bits(quirks: Chip8Quirks) -> u8 {
+    [
+        quirks.increment_i_on_store,
+        quirks.reset_vf_on_logic,
+        quirks.wrap_draw,
+        quirks.shift_uses_vx,
+        quirks.wait_for_key_release,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (index, &is_set)| bits | ((is_set as u8) << index))
+}
+
+fn quirks_from_bits(bits: u8) -> Option<Chip8Quirks> {
+    if bits >> 5 != 0 {
+        return None;
+    }
+    let is_set = |index: u8| bits & (1 << index) != 0;
+    Some(Chip8Quirks {
+        increment_i_on_store: is_set(0),
+        reset_vf_on_logic: is_set(1),
+        wrap_draw: is_set(2),
+        shift_uses_vx: is_set(3),
+        wait_for_key_release: is_set(4),
+    })
+}
+
+fn key_mask(keys: &[bool; 16]) -> Chip8KeyMask {
+    (0..16).fold(0, |mask, key| mask | ((keys[key] as u16) << key))
+}
+
+fn key_array(mask: Chip8KeyMask) -> [bool; 16] {
+    core::array::from_fn(|key| mask & (1 << key) != 0)
+}
+
+// Appends big-endian fields to a buffer already checked to be long enough
+struct StateWriter<'a> {
+    state: &'a mut [u8],
+    offset: usize,
+}
+
+impl StateWriter<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.state[self.offset..self.offset + bytes.len()].copy_from_slice(bytes);
+        self.offset += bytes.len();
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn optional_address(&mut self, address: Option<u16>) {
+        self.bool(address.is_some());
+        self.u16(address.unwrap_or(0));
+    }
+}
+
+// Reads big-endian fields, reporting the offset of a truncated or invalid one
+struct StateReader<'a> {
+    state: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> StateReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        let bytes = self
+            .state
+            .get(self.offset..self.offset + len)
+            .ok_or(Chip8Error::InvalidSaveState(self.state.len()))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Chip8Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // Reads a byte and converts it, failing at its offset when that gives None
+    fn map<T>(&mut self, convert: impl FnOnce(u8) -> Option<T>) -> Result<T, Chip8Error> {
+        let offset = self.offset;
+        convert(self.u8()?).ok_or(Chip8Error::InvalidSaveState(offset))
+    }
+
+    fn expect(&mut self, is_valid: impl FnOnce(u8) -> bool) -> Result<u8, Chip8Error> {
+        self.map(|value| is_valid(value).then_some(value))
+    }
+
+    fn bool(&mut self) -> Result<bool, Chip8Error> {
+        self.map(|value| match value {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        })
+    }
+
+    fn optional_key(&mut self) -> Result<Option<u8>, Chip8Error> {
+        self.map(|value| match value {
+            NO_KEY => Some(None),
+            0..=0xF => Some(Some(value)),
+            _ => None,
+        })
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn optional_address(&mut self) -> Result<Option<u16>, Chip8Error> {
+        let is_set = self.bool()?;
+        let address = self.u16()?;
+        Ok(is_set.then_some(address))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Chip8Error> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A short program: set the delay timer, draw the 0 glyph at 0,0 and count up in V1 forever
+    const ROM: [u8; 12] = [
+        0x6A, 0x0A, 0xFA, 0x15, 0xA0, 0x50, 0xD1, 0x15, 0x71, 0x01, 0x12, 0x08,
+    ];
+
+    // Runs the ROM for a frame with a seeded generator and saves the state
+    fn saved_machine() -> (Chip8Sys, [u8; CHIP8_STATE_LEN]) {
+        let mut machine = Chip8Sys::new_chip_8();
+        machine.set_random_seed(Some(7));
+        Machine::load_rom_bytes(&mut machine, &ROM);
+        machine.run_frame(10).unwrap();
+        let mut state = [0; CHIP8_STATE_LEN];
+        assert_eq!(machine.save_state(&mut state).unwrap(), CHIP8_STATE_LEN);
+        (machine, state)
+    }
+
+    #[test]
+    // Tests that a restored machine runs on with the same registers and timers
+    fn test_state_registers() {
+        let (mut machine, state) = saved_machine();
+        let mut restored = Chip8Sys::new_chip_8();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.program_counter, machine.program_counter);
+        machine.run_frame(10).unwrap();
+        restored.run_frame(10).unwrap();
+        assert_eq!(restored.register, machine.register);
+        assert_eq!(restored.register_i, machine.register_i);
+        assert_eq!(restored.delay_timer, machine.delay_timer);
+    }
+
+    #[test]
+    // Tests that a seeded random sequence continues where it was saved
+    fn test_state_random_sequence() {
+        let (mut machine, state) = saved_machine();
+        let mut restored = Chip8Sys::new_chip_8();
+        restored.load_state(&state).unwrap();
+        for _ in 0..4 {
+            assert_eq!(
+                restored.random_byte().unwrap(),
+                machine.random_byte().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    // Tests that the framebuffer is restored
+    fn test_state_framebuffer() {
+        let (machine, state) = saved_machine();
+        let mut restored = Chip8Sys::new_chip_8();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.framebuffer(), machine.framebuffer());
+        assert_eq!(restored.framebuffer().pixel(0, 0), 1);
+        assert_eq!(restored.framebuffer().pixel(4, 0), 0);
+    }
+
+    #[test]
+    // Tests that the memory map is saved and replaces the one of the machine it is loaded into
+    fn test_state_memory_map() {
+        let mut machine = Chip8Sys::new_chip_8();
+        let map = MemoryMap {
+            memory_size: 2048,
+            entry_address: Some(0x602),
+            ..MemoryMap::eti_660()
+        };
+        machine.set_memory_map(map).unwrap();
+        let mut state = [0; CHIP8_STATE_LEN];
+        machine.save_state(&mut state).unwrap();
+
+        let mut restored = Chip8Sys::new_chip_8();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.memory_map(), map);
+        assert_eq!(restored.memory.size(), 2048);
+        assert_eq!(restored.program_counter, 0x602);
+        assert_eq!(restored.memory[0x050..0x055], machine.memory[0x050..0x055]);
+    }
+
+    #[test]
+    // Tests that a restored memory map is kept by a reset
+    fn test_state_memory_map_reset() {
+        let mut machine = Chip8Sys::new_chip_8();
+        let map = MemoryMap {
+            entry_address: Some(0x602),
+            ..MemoryMap::eti_660()
+        };
+        machine.set_memory_map(map).unwrap();
+        let mut state = [0; CHIP8_STATE_LEN];
+        machine.save_state(&mut state).unwrap();
+
+        let mut restored = Chip8Sys::new_chip_8();
+        restored.load_state(&state).unwrap();
+        restored.reset();
+        assert_eq!(restored.memory_map(), map);
+        assert_eq!(restored.program_counter, 0x602);
+    }
+
+    #[test]
+    // Tests that a buffer shorter than a state is rejected with the needed length
+    fn test_state_buffer_too_small() {
+        let machine = Chip8Sys::new_chip_8();
+        let mut state = [0; CHIP8_STATE_LEN - 1];
+        assert!(matches!(
+            machine.save_state(&mut state),
+            Err(Chip8Error::StateBufferTooSmall(CHIP8_STATE_LEN))
+        ));
+    }
+
+    #[test]
+    // Tests that a truncated state is rejected at its end
+    fn test_truncated_state() {
+        let (_, state) = saved_machine();
+        let mut machine = Chip8Sys::new_chip_8();
+        assert!(matches!(
+            machine.load_state(&state[..100]),
+            Err(Chip8Error::InvalidSaveState(100))
+        ));
+        assert!(matches!(
+            machine.load_state(&state[..CHIP8_STATE_LEN - 1]),
+            Err(Chip8Error::InvalidSaveState(len)) if len == CHIP8_STATE_LEN - 1
+        ));
+        assert_eq!(machine.program_counter, 0x200);
+    }
+
+    #[test]
+    // Tests that a state without the magic bytes is rejected at the start
+    fn test_foreign_state() {
+        let (_, mut state) = saved_machine();
+        state[0] = b'X';
+        let mut machine = Chip8Sys::new_chip_8();
+        assert!(matches!(
+            machine.load_state(&state),
+            Err(Chip8Error::InvalidSaveState(0))
+        ));
+    }
+
+    #[test]
+    // Tests that a state of another version is rejected at the version byte
+    fn test_state_version() {
+        let (_, mut state) = saved_machine();
+        state[5] = STATE_VERSION + 1;
+        let mut machine = Chip8Sys::new_chip_8();
+        assert!(matches!(
+            machine.load_state(&state),
+            Err(Chip8Error::InvalidSaveState(5))
+        ));
+    }
+
+    #[test]
+    // Tests that an unknown platform is rejected at its byte
+    fn test_invalid_state_platform() {
+        let (_, mut state) = saved_machine();
+        state[6] = 0xFF;
+        let mut machine = Chip8Sys::new_chip_8();
+        assert!(matches!(
+            machine.load_state(&state),
+            Err(Chip8Error::InvalidSaveState(6))
+        ));
+        assert_eq!(machine.platform, Platform::Chip8);
+    }
+
+    #[test]
+    // Tests that a state whose memory map does not fit is rejected at the map
+    fn test_invalid_state_memory_map() {
+        let mut machine = Chip8Sys::new_chip_8();
+        let mut state = [0; CHIP8_STATE_LEN];
+        machine.save_state(&mut state).unwrap();
+        // the memory size follows the magic and 7 flag bytes
+        state[11..13].copy_from_slice(&3000u16.to_be_bytes());
+        assert!(matches!(
+            machine.load_state(&state),
+            Err(Chip8Error::InvalidSaveState(11))
+        ));
+        assert_eq!(machine.memory_map(), MemoryMap::default());
+    }
+
+    #[test]
+    // Tests that a stack pointer past the stack is rejected at its byte without changing memory
+    fn test_invalid_state_stack_pointer() {
+        let (_, mut state) = saved_machine();
+        // the map, memory, registers, I and PC come before the stack pointer
+        let offset = 11 + 2 + SMALL_FONT_LEN + BIG_FONT_LEN + 1 + 2 + 3 + 3 + MEMORY_SIZE + 16 + 4;
+        state[offset] = 17;
+        let mut machine = Chip8Sys::new_chip_8();
+        assert!(matches!(
+            machine.load_state(&state),
+            Err(Chip8Error::InvalidSaveState(error)) if error == offset
+        ));
+        assert_eq!(machine.memory[0x200], 0);
+    }
+}
@@ -0,0 +1,271 @@
+//! Memory layouts of the machines CHIP-8 ran on.
+//!
+//! The COSMAC VIP loaded programs at 0x200, but the ETI-660 kept its interpreter and display below
+//! 0x600 and loaded programs there, and other hybrids used their own addresses. A `MemoryMap`
+//! sets the installed memory, the font, where ROMs are loaded and where execution starts, so such
+//! archives run unchanged. The map survives `reset`.
+
+use crate::bus;
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+use crate::font::{Font, FontSet};
+use crate::platform::Platform;
+
+/// This struct describes where a machine keeps its font and programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// This field stores the installed memory in bytes, a power of two from 1024 to 4096.
+    pub memory_size: usize,
+    /// This field stores the font and its address.
+    pub font: Font,
+    /// This field stores the address ROMs are loaded at, or None for the platform's.
+    pub load_address: Option<u16>,
+    /// This field stores the address execution starts at, or None for the load address.
+    pub entry_address: Option<u16>,
+}
+
+impl Default for MemoryMap {
+    /// This function returns the layout `Chip8Sys::new_chip_8` uses: 4KB, the CHIP-48 font at
+    /// 0x050 and programs at the platform's program start.
+    /// Arguments: none.
+    /// Returns: The default memory map.
+    fn default() -> Self {
+        MemoryMap {
+            memory_size: 4096,
+            font: Font::default(),
+            load_address: None,
+            entry_address: None,
+        }
+    }
+}
+
+impl MemoryMap {
+    /// This function returns the COSMAC VIP layout: 4KB, the VIP font and programs at 0x200.
+    /// Arguments: none.
+    /// Returns: The memory map.
+    pub fn cosmac_vip() -> MemoryMap {
+        MemoryMap {
+            font: Font::new(FontSet::CosmacVip),
+            load_address: Some(0x200),
+            ..MemoryMap::default()
+        }
+    }
+
+    /// This function returns the ETI-660 layout: 4KB, the ETI-660 font and programs at 0x600.
+    /// Arguments: none.
+    /// Returns: The memory map.
+    pub fn eti_660() -> MemoryMap {
+        MemoryMap {
+            font: Font::new(FontSet::Eti660),
+            load_address: Some(0x600),
+            ..MemoryMap::default()
+        }
+    }
+
+    /// This function returns the DREAM 6800 layout: 4KB, the DREAM 6800 font and programs at
+    /// 0x200.
+    /// Arguments: none.
+    /// Returns: The memory map.
+    pub fn dream_6800() -> MemoryMap {
+        MemoryMap {
+            font: Font::new(FontSet::Dream6800),
+            load_address: Some(0x200),
+            ..MemoryMap::default()
+        }
+    }
+
+    // Checks the whole map for a platform, in the order set_memory_map reports errors
+    pub(crate) fn check(&self, platform: Platform) -> Result<(), Chip8Error> {
+        bus::check_size(self.memory_size)?;
+        self.font.check_fits(self.memory_size)?;
+        let font = self.font.range();
+        let load_address = self
+            .load_address
+            .unwrap_or_else(|| platform.program_start());
+        let entry_address = self.entry_address.unwrap_or(load_address);
+        for address in [load_address, entry_address] {
+            if font.contains(&address) || address as usize >= self.memory_size {
+                return Err(Chip8Error::InvalidLoadAddress(address));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Chip8Sys {
+    /// This function returns the active memory layout.
+    /// Arguments: none.
+    /// Returns: The memory map.
+    pub fn memory_map(&self) -> MemoryMap {
+        MemoryMap {
+            memory_size: self.memory.size(),
+            font: self.font,
+            load_address: self.load_address,
+            entry_address: self.entry_address,
+        }
+    }
+
+    /// This function applies a memory layout and moves the program counter to its entry address.
+    /// Nothing changes when the layout is invalid.
+    /// Arguments:
+    /// - map: The memory map.
+    ///
+    /// Returns: The updated Chip-8 system, or InvalidMemorySize, InvalidFontAddress when the
+    /// font does not fit, or InvalidLoadAddress when programs would load or start inside the
+    /// font or past the end of memory.
+    pub fn set_memory_map(&mut self, map: MemoryMap) -> Result<&mut Self, Chip8Error> {
+        // the whole map is checked first so an invalid one leaves memory untouched
+        map.check(self.platform)?;
+        self.memory
+            .set_size(map.memory_size)
+            .expect("the memory size was checked");
+        self.set_font(map.font).expect("the font was checked");
+        self.load_address = map.load_address;
+        self.entry_address = map.entry_address;
+        self.program_counter = self.entry_address();
+        Ok(self)
+    }
+
+    /// This function returns the address execution starts at after a reset.
+    /// Arguments: none.
+    /// Returns: The configured entry address, or the program start.
+    pub fn entry_address(&self) -> u16 {
+        self.entry_address.unwrap_or_else(|| self.program_start())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that an ETI-660 ROM is loaded at 0x600 and runs from there
+    fn test_eti_660() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_memory_map(MemoryMap::eti_660()).unwrap();
+        assert_eq!(chip8.program_counter, 0x600);
+        // 6A05 1602: set VA then jump to the second instruction
+        chip8.load_rom_bytes(&[0x6A, 0x05, 0x16, 0x02]);
+        assert_eq!(chip8.memory[0x600], 0x6A);
+        assert_eq!(chip8.memory[0x200], 0);
+        chip8.tick(2).unwrap();
+        assert_eq!(chip8.register[0xA], 5);
+        assert_eq!(chip8.program_counter, 0x602);
+    }
+
+    #[test]
+    // Tests that the layout and its font survive a reset
+    fn test_map_survives_reset() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_memory_map(MemoryMap::eti_660()).unwrap();
+        chip8.reset();
+        assert_eq!(chip8.memory_map(), MemoryMap::eti_660());
+        assert_eq!(chip8.program_counter, 0x600);
+        assert_eq!(chip8.memory[0x050 + 5], 0x20);
+    }
+
+    #[test]
+    // Tests that a separate entry address is used and survives a reset
+    fn test_entry_address() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        let map = MemoryMap {
+            entry_address: Some(0x260),
+            ..MemoryMap::default()
+        };
+        chip8.set_memory_map(map).unwrap();
+        assert_eq!(chip8.entry_address(), 0x260);
+        assert_eq!(chip8.program_counter, 0x260);
+        chip8.reset();
+        assert_eq!(chip8.program_counter, 0x260);
+    }
+
+    #[test]
+    // Tests that a map without a load address follows the platform's program start
+    fn test_platform_load_address() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_memory_map(MemoryMap::default()).unwrap();
+        assert_eq!(chip8.entry_address(), 0x200);
+        chip8.set_platform(Platform::Chip8X);
+        assert_eq!(chip8.entry_address(), 0x300);
+    }
+
+    #[test]
+    // Tests that a memory size other than a power of two is rejected
+    fn test_invalid_memory_size() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        let invalid = MemoryMap {
+            memory_size: 3000,
+            ..MemoryMap::default()
+        };
+        assert!(matches!(
+            chip8.set_memory_map(invalid),
+            Err(Chip8Error::InvalidMemorySize(3000))
+        ));
+        assert_eq!(chip8.memory.size(), 4096);
+    }
+
+    #[test]
+    // Tests that a font past the end of a smaller memory is rejected
+    fn test_font_past_memory() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        let invalid = MemoryMap {
+            memory_size: 1024,
+            font: Font::default().at(0x3C0),
+            ..MemoryMap::default()
+        };
+        assert!(matches!(
+            chip8.set_memory_map(invalid),
+            Err(Chip8Error::InvalidFontAddress(0x3C0))
+        ));
+        assert_eq!(chip8.memory_map(), MemoryMap::default());
+    }
+
+    #[test]
+    // Tests that a load address past the end of a smaller memory is rejected without changes
+    fn test_load_address_past_memory() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        let invalid = MemoryMap {
+            memory_size: 1024,
+            ..MemoryMap::eti_660()
+        };
+        assert!(matches!(
+            chip8.set_memory_map(invalid),
+            Err(Chip8Error::InvalidLoadAddress(0x600))
+        ));
+        assert_eq!(chip8.memory_map(), MemoryMap::default());
+        assert_eq!(chip8.memory[0x050], 0xF0);
+    }
+
+    #[test]
+    // Tests that an entry address inside the font is rejected
+    fn test_entry_address_in_font() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        let invalid = MemoryMap {
+            entry_address: Some(0x060),
+            ..MemoryMap::default()
+        };
+        assert!(matches!(
+            chip8.set_memory_map(invalid),
+            Err(Chip8Error::InvalidLoadAddress(0x060))
+        ));
+        assert_eq!(chip8.program_counter, 0x200);
+    }
+
+    #[test]
+    // Tests that a map whose font is valid but whose load address is not writes no glyphs
+    fn test_invalid_map_leaves_memory() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.memory[0x300] = 0xAA;
+        let invalid = MemoryMap {
+            font: Font::default().at(0x300),
+            load_address: Some(0x300),
+            ..MemoryMap::default()
+        };
+        assert!(matches!(
+            chip8.set_memory_map(invalid),
+            Err(Chip8Error::InvalidLoadAddress(0x300))
+        ));
+        assert_eq!(chip8.memory[0x300], 0xAA);
+        assert_eq!(chip8.memory_map(), MemoryMap::default());
+    }
+}
@@ -0,0 +1,396 @@
+//! Platform variants of the CHIP-8 interpreter.
+//!
+//! Besides the original 64x32 CHIP-8 this module describes two historic COSMAC VIP variants:
+//! the two-page hi-res CHIP-8 with a 64x64 display, and CHIP-8X for the VP-590 colour board.
+
+use crate::chip8::{
+    Chip8Sys, DISPLAY_HEIGHT, DISPLAY_WIDTH, FRAMEBUFFER_MAX_LEN, MAX_DISPLAY_HEIGHT,
+};
+
+/// This constant defines the first two bytes of every hi-res CHIP-8 program (`1260`).
+pub const HIRES_SIGNATURE: [u8; 2] = [0x12, 0x60];
+// The hi-res interpreter lives at 0x200-0x2BF and starts the program proper at 0x2C0
+const HIRES_ENTRY_JUMP: [u8; 2] = [0x12, 0xC0];
+
+/// This constant defines the number of rows in the CHIP-8X colour map.
+pub const CHIP8X_COLOR_ROWS: usize = 32;
+/// This constant defines the number of 8-pixel colour columns in the CHIP-8X colour map.
+pub const CHIP8X_COLOR_COLUMNS: usize = 8;
+/// This constant defines the number of pixel rows covered by one BXY0 colour zone.
+pub const CHIP8X_ZONE_HEIGHT: usize = 4;
+/// This constant defines the foreground colour the VP-590 starts with (red).
+pub const CHIP8X_DEFAULT_FOREGROUND: u8 = 1;
+
+/// This constant defines the VP-590 foreground palette as RGB values.
+pub const CHIP8X_FOREGROUND_PALETTE: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00], // 0 black
+    [0xFF, 0x00, 0x00], // 1 red
+    [0x00, 0x00, 0xFF], // 2 blue
+    [0xFF, 0x00, 0xFF], // 3 violet
+    [0x00, 0xFF, 0x00], // 4 green
+    [0xFF, 0xFF, 0x00], // 5 yellow
+    [0x00, 0xFF, 0xFF], // 6 aqua
+    [0xFF, 0xFF, 0xFF], // 7 white
+];
+/// This constant defines the VP-590 background colours in the order 02A0 cycles through them.
+pub const CHIP8X_BACKGROUND_PALETTE: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x80], // dark blue
+    [0x00, 0x00, 0x00], // black
+    [0x00, 0x80, 0x00], // dark green
+    [0x80, 0x00, 0x00], // dark red
+];
+
+/// This enum lists the CHIP-8 platforms the interpreter can emulate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// This platform is the original COSMAC VIP CHIP-8 with a 64x32 display.
+    #[default]
+    Chip8,
+    /// This platform is the two-page hi-res CHIP-8 with a 64x64 display.
+    HiresChip8,
+    /// This platform is CHIP-8X with the VP-590 colour board and programs at 0x300.
+    Chip8X,
+}
+
+impl Platform {
+    /// This function returns the display width in pixels.
+    /// Arguments: none.
+    /// Returns: The display width.
+    pub fn display_width(self) -> usize {
+        DISPLAY_WIDTH
+    }
+
+    /// This function returns the display height in pixels.
+    /// Arguments: none.
+    /// Returns: The display height.
+    pub fn display_height(self) -> usize {
+        match self {
+            Platform::HiresChip8 => MAX_DISPLAY_HEIGHT,
+            Platform::Chip8 | Platform::Chip8X => DISPLAY_HEIGHT,
+        }
+    }
+
+    /// This function returns the packed framebuffer length in bytes.
+    /// Arguments: none.
+    /// Returns: The packed framebuffer length.
+    pub fn framebuffer_len(self) -> usize {
+        self.display_width() * self.display_height() / 8
+    }
+
+    /// This function returns the address programs are loaded at.
+    /// Arguments: none.
+    /// Returns: The program start address.
+    pub fn program_start(self) -> u16 {
+        match self {
+            Platform::Chip8 | Platform::HiresChip8 => 0x200,
+            Platform::Chip8X => 0x300,
+        }
+    }
+
+    /// This function returns a human readable platform name.
+    /// Arguments: none.
+    /// Returns: The platform name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::HiresChip8 => "Hi-res CHIP-8",
+            Platform::Chip8X => "CHIP-8X",
+        }
+    }
+
+    /// This function detects platforms that can be recognised from the ROM bytes alone.
+    /// Only the hi-res signature is reliable, everything else is reported as CHIP-8.
+    /// Arguments:
+    /// - rom_bytes: The ROM bytes as they would be loaded at 0x200.
+    ///
+    /// Returns: The detected platform.
+    pub fn detect(rom_bytes: &[u8]) -> Platform {
+        if rom_bytes.starts_with(&HIRES_SIGNATURE) {
+            Platform::HiresChip8
+        } else {
+            Platform::Chip8
+        }
+    }
+}
+
+impl Chip8Sys {
+    /// This function returns the emulated platform.
+    /// Arguments: none.
+    /// Returns: The active platform.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// This function switches the emulated platform.
+    /// Clears the display, resets the CHIP-8X colours and moves the program counter to the
+    /// entry address.
+    /// Arguments:
+    /// - platform: The platform to emulate.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_platform(&mut self, platform: Platform) -> &mut Self {
+        self.platform = platform;
+        self.is_platform_detected = false;
+        self.frame_buffer = [0x00; FRAMEBUFFER_MAX_LEN];
+        self.is_display_dirty = true;
+        self.color_map = [CHIP8X_DEFAULT_FOREGROUND; CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS];
+        self.background_color = 0;
+        self.program_counter = self.entry_address();
+        self
+    }
+
+    /// This function returns the display width of the active platform.
+    /// Arguments: none.
+    /// Returns: The display width in pixels.
+    pub fn display_width(&self) -> usize {
+        self.platform.display_width()
+    }
+
+    /// This function returns the display height of the active platform.
+    /// Arguments: none.
+    /// Returns: The display height in pixels.
+    pub fn display_height(&self) -> usize {
+        self.platform.display_height()
+    }
+
+    /// This function returns the CHIP-8X foreground colour map.
+    /// The map has one palette index per 8-pixel column for each of the 32 display rows.
+    /// Arguments: none.
+    /// Returns: The colour map, indexed by `row * 8 + column`.
+    pub fn color_map(&self) -> &[u8] {
+        &self.color_map
+    }
+
+    /// This function returns the CHIP-8X background colour.
+    /// Arguments: none.
+    /// Returns: An index into `CHIP8X_BACKGROUND_PALETTE`.
+    pub fn background_color(&self) -> u8 {
+        self.background_color
+    }
+
+    // Switches to hi-res when a CHIP-8 ROM loaded at 0x200 carries the hi-res signature, and points the
+    // signature jump at the program proper since the hi-res interpreter is not loaded. A hi-res
+    // platform set up front gets the same jump.
+    pub(crate) fn detect_platform(&mut self, rom_bytes: &[u8]) {
+        let start = Platform::HiresChip8.program_start();
+        if self.program_start() != start || Platform::detect(rom_bytes) != Platform::HiresChip8 {
+            return;
+        }
+        match self.platform {
+            Platform::Chip8 => {
+                self.set_platform(Platform::HiresChip8);
+                self.is_platform_detected = true;
+            }
+            Platform::HiresChip8 => {}
+            Platform::Chip8X => return,
+        }
+        let start = start as usize;
+        self.memory[start..start + 2].copy_from_slice(&HIRES_ENTRY_JUMP);
+    }
+
+    // 0x02A0 - cycles the background through blue, black, green and red
+    pub(crate) fn cycle_background_color(&mut self) {
+        self.background_color = (self.background_color + 1) % CHIP8X_BACKGROUND_PALETTE.len() as u8;
+        self.is_display_dirty = true;
+    }
+
+    // 0xBXY0 - colours 8x4 zones, VX holds the column and width, VX+1 the zone row and height
+    pub(crate) fn set_zone_colors(&mut self, x: u8, y: u8) {
+        let horizontal = self.register[x as usize];
+        let vertical = self.register[((x + 1) & 0xF) as usize];
+        let color = self.register[y as usize] & 0x7;
+        let column_start = (horizontal & 0xF) as usize;
+        let column_end = column_start + (horizontal >> 4) as usize;
+        let zone_start = (vertical & 0xF) as usize;
+        let zone_end = zone_start + (vertical >> 4) as usize;
+        for zone in zone_start..=zone_end {
+            for row in zone * CHIP8X_ZONE_HEIGHT..(zone + 1) * CHIP8X_ZONE_HEIGHT {
+                for column in column_start..=column_end {
+                    self.set_color(row, column, color);
+                }
+            }
+        }
+    }
+
+    // 0xBXYN - colours the 8xN area a sprite drawn at (VX, VX+1) would cover
+    pub(crate) fn set_sprite_colors(&mut self, x: u8, y: u8, n: u8) {
+        let column = self.register[x as usize] as usize / 8;
+        let row_start = self.register[((x + 1) & 0xF) as usize] as usize;
+        let color = self.register[y as usize] & 0x7;
+        for row in row_start..row_start + n as usize {
+            self.set_color(row, column, color);
+        }
+    }
+
+    // Sets one colour map cell, wrapping around the edges like the VP-590 does
+    fn set_color(&mut self, row: usize, column: usize, color: u8) {
+        let row = row % CHIP8X_COLOR_ROWS;
+        let column = column % CHIP8X_COLOR_COLUMNS;
+        self.color_map[row * CHIP8X_COLOR_COLUMNS + column] = color;
+        self.is_display_dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decode::test::single_instruction_chip_8;
+    use crate::snapshot::assert_frame_snapshot;
+
+    #[test]
+    // Tests that ROMs starting with 1260 are detected as hi-res
+    fn test_detect_hires() {
+        assert_eq!(Platform::detect(&[0x12, 0x60, 0x00]), Platform::HiresChip8);
+        assert_eq!(Platform::detect(&[0x12, 0x00]), Platform::Chip8);
+        assert_eq!(Platform::detect(&[]), Platform::Chip8);
+    }
+
+    #[test]
+    // Tests that loading a hi-res ROM switches to 64x64 and patches the entry jump
+    fn test_load_hires_rom() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x12, 0x60]);
+        assert_eq!(chip8.platform(), Platform::HiresChip8);
+        assert_eq!(chip8.display_height(), 64);
+        assert_eq!(chip8.framebuffer_packed().len(), 512);
+        assert_eq!(chip8.memory[0x200..0x202], HIRES_ENTRY_JUMP);
+        chip8.run().unwrap();
+        assert_eq!(chip8.program_counter, 0x2C0);
+    }
+
+    #[test]
+    // Tests that a hi-res ROM loaded on a configured hi-res platform skips the interpreter too
+    fn test_load_hires_rom_configured() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_platform(Platform::HiresChip8);
+        chip8.load_rom_bytes(&[0x12, 0x60]);
+        assert_eq!(chip8.memory[0x200..0x202], HIRES_ENTRY_JUMP);
+        chip8.reset();
+        assert_eq!(chip8.platform(), Platform::HiresChip8);
+    }
+
+    #[test]
+    // Tests that a reset forgets a detected platform but keeps a configured one
+    fn test_reset_platform() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&[0x12, 0x60]);
+        chip8.reset();
+        assert_eq!(chip8.platform(), Platform::Chip8);
+        chip8.set_platform(Platform::Chip8X);
+        chip8.reset();
+        assert_eq!(chip8.platform(), Platform::Chip8X);
+        assert_eq!(chip8.program_counter, 0x300);
+    }
+
+    #[test]
+    // Tests that CHIP-8X ROMs are loaded at 0x300
+    fn test_chip8x_load_address() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_platform(Platform::Chip8X);
+        chip8.load_rom_bytes(&[0xAB, 0xCD]);
+        assert_eq!(chip8.memory[0x300..0x302], [0xAB, 0xCD]);
+        assert_eq!(chip8.memory[0x200], 0);
+    }
+
+    #[test]
+    // Tests that sprites drawn below row 32 land in the lower page in hi-res mode
+    fn test_hires_draw_lower_page() {
+        let mut chip8 = single_instruction_chip_8(0xD015);
+        chip8.set_platform(Platform::HiresChip8);
+        chip8.memory[0x200] = 0xD0;
+        chip8.memory[0x201] = 0x15;
+        chip8.register[0] = 4;
+        chip8.register[1] = 60;
+        chip8.register_i = 0x050;
+        chip8.set_quirks(crate::chip8::Chip8Quirks {
+            wrap_draw: true,
+            ..chip8.quirks()
+        });
+        chip8.run().unwrap();
+        assert_frame_snapshot(&chip8, "hires_draw_wrap");
+    }
+
+    #[test]
+    // Tests that 0230 clears the hi-res screen
+    fn test_hires_clear_screen() {
+        let mut chip8 = single_instruction_chip_8(0x0230);
+        chip8.set_platform(Platform::HiresChip8);
+        chip8.memory[0x200] = 0x02;
+        chip8.memory[0x201] = 0x30;
+        chip8.frame_buffer = [0xFF; FRAMEBUFFER_MAX_LEN];
+        chip8.run().unwrap();
+        assert_eq!(chip8.frame_buffer, [0x00; FRAMEBUFFER_MAX_LEN]);
+    }
+
+    #[test]
+    // Tests that 02A0 cycles through the four background colours
+    fn test_chip8x_cycle_background() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_platform(Platform::Chip8X);
+        for expected in [1, 2, 3, 0] {
+            chip8.memory[0x300] = 0x02;
+            chip8.memory[0x301] = 0xA0;
+            chip8.program_counter = 0x300;
+            chip8.run().unwrap();
+            assert_eq!(chip8.background_color(), expected);
+        }
+    }
+
+    #[test]
+    // Tests that BXY0 colours whole 8x4 zones
+    fn test_chip8x_zone_colors() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_platform(Platform::Chip8X);
+        chip8.memory[0x300] = 0xB4;
+        chip8.memory[0x301] = 0x60;
+        // columns 2-3, zone rows 1-2 in green
+        chip8.register[4] = 0x12;
+        chip8.register[5] = 0x11;
+        chip8.register[6] = 4;
+        chip8.run().unwrap();
+        for row in 0..CHIP8X_COLOR_ROWS {
+            for column in 0..CHIP8X_COLOR_COLUMNS {
+                let expected = if (4..12).contains(&row) && (2..=3).contains(&column) {
+                    4
+                } else {
+                    CHIP8X_DEFAULT_FOREGROUND
+                };
+                assert_eq!(
+                    chip8.color_map()[row * CHIP8X_COLOR_COLUMNS + column],
+                    expected,
+                    "Chip-8X 0xBXY0 colour at row {row} column {column} is wrong"
+                );
+            }
+        }
+    }
+
+    #[test]
+    // Tests that BXYN colours N single rows at sprite resolution
+    fn test_chip8x_sprite_colors() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_platform(Platform::Chip8X);
+        chip8.memory[0x300] = 0xB0;
+        chip8.memory[0x301] = 0x23;
+        chip8.register[0] = 17;
+        chip8.register[1] = 5;
+        chip8.register[2] = 7;
+        chip8.run().unwrap();
+        let colored: Vec<usize> = (0..CHIP8X_COLOR_ROWS * CHIP8X_COLOR_COLUMNS)
+            .filter(|index| chip8.color_map()[*index] == 7)
+            .collect();
+        assert_eq!(colored, vec![5 * 8 + 2, 6 * 8 + 2, 7 * 8 + 2]);
+    }
+
+    #[test]
+    // Tests that 5XY1 adds each nibble separately, modulo 8
+    fn test_chip8x_nibble_add() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_platform(Platform::Chip8X);
+        chip8.memory[0x300] = 0x50;
+        chip8.memory[0x301] = 0x11;
+        chip8.register[0] = 0x35;
+        chip8.register[1] = 0x64;
+        chip8.run().unwrap();
+        assert_eq!(chip8.register[0], 0x11);
+    }
+}
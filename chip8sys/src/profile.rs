@@ -0,0 +1,508 @@
+//! Instruction profiler with call-graph attribution.
+//!
+//! The profiler follows 2NNN and 00EE with a shadow of the CPU stack, so every instruction is
+//! charged to the subroutine it ran in and to every caller above it. Time is counted in
+//! instructions and in VIP machine cycles from the `timing` table, which match the hardware
+//! under `TimingModel::CosmacVip`. The results come out as a flat profile, a call tree and the
+//! instruction count of each frame, to spot frames over a cycle budget.
+//!
+//! A frame ends on each 60Hz VIP interrupt under `TimingModel::CosmacVip`, and on each
+//! `tick_timers` tick under `TimingModel::Instruction`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+
+use crate::chip8::Chip8Sys;
+use crate::coverage::SymbolFile;
+use crate::timing::{TimingModel, VIP_INTERRUPT_CYCLES, VipClock};
+
+// Frames kept for frames(), a minute at 60Hz
+const MAX_FRAMES: usize = 3600;
+
+/// This struct stores the time charged to a subroutine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ProfileStats {
+    /// This field stores the number of times the subroutine was called.
+    pub calls: u64,
+    /// This field stores the instructions run in the subroutine itself.
+    pub self_instructions: u64,
+    /// This field stores the machine cycles spent in the subroutine itself.
+    pub self_cycles: u64,
+    /// This field stores the instructions run in the subroutine and everything it called.
+    pub total_instructions: u64,
+    /// This field stores the machine cycles spent in the subroutine and everything it called.
+    pub total_cycles: u64,
+}
+
+/// This struct is one line of the flat profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FlatEntry {
+    /// This field stores the subroutine's address, the program start for the main program.
+    pub address: u16,
+    /// This field stores the time charged to every call of the subroutine.
+    pub stats: ProfileStats,
+}
+
+/// This struct is a subroutine in the call tree, one node per call path.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CallNode {
+    /// This field stores the subroutine's address, the program start for the root.
+    pub address: u16,
+    /// This field stores the time charged to the subroutine on this call path.
+    pub stats: ProfileStats,
+    /// This field stores the subroutines called on this path, by address.
+    pub children: Vec<CallNode>,
+}
+
+/// This struct stores the work done in one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FrameStats {
+    /// This field stores the frame number, counting from when profiling was turned on.
+    pub frame: u64,
+    /// This field stores the instructions run in the frame.
+    pub instructions: u32,
+    /// This field stores the machine cycles spent on instructions in the frame.
+    pub cycles: u32,
+}
+
+// A call tree node, children are indices into Profiler::nodes
+#[derive(Clone, Debug)]
+struct Node {
+    address: u16,
+    stats: ProfileStats,
+    children: BTreeMap<u16, usize>,
+}
+
+/// This struct collects the profile, see `Chip8Sys::set_profiling`.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    // the call tree, the root is node 0
+    nodes: Vec<Node>,
+    // the node of each active call, mirrors the CPU stack
+    stack: Vec<usize>,
+    flat: BTreeMap<u16, ProfileStats>,
+    frames: VecDeque<FrameStats>,
+    frame: FrameStats,
+}
+
+impl Profiler {
+    fn new(entry: u16) -> Profiler {
+        let root = Node {
+            address: entry,
+            stats: ProfileStats {
+                calls: 1,
+                ..ProfileStats::default()
+            },
+            children: BTreeMap::new(),
+        };
+        let mut flat = BTreeMap::new();
+        flat.insert(entry, root.stats);
+        Profiler {
+            nodes: vec![root],
+            stack: vec![0],
+            flat,
+            frames: VecDeque::new(),
+            frame: FrameStats::default(),
+        }
+    }
+
+    // Charges an executed instruction and follows calls and returns
+    fn record(&mut self, opcode: u16, cycles: u32, stack_pointer: u8) {
+        let cycles_wide = cycles as u64;
+        let top = *self.stack.last().unwrap_or(&0);
+        for (depth, &index) in self.stack.iter().enumerate() {
+            let address = self.nodes[index].address;
+            let stats = &mut self.nodes[index].stats;
+            stats.total_instructions += 1;
+            stats.total_cycles += cycles_wide;
+            // a recursive subroutine is on the stack more than once but only ran one instruction
+            let is_outermost = !self.stack[..depth]
+                .iter()
+                .any(|&outer| self.nodes[outer].address == address);
+            if is_outermost {
+                let flat = self.flat.entry(address).or_default();
+                flat.total_instructions += 1;
+                flat.total_cycles += cycles_wide;
+            }
+        }
+        let top_address = self.nodes[top].address;
+        self.nodes[top].stats.self_instructions += 1;
+        self.nodes[top].stats.self_cycles += cycles_wide;
+        let flat = self.flat.entry(top_address).or_default();
+        flat.self_instructions += 1;
+        flat.self_cycles += cycles_wide;
+        self.frame.instructions += 1;
+        self.frame.cycles = self.frame.cycles.saturating_add(cycles);
+
+        if opcode & 0xF000 == 0x2000 {
+            let address = opcode & 0x0FFF;
+            let next_index = self.nodes.len();
+            let child = *self.nodes[top]
+                .children
+                .entry(address)
+                .or_insert(next_index);
+            if child == next_index {
+                self.nodes.push(Node {
+                    address,
+                    stats: ProfileStats::default(),
+                    children: BTreeMap::new(),
+                });
+            }
+            self.nodes[child].stats.calls += 1;
+            self.flat.entry(address).or_default().calls += 1;
+            self.stack.push(child);
+        } else if opcode == 0x00EE && self.stack.len() > 1 {
+            self.stack.pop();
+        }
+        // stay in step with the CPU if profiling started inside a subroutine
+        self.stack.truncate(stack_pointer as usize + 1);
+    }
+
+    fn end_frame(&mut self) {
+        if self.frames.len() == MAX_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(self.frame);
+        self.frame = FrameStats {
+            frame: self.frame.frame + 1,
+            ..FrameStats::default()
+        };
+    }
+
+    /// This function returns the flat profile.
+    /// Arguments: none.
+    /// Returns: One entry per subroutine, the most expensive by self cycles first.
+    pub fn flat_profile(&self) -> Vec<FlatEntry> {
+        let mut entries: Vec<FlatEntry> = self
+            .flat
+            .iter()
+            .map(|(&address, &stats)| FlatEntry { address, stats })
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.stats.self_cycles));
+        entries
+    }
+
+    /// This function returns the call tree.
+    /// Arguments: none.
+    /// Returns: The root node, the program as entered at its start address.
+    pub fn call_tree(&self) -> CallNode {
+        self.call_node(0)
+    }
+
+    fn call_node(&self, index: usize) -> CallNode {
+        let node = &self.nodes[index];
+        CallNode {
+            address: node.address,
+            stats: node.stats,
+            children: node
+                .children
+                .values()
+                .map(|&child| self.call_node(child))
+                .collect(),
+        }
+    }
+
+    /// This function returns the finished frames.
+    /// Arguments: none.
+    /// Returns: Up to the last 3600 frames, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &FrameStats> {
+        self.frames.iter()
+    }
+
+    /// This function returns the finished frames that ran more than a budget.
+    /// Arguments:
+    /// - cycles: The machine-cycle budget per frame.
+    ///
+    /// Returns: The frames over budget, oldest first.
+    pub fn frames_over_budget(&self, cycles: u32) -> impl Iterator<Item = &FrameStats> {
+        self.frames
+            .iter()
+            .filter(move |frame| frame.cycles > cycles)
+    }
+
+    /// This function writes the flat profile and the call tree as text.
+    /// Arguments:
+    /// - symbols: An optional symbol file to name subroutines by their labels.
+    ///
+    /// Returns: The report.
+    pub fn report(&self, symbols: Option<&SymbolFile>) -> String {
+        let name = |address: u16| {
+            let label = symbols.and_then(|symbols| symbols.labels(address).next());
+            match label {
+                Some(label) => format!("{label} ({address:03X})"),
+                None => format!("{address:03X}"),
+            }
+        };
+        let mut report = String::from(
+            "Flat profile\n  calls   self inst  self cycles  total inst total cycles  subroutine\n",
+        );
+        for entry in self.flat_profile() {
+            let stats = entry.stats;
+            let _ = writeln!(
+                report,
+                "{:>7} {:>11} {:>12} {:>11} {:>12}  {}",
+                stats.calls,
+                stats.self_instructions,
+                stats.self_cycles,
+                stats.total_instructions,
+                stats.total_cycles,
+                name(entry.address)
+            );
+        }
+        report.push_str("\nCall tree (calls, total inst, total cycles)\n");
+        let mut pending = vec![(0, self.call_tree())];
+        while let Some((depth, node)) = pending.pop() {
+            let _ = writeln!(
+                report,
+                "{:indent$}{} {} {} {}",
+                "",
+                name(node.address),
+                node.stats.calls,
+                node.stats.total_instructions,
+                node.stats.total_cycles,
+                indent = depth * 2
+            );
+            pending.extend(
+                node.children
+                    .into_iter()
+                    .rev()
+                    .map(|child| (depth + 1, child)),
+            );
+        }
+        report
+    }
+}
+
+impl Chip8Sys {
+    /// This function turns the profiler on or off. Turning it off discards the profile.
+    /// The profile survives `reset`, so several runs can be profiled together.
+    /// Arguments:
+    /// - is_enabled: True to profile every instruction.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_profiling(&mut self, is_enabled: bool) -> &mut Self {
+        match (is_enabled, &self.profiler) {
+            (true, None) => {
+                let entry = self.entry_address();
+                self.profiler = Some(Box::new(Profiler::new(entry)));
+            }
+            (false, Some(_)) => self.profiler = None,
+            _ => {}
+        }
+        self
+    }
+
+    /// This function returns the profile collected so far.
+    /// Arguments: none.
+    /// Returns: The profiler, or None when profiling is off.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    // Called after each instruction with its estimated cost and the VIP clock from before it ran
+    pub(crate) fn profile_instruction(&mut self, opcode: u16, cycles: u32, clock_before: VipClock) {
+        let cycles = if self.timing_model() == TimingModel::CosmacVip {
+            // the clock also has the display wait; the display's share of the frame is not the
+            // instruction's
+            let interrupts = self.vip_clock.interrupts - clock_before.interrupts;
+            (self.vip_clock.total_cycles
+                - clock_before.total_cycles
+                - interrupts * VIP_INTERRUPT_CYCLES as u64) as u32
+        } else {
+            cycles
+        };
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(opcode, cycles, self.stack_pointer);
+        }
+    }
+
+    // Called on every 60Hz frame, see the module docs
+    pub(crate) fn profile_frame(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end_frame();
+        }
+    }
+
+    // The program starts over after a reset, so no subroutine is active any more
+    pub(crate) fn restart_profile(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.stack.truncate(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::timing::VIP_CYCLES_PER_FRAME;
+
+    // 2206 2206 1204 | 220A 00EE | 00EE: main calls 0x206 twice, which calls 0x20A
+    const ROM: [u8; 12] = [
+        0x22, 0x06, 0x22, 0x06, 0x12, 0x04, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE,
+    ];
+
+    fn profiled() -> Chip8Sys {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_profiling(true).load_rom_bytes(&ROM);
+        chip8.tick(9).unwrap();
+        chip8
+    }
+
+    // Collects the flat profile by subroutine address
+    fn flat(profiler: &Profiler) -> BTreeMap<u16, ProfileStats> {
+        profiler
+            .flat_profile()
+            .into_iter()
+            .map(|entry| (entry.address, entry.stats))
+            .collect()
+    }
+
+    #[test]
+    // Tests the calls and the self and total instructions of each subroutine
+    fn test_flat_profile() {
+        let chip8 = profiled();
+        let flat = flat(chip8.profiler().unwrap());
+        let counts = |address| {
+            let stats = flat[&address];
+            (
+                stats.calls,
+                stats.self_instructions,
+                stats.total_instructions,
+            )
+        };
+        assert_eq!(counts(0x200), (1, 3, 9));
+        assert_eq!(counts(0x206), (2, 4, 6));
+        assert_eq!(counts(0x20A), (2, 2, 2));
+    }
+
+    #[test]
+    // Tests that cycles come from the timing table and that the most expensive entry is first
+    fn test_flat_profile_cycles() {
+        let chip8 = profiled();
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(flat(profiler)[&0x200].total_cycles, 26 * 4 + 12 + 10 * 4);
+        let entries = profiler.flat_profile();
+        assert!(
+            entries
+                .windows(2)
+                .all(|pair| pair[0].stats.self_cycles >= pair[1].stats.self_cycles)
+        );
+    }
+
+    #[test]
+    // Tests that the call tree has one node per call path
+    fn test_call_tree() {
+        let chip8 = profiled();
+        let tree = chip8.profiler().unwrap().call_tree();
+        assert_eq!(tree.address, 0x200);
+        assert_eq!(tree.stats.calls, 1);
+        assert_eq!(tree.children.len(), 1);
+        assert_eq!(tree.children[0].address, 0x206);
+        assert_eq!(tree.children[0].stats.calls, 2);
+        assert_eq!(tree.children[0].children[0].address, 0x20A);
+        assert!(tree.children[0].children[0].children.is_empty());
+    }
+
+    #[test]
+    // Tests that the report names subroutines by their labels and indents the call tree
+    fn test_report() {
+        let chip8 = profiled();
+        let symbols = SymbolFile::parse("0x206 update\n0x20A draw").unwrap();
+        let report = chip8.profiler().unwrap().report(Some(&symbols));
+        assert!(report.starts_with("Flat profile\n"));
+        assert!(report.contains("\n  update (206) 2 6 "));
+        assert!(report.contains("\n    draw (20A) 2 2 "));
+        let report = chip8.profiler().unwrap().report(None);
+        assert!(report.contains("\n  206 2 6 "));
+    }
+
+    #[test]
+    // Tests that a 00EE without a matching call keeps charging the main program
+    fn test_return_without_call() {
+        let mut profiler = Profiler::new(0x200);
+        profiler.record(0x00EE, 10, 0);
+        profiler.record(0x6001, 6, 0);
+        assert_eq!(profiler.stack, [0]);
+        let root = profiler.call_tree();
+        assert_eq!(root.stats.self_instructions, 2);
+        assert_eq!(root.stats.total_cycles, 16);
+        assert!(root.children.is_empty());
+    }
+
+    #[test]
+    // Tests that returns out of subroutines entered before profiling started do not unwind
+    // past the main program, and that later calls are still followed
+    fn test_profiling_started_in_subroutine() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.load_rom_bytes(&ROM);
+        chip8.tick(2).unwrap();
+        assert_eq!(chip8.stack_pointer, 2);
+        chip8.set_profiling(true);
+        chip8.tick(2).unwrap();
+        assert_eq!(chip8.stack_pointer, 0);
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(profiler.stack, [0]);
+        assert_eq!(flat(profiler)[&0x200].self_instructions, 2);
+        chip8.tick(1).unwrap();
+        let tree = chip8.profiler().unwrap().call_tree();
+        assert_eq!(tree.children[0].address, 0x206);
+        assert_eq!(tree.children[0].stats.calls, 1);
+    }
+
+    #[test]
+    // Tests that frames end on tick_timers under the instruction model
+    fn test_frames() {
+        let mut chip8 = profiled();
+        chip8.tick_timers(1);
+        chip8.tick(1).unwrap();
+        chip8.tick_timers(1);
+        let frames: Vec<(u64, u32)> = chip8
+            .profiler()
+            .unwrap()
+            .frames()
+            .map(|frame| (frame.frame, frame.instructions))
+            .collect();
+        assert_eq!(frames, [(0, 9), (1, 1)]);
+    }
+
+    #[test]
+    // Tests that frames end on the VIP interrupt and are checked against a cycle budget
+    fn test_frames_vip() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8
+            .set_timing_model(TimingModel::CosmacVip)
+            .set_profiling(true)
+            .load_rom_bytes(&ROM);
+        chip8.run_for_microseconds(50_000).unwrap();
+        let profiler = chip8.profiler().unwrap();
+        let frames = chip8.vip_clock().interrupts as usize;
+        assert!(frames >= 2);
+        assert_eq!(profiler.frames().count(), frames);
+        assert_eq!(profiler.frames_over_budget(1000).count(), frames);
+        assert_eq!(profiler.frames_over_budget(VIP_CYCLES_PER_FRAME).count(), 0);
+    }
+
+    #[test]
+    // Tests that a reset keeps the profile but leaves every subroutine
+    fn test_reset() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_profiling(true).load_rom_bytes(&ROM);
+        chip8.tick(2).unwrap();
+        assert_eq!(chip8.profiler().unwrap().stack.len(), 3);
+        chip8.reset();
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(profiler.stack, [0]);
+        assert_eq!(profiler.call_tree().stats.total_instructions, 2);
+    }
+
+    #[test]
+    // Tests that profiling is off by default and that turning it off discards the profile
+    fn test_off() {
+        let mut chip8 = profiled();
+        chip8.set_profiling(false);
+        assert!(chip8.profiler().is_none());
+        chip8.set_profiling(true);
+        assert_eq!(chip8.profiler().unwrap().flat_profile().len(), 1);
+        assert!(Chip8Sys::new_chip_8().profiler().is_none());
+    }
+}
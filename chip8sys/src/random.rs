@@ -0,0 +1,210 @@
+//! Random numbers for CXNN.
+//!
+//! CXNN draws its bytes from a `RandomSource`. With the `os-rng` feature the system starts on the
+//! operating system's generator. Seeding the system switches to a small SplitMix64 generator so
+//! runs can be repeated, e.g. for regression sweeps. Without `os-rng` the system starts on
+//! SplitMix64 seeded with 0; boards seed it from a hardware generator or plug one in with
+//! `Chip8Sys::set_random_source`.
+
+use crate::chip8::Chip8Sys;
+use crate::chip8error::Chip8Error;
+
+/// This trait supplies the random bytes CXNN masks.
+pub trait RandomSource {
+    /// This function returns the next random byte.
+    /// Arguments: none.
+    /// Returns: The byte, or an error that stops `Chip8Sys::run`.
+    fn random_byte(&mut self) -> Result<u8, Chip8Error>;
+}
+
+/// This type is how `Chip8Sys::set_random_source` takes a generator: boxed with `std`, and as a
+/// `'static` reference, e.g. from a `static_cell`, without it.
+#[cfg(feature = "std")]
+pub type RandomSourceHandle = Box<dyn RandomSource + Send>;
+/// This type is how `Chip8Sys::set_random_source` takes a generator: boxed with `std`, and as a
+/// `'static` reference, e.g. from a `static_cell`, without it.
+#[cfg(not(feature = "std"))]
+pub type RandomSourceHandle = &'static mut (dyn RandomSource + Send);
+
+/// This struct is the SplitMix64 generator, see <https://prng.di.unimi.it/splitmix64.c>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SplitMix64 {
+    seed: u64,
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// This function builds a generator.
+    /// Arguments:
+    /// - seed: The seed the sequence starts from.
+    ///
+    /// Returns: The generator.
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { seed, state: seed }
+    }
+
+    /// This function returns the seed the generator was built with.
+    /// Arguments: none.
+    /// Returns: The seed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // The position in the sequence, for save states
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+
+    // Rebuilds a generator part way through its sequence, for save states
+    pub(crate) fn resume(seed: u64, state: u64) -> SplitMix64 {
+        SplitMix64 { seed, state }
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn random_byte(&mut self) -> Result<u8, Chip8Error> {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Ok(((z ^ (z >> 31)) >> 56) as u8)
+    }
+}
+
+/// This struct draws from the operating system's generator.
+#[cfg(feature = "os-rng")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OsRandom;
+
+#[cfg(feature = "os-rng")]
+impl RandomSource for OsRandom {
+    fn random_byte(&mut self) -> Result<u8, Chip8Error> {
+        let rng = getrandom::u32().map_err(Chip8Error::IssueGeneratingRandomNum)?;
+        Ok((rng & 0xFF) as u8)
+    }
+}
+
+// The generator CXNN draws from
+pub(crate) enum Rng {
+    #[cfg(feature = "os-rng")]
+    Os(OsRandom),
+    Seeded(SplitMix64),
+    Custom(RandomSourceHandle),
+}
+
+impl Default for Rng {
+    #[cfg(feature = "os-rng")]
+    fn default() -> Self {
+        Rng::Os(OsRandom)
+    }
+
+    #[cfg(not(feature = "os-rng"))]
+    fn default() -> Self {
+        Rng::Seeded(SplitMix64::new(0))
+    }
+}
+
+impl Rng {
+    // Restarts a seeded sequence from its seed, other generators carry on
+    pub(crate) fn restarted(self) -> Rng {
+        match self {
+            Rng::Seeded(generator) => Rng::Seeded(SplitMix64::new(generator.seed())),
+            rng => rng,
+        }
+    }
+}
+
+impl Chip8Sys {
+    /// This function seeds the random number generator used by CXNN.
+    /// The sequence restarts from the seed on `reset`.
+    /// Arguments:
+    /// - seed: The seed, or None for the default generator: the operating system's with `os-rng`,
+    ///   SplitMix64 seeded with 0 without it.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_random_seed(&mut self, seed: Option<u64>) -> &mut Self {
+        self.rng = seed.map_or_else(Rng::default, |seed| Rng::Seeded(SplitMix64::new(seed)));
+        self
+    }
+
+    /// This function returns the seed of the random number generator.
+    /// Arguments: none.
+    /// Returns: The seed, or None when the operating system's or a custom generator is used.
+    pub fn random_seed(&self) -> Option<u64> {
+        match &self.rng {
+            Rng::Seeded(generator) => Some(generator.seed()),
+            _ => None,
+        }
+    }
+
+    /// This function replaces the random number generator used by CXNN, e.g. with a board's
+    /// hardware generator. The generator is kept across `reset`.
+    /// Arguments:
+    /// - source: The generator.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_random_source(&mut self, source: RandomSourceHandle) -> &mut Self {
+        self.rng = Rng::Custom(source);
+        self
+    }
+
+    // Returns the next random byte for 0xCXNN
+    pub(crate) fn random_byte(&mut self) -> Result<u8, Chip8Error> {
+        match &mut self.rng {
+            #[cfg(feature = "os-rng")]
+            Rng::Os(generator) => generator.random_byte(),
+            Rng::Seeded(generator) => generator.random_byte(),
+            Rng::Custom(source) => source.random_byte(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that a seeded generator repeats its sequence after a reset
+    fn test_seeded_sequence_repeats() {
+        let mut chip8 = Chip8Sys::new_chip_8();
+        chip8.set_random_seed(Some(42));
+        let first: Vec<u8> = (0..8).map(|_| chip8.random_byte().unwrap()).collect();
+        chip8.reset();
+        assert_eq!(chip8.random_seed(), Some(42));
+        let second: Vec<u8> = (0..8).map(|_| chip8.random_byte().unwrap()).collect();
+        assert_eq!(
+            first, second,
+            "Chip-8 seeded CXNN should repeat after reset"
+        );
+        assert!(
+            first.iter().any(|&byte| byte != first[0]),
+            "Chip-8 seeded CXNN should not return a constant"
+        );
+    }
+
+    // Counts up from a start value
+    struct Counter(u8);
+
+    impl RandomSource for Counter {
+        fn random_byte(&mut self) -> Result<u8, Chip8Error> {
+            self.0 = self.0.wrapping_add(1);
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    // Tests that CXNN draws from a custom source, which survives a reset
+    fn test_custom_source() {
+        // C0FF: V0 = random & 0xFF
+        let mut chip8 = crate::decode::test::single_instruction_chip_8(0xC0FF);
+        #[cfg(feature = "std")]
+        chip8.set_random_source(Box::new(Counter(0x41)));
+        #[cfg(not(feature = "std"))]
+        chip8.set_random_source(Box::leak(Box::new(Counter(0x41))));
+        chip8.run().unwrap();
+        assert_eq!(chip8.register[0], 0x42);
+        assert_eq!(chip8.random_seed(), None);
+        chip8.reset();
+        assert_eq!(chip8.random_byte().unwrap(), 0x43);
+    }
+}
@@ -0,0 +1,590 @@
+//! ROM metadata lookup by SHA-1.
+//!
+//! The data follows the `programs.json` format of the community CHIP-8 database
+//! (<https://github.com/chip-8/chip-8-database>): a list of programs, each with its ROM files keyed
+//! by the SHA-1 of the ROM bytes. An embedded copy lives in `data/programs.json`; the full
+//! upstream file can also be loaded at runtime with `RomDatabase::from_json`.
+
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "serde")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::chip8::{Chip8Quirks, Chip8Sys};
+use crate::chip8error::Chip8Error;
+use crate::memory_map::MemoryMap;
+use crate::platform::Platform;
+
+// Embedded copy of the database's programs.json
+#[cfg(feature = "serde")]
+const EMBEDDED_PROGRAMS: &str = include_str!("../data/programs.json");
+
+/// This struct describes a ROM found in the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    /// This field stores the lowercase hex SHA-1 of the ROM bytes.
+    pub sha1: String,
+    /// This field stores the program title.
+    pub title: String,
+    /// This field stores the program authors.
+    pub authors: Vec<String>,
+    /// This field stores the ROM file name used by the database.
+    pub file: Option<String>,
+    /// This field stores the database platform ids the ROM runs on, most preferred first.
+    pub platform_ids: Vec<String>,
+    /// This field stores the first platform in `platform_ids` this emulator supports. Lookups by
+    /// ROM bytes report VIP ROMs with the hi-res signature as `Platform::HiresChip8`.
+    pub platform: Option<Platform>,
+    /// This field stores the quirks the ROM needs on `platform`.
+    pub quirks: Chip8Quirks,
+    /// This field stores the database names of quirks the ROM needs that this emulator does not
+    /// implement, e.g. `memoryIncrementByX`; they are missing from `quirks`.
+    pub unsupported_quirks: Vec<&'static str>,
+    /// This field stores whether the ROM expects DXYN to wait for the display interrupt.
+    pub display_wait: bool,
+    /// This field stores the recommended number of instructions per 60Hz frame.
+    pub tick_rate: Option<u32>,
+    /// This field stores the address the ROM should be loaded at, when it is not the default.
+    pub start_address: Option<u16>,
+    /// This field stores the ROM's key bindings, mapping an action name to a keypad key.
+    pub keys: BTreeMap<String, u8>,
+    /// This field stores the recommended colours.
+    pub colors: Option<RomColors>,
+}
+
+/// This struct stores the colours a database entry recommends, as CSS hex strings.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct RomColors {
+    /// This field stores the pixel colours, background first.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pixels: Vec<String>,
+    /// This field stores the colour to show while the buzzer sounds.
+    pub buzzer: Option<String>,
+    /// This field stores the colour to show while the buzzer is silent.
+    pub silence: Option<String>,
+}
+
+impl RomInfo {
+    /// This function configures a Chip-8 system for this ROM.
+    /// Sets the load and entry address (when the database has a start address), the platform
+    /// (when supported) and the required quirks; timing is left to the caller, who can use
+    /// `tick_rate` and `display_wait`. Quirks in `unsupported_quirks` are not applied. Configure
+    /// before loading the ROM so it lands at the start address.
+    /// Arguments:
+    /// - chip8: The Chip-8 system to configure.
+    ///
+    /// Returns: Ok, or InvalidLoadAddress when the start address does not fit the memory map,
+    /// in which case nothing changes.
+    pub fn configure(&self, chip8: &mut Chip8Sys) -> Result<(), Chip8Error> {
+        if let Some(start_address) = self.start_address {
+            chip8.set_memory_map(MemoryMap {
+                load_address: Some(start_address),
+                entry_address: None,
+                ..chip8.memory_map()
+            })?;
+        }
+        if let Some(platform) = self.platform {
+            chip8.set_platform(platform);
+        }
+        chip8.set_quirks(self.quirks);
+        Ok(())
+    }
+}
+
+/// This struct holds database entries indexed by ROM hash.
+#[derive(Clone, Debug, Default)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// This function returns the database embedded in the crate.
+    /// Arguments: none.
+    /// Returns: The embedded database.
+    #[cfg(feature = "serde")]
+    pub fn embedded() -> &'static RomDatabase {
+        static EMBEDDED: OnceLock<RomDatabase> = OnceLock::new();
+        EMBEDDED.get_or_init(|| {
+            RomDatabase::from_json(EMBEDDED_PROGRAMS)
+                .expect("the embedded programs.json should be valid")
+        })
+    }
+
+    /// This function parses a database in the community `programs.json` format.
+    /// Arguments:
+    /// - json: The contents of `programs.json`.
+    ///
+    /// Returns: The parsed database, or an error describing the invalid JSON.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<RomDatabase, Chip8Error> {
+        let programs: Vec<RawProgram> = serde_json::from_str(json)
+            .map_err(|error| Chip8Error::InvalidRomDatabase(error.to_string()))?;
+        let mut roms = HashMap::new();
+        for program in programs {
+            for (sha1, rom) in program.roms {
+                let sha1 = sha1.to_ascii_lowercase();
+                let info = rom.into_info(&sha1, &program.title, &program.authors);
+                roms.insert(sha1, info);
+            }
+        }
+        Ok(RomDatabase { roms })
+    }
+
+    /// This function returns the number of ROMs in the database.
+    /// Arguments: none.
+    /// Returns: The ROM count.
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    /// This function reports whether the database has no ROMs.
+    /// Arguments: none.
+    /// Returns: True when the database is empty.
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    /// This function looks up a ROM by its bytes.
+    /// Arguments:
+    /// - rom_bytes: The ROM file contents.
+    ///
+    /// Returns: The ROM's metadata with its platform resolved from the bytes, see
+    /// `platform_for_rom`, if the database knows it.
+    pub fn lookup(&self, rom_bytes: &[u8]) -> Option<RomInfo> {
+        let mut info = self.lookup_sha1(&sha1_hex(rom_bytes))?.clone();
+        info.platform = info
+            .platform_ids
+            .iter()
+            .find_map(|id| platform_for_rom(id, rom_bytes));
+        Some(info)
+    }
+
+    /// This function looks up a ROM by its SHA-1.
+    /// Without the bytes, hi-res ROMs are reported as `Platform::Chip8`.
+    /// Arguments:
+    /// - sha1: The hex SHA-1 of the ROM bytes.
+    ///
+    /// Returns: The ROM's metadata, if the database knows it.
+    pub fn lookup_sha1(&self, sha1: &str) -> Option<&RomInfo> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+}
+
+/// This function looks up a ROM in the embedded database.
+/// Arguments:
+/// - rom_bytes: The ROM file contents.
+///
+/// Returns: The ROM's metadata, if the embedded database knows it.
+#[cfg(feature = "serde")]
+pub fn lookup(rom_bytes: &[u8]) -> Option<RomInfo> {
+    RomDatabase::embedded().lookup(rom_bytes)
+}
+
+/// This function hashes ROM bytes the way the database keys them.
+/// Arguments:
+/// - rom_bytes: The ROM file contents.
+///
+/// Returns: The lowercase hex SHA-1.
+pub fn sha1_hex(rom_bytes: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom_bytes).digest().to_string()
+}
+
+/// This function maps a database platform id to a supported platform.
+/// Arguments:
+/// - id: The database platform id, e.g. `originalChip8`.
+///
+/// Returns: The matching platform, or None when it is not emulated.
+pub fn platform_from_id(id: &str) -> Option<Platform> {
+    match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" => Some(Platform::Chip8),
+        "chip8x" => Some(Platform::Chip8X),
+        _ => None,
+    }
+}
+
+/// This function maps a database platform id to a supported platform for a given ROM.
+/// The two-page hi-res CHIP-8 is recognised by the ROM's `1260` signature rather than by id, so
+/// VIP ROMs that carry it map to `Platform::HiresChip8`.
+/// Arguments:
+/// - id: The database platform id.
+/// - rom_bytes: The ROM file contents.
+///
+/// Returns: The matching platform, or None when it is not emulated.
+pub fn platform_for_rom(id: &str, rom_bytes: &[u8]) -> Option<Platform> {
+    match platform_from_id(id)? {
+        Platform::Chip8 if id != "modernChip8" => Some(Platform::detect(rom_bytes)),
+        platform => Some(platform),
+    }
+}
+
+/// This function returns the default quirks of a database platform id.
+/// Arguments:
+/// - id: The database platform id.
+///
+/// Returns: The platform's default quirks, or None for an unknown id.
+pub fn platform_quirks(id: &str) -> Option<RawQuirks> {
+    let vip = RawQuirks {
+        logic: Some(true),
+        vblank: Some(true),
+        ..RawQuirks::default()
+    };
+    match id {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some(vip),
+        "modernChip8" => Some(RawQuirks::default()),
+        "chip48" => Some(RawQuirks {
+            shift: Some(true),
+            memory_increment_by_x: Some(true),
+            jump: Some(true),
+            ..RawQuirks::default()
+        }),
+        "superchip1" | "superchip" => Some(RawQuirks {
+            shift: Some(true),
+            memory_leave_i_unchanged: Some(true),
+            jump: Some(true),
+            ..RawQuirks::default()
+        }),
+        "xochip" => Some(RawQuirks {
+            wrap: Some(true),
+            ..RawQuirks::default()
+        }),
+        _ => None,
+    }
+}
+
+/// This struct mirrors the database's quirk settings; unset fields use the platform default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct RawQuirks {
+    /// This field is set when 8XY6/8XYE shift VX in place.
+    pub shift: Option<bool>,
+    /// This field is set when FX55/FX65 increment I by X instead of X + 1.
+    pub memory_increment_by_x: Option<bool>,
+    /// This field is set when FX55/FX65 leave I unchanged.
+    pub memory_leave_i_unchanged: Option<bool>,
+    /// This field is set when sprites wrap around the screen edges.
+    pub wrap: Option<bool>,
+    /// This field is set when BNNN jumps to XNN + VX.
+    pub jump: Option<bool>,
+    /// This field is set when DXYN waits for the display interrupt.
+    pub vblank: Option<bool>,
+    /// This field is set when 8XY1-3 reset VF.
+    pub logic: Option<bool>,
+}
+
+impl RawQuirks {
+    // Fills unset fields from the platform defaults
+    #[cfg(feature = "serde")]
+    fn or(self, defaults: RawQuirks) -> RawQuirks {
+        RawQuirks {
+            shift: self.shift.or(defaults.shift),
+            memory_increment_by_x: self
+                .memory_increment_by_x
+                .or(defaults.memory_increment_by_x),
+            memory_leave_i_unchanged: self
+                .memory_leave_i_unchanged
+                .or(defaults.memory_leave_i_unchanged),
+            wrap: self.wrap.or(defaults.wrap),
+            jump: self.jump.or(defaults.jump),
+            vblank: self.vblank.or(defaults.vblank),
+            logic: self.logic.or(defaults.logic),
+        }
+    }
+
+    /// This function converts the settings to the quirks this emulator implements.
+    /// `memory_increment_by_x` and `jump` are not implemented, see `unsupported`.
+    /// Arguments:
+    /// - platform_id: The database platform id the settings are for.
+    ///
+    /// Returns: The quirks, with unset fields off.
+    pub fn to_quirks(self, platform_id: &str) -> Chip8Quirks {
+        Chip8Quirks {
+            increment_i_on_store: !self.memory_leave_i_unchanged.unwrap_or(false),
+            reset_vf_on_logic: self.logic.unwrap_or(false),
+            wrap_draw: self.wrap.unwrap_or(false),
+            shift_uses_vx: self.shift.unwrap_or(false),
+            // the database has no setting for this; only the COSMAC VIP interpreter waits for
+            // the key to be released
+            wait_for_key_release: matches!(platform_id, "originalChip8" | "hybridVIP"),
+        }
+    }
+
+    /// This function lists the settings that `to_quirks` cannot express.
+    /// Arguments: none.
+    /// Returns: The database names of the quirks that are on but not implemented.
+    pub fn unsupported(self) -> Vec<&'static str> {
+        [
+            ("memoryIncrementByX", self.memory_increment_by_x),
+            ("jump", self.jump),
+        ]
+        .into_iter()
+        .filter(|(_, setting)| *setting == Some(true))
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+// One program in programs.json
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawProgram {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, RawRom>,
+}
+
+// One ROM file of a program in programs.json
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRom {
+    file: Option<String>,
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, RawQuirks>,
+    tickrate: Option<u32>,
+    start_address: Option<u16>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+    colors: Option<RomColors>,
+}
+
+#[cfg(feature = "serde")]
+impl RawRom {
+    // Resolves the preferred supported platform and its quirks
+    fn into_info(self, sha1: &str, title: &str, authors: &[String]) -> RomInfo {
+        let platform_id = self
+            .platforms
+            .iter()
+            .find(|id| platform_from_id(id).is_some())
+            .or(self.platforms.first());
+        let quirks = platform_id
+            .map(|id| {
+                let defaults = platform_quirks(id).unwrap_or_default();
+                self.quirky_platforms
+                    .get(id)
+                    .copied()
+                    .unwrap_or_default()
+                    .or(defaults)
+            })
+            .unwrap_or_default();
+        let platform = platform_id.and_then(|id| platform_from_id(id));
+        let chip8_quirks = quirks.to_quirks(platform_id.map_or("", String::as_str));
+        RomInfo {
+            sha1: sha1.to_owned(),
+            title: title.to_owned(),
+            authors: authors.to_vec(),
+            file: self.file,
+            platform,
+            platform_ids: self.platforms,
+            quirks: chip8_quirks,
+            unsupported_quirks: quirks.unsupported(),
+            display_wait: quirks.vblank.unwrap_or(false),
+            tick_rate: self.tickrate,
+            start_address: self.start_address,
+            keys: self.keys,
+            colors: self.colors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 0x200: jump to self
+    const ROM: [u8; 2] = [0x12, 0x00];
+    const ROM_SHA1: &str = "92a5652d382a18e89c4881ec57041fc7d885ca80";
+
+    #[cfg(feature = "serde")]
+    fn test_database() -> RomDatabase {
+        RomDatabase::from_json(&format!(
+            r##"[
+                {{
+                    "title": "Spin",
+                    "authors": ["Someone"],
+                    "roms": {{
+                        "{}": {{
+                            "file": "spin.ch8",
+                            "platforms": ["superchip", "modernChip8"],
+                            "quirkyPlatforms": {{ "modernChip8": {{ "shift": true }} }},
+                            "tickrate": 30,
+                            "startAddress": 768,
+                            "keys": {{ "up": 5 }},
+                            "colors": {{ "pixels": ["#000000", "#ffffff"] }}
+                        }}
+                    }}
+                }}
+            ]"##,
+            ROM_SHA1.to_ascii_uppercase()
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    // Tests the SHA-1 of a known byte string
+    fn test_sha1_hex() {
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(sha1_hex(&ROM), ROM_SHA1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    // Tests that lookups resolve the preferred supported platform and its quirks
+    fn test_lookup() {
+        let database = test_database();
+        let info = database
+            .lookup(&ROM)
+            .expect("ROM should be in the database");
+        assert_eq!(info.title, "Spin");
+        assert_eq!(info.platform, Some(Platform::Chip8));
+        assert_eq!(
+            info.quirks,
+            Chip8Quirks {
+                increment_i_on_store: true,
+                reset_vf_on_logic: false,
+                wrap_draw: false,
+                shift_uses_vx: true,
+                wait_for_key_release: false,
+            }
+        );
+        assert!(info.unsupported_quirks.is_empty());
+        assert!(!info.display_wait);
+        assert_eq!(info.tick_rate, Some(30));
+        assert_eq!(info.start_address, Some(0x300));
+        assert_eq!(info.keys.get("up"), Some(&5));
+        assert!(database.lookup(&[0x00]).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    // Tests that a database entry configures the Chip-8 system
+    fn test_configure() {
+        let database = test_database();
+        let mut chip8 = Chip8Sys::new_chip_8();
+        database
+            .lookup(&ROM)
+            .unwrap()
+            .configure(&mut chip8)
+            .unwrap();
+        assert!(chip8.is_mod_vx_in_place());
+        assert!(!chip8.is_register_f_reset());
+        chip8.load_rom_bytes(&ROM);
+        assert_eq!(chip8.memory[0x300..0x302], ROM);
+        assert_eq!(chip8.program_counter, 0x300);
+    }
+
+    #[test]
+    // Tests that VIP ROMs with the hi-res signature map to the hi-res platform
+    fn test_platform_for_rom() {
+        let hires = [0x12, 0x60, 0x00, 0xE0];
+        assert_eq!(
+            platform_for_rom("originalChip8", &hires),
+            Some(Platform::HiresChip8)
+        );
+        assert_eq!(
+            platform_for_rom("hybridVIP", &hires),
+            Some(Platform::HiresChip8)
+        );
+        assert_eq!(
+            platform_for_rom("originalChip8", &ROM),
+            Some(Platform::Chip8)
+        );
+        assert_eq!(
+            platform_for_rom("modernChip8", &hires),
+            Some(Platform::Chip8)
+        );
+        assert_eq!(platform_for_rom("chip8x", &hires), Some(Platform::Chip8X));
+        assert_eq!(platform_for_rom("superchip", &hires), None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    // Tests that a hi-res ROM looked up by its bytes configures and starts the hi-res platform
+    fn test_lookup_hires() {
+        let hires = [0x12, 0x60, 0x00, 0xE0];
+        let database = RomDatabase::from_json(&format!(
+            r#"[{{ "title": "Hi-res", "roms": {{ "{}": {{ "platforms": ["originalChip8"] }} }} }}]"#,
+            sha1_hex(&hires)
+        ))
+        .unwrap();
+        let info = database.lookup(&hires).unwrap();
+        assert_eq!(info.platform, Some(Platform::HiresChip8));
+        assert_eq!(
+            database.lookup_sha1(&sha1_hex(&hires)).unwrap().platform,
+            Some(Platform::Chip8)
+        );
+        let mut chip8 = Chip8Sys::new_chip_8();
+        info.configure(&mut chip8).unwrap();
+        chip8.load_rom_bytes(&hires);
+        assert_eq!(chip8.platform(), Platform::HiresChip8);
+        chip8.run().unwrap();
+        assert_eq!(chip8.program_counter, 0x2C0);
+    }
+
+    #[test]
+    // Tests that only the COSMAC VIP platforms wait for FX0A's key to be released
+    fn test_key_release_platforms() {
+        for (id, wait_for_key_release) in [
+            ("originalChip8", true),
+            ("hybridVIP", true),
+            ("modernChip8", false),
+            ("chip48", false),
+            ("superchip", false),
+            ("xochip", false),
+        ] {
+            let quirks = platform_quirks(id).unwrap().to_quirks(id);
+            assert_eq!(quirks.wait_for_key_release, wait_for_key_release, "{id}");
+        }
+    }
+
+    #[test]
+    // Tests that quirks the emulator cannot apply are reported
+    fn test_unsupported_quirks() {
+        let chip48 = platform_quirks("chip48").unwrap();
+        assert_eq!(chip48.unsupported(), ["memoryIncrementByX", "jump"]);
+        assert!(
+            platform_quirks("originalChip8")
+                .unwrap()
+                .unsupported()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    // Tests that invalid JSON is reported as an error
+    fn test_invalid_json() {
+        assert!(matches!(
+            RomDatabase::from_json("{"),
+            Err(Chip8Error::InvalidRomDatabase(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    #[ignore = "needs the vendored database, run scripts/update-rom-db.sh"]
+    // Tests that the embedded database has programs that resolve to emulated platforms
+    fn test_embedded_database() {
+        let database = RomDatabase::embedded();
+        assert!(!database.is_empty(), "data/programs.json has no programs");
+        let infos: Vec<&RomInfo> = database.roms.values().collect();
+        assert!(infos.iter().all(|info| !info.title.is_empty()));
+        assert!(
+            infos
+                .iter()
+                .any(|info| info.platform == Some(Platform::Chip8))
+        );
+        let info = infos[0];
+        assert_eq!(
+            database.lookup_sha1(&info.sha1.to_ascii_uppercase()),
+            Some(info)
+        );
+    }
+}
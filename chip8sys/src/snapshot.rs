@@ -0,0 +1,200 @@
+//! Golden framebuffer snapshots for tests.
+//!
+//! Snapshots live in `chip8sys/snapshots/<name>.txt` and store the framebuffer as one text row
+//! per display row, using `#` for a lit pixel and `.` for an unlit pixel. Set the
+//! `CHIP8_BLESS_SNAPSHOTS` environment variable to write (or overwrite) snapshot files from the
+//! current framebuffer instead of comparing against them.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::chip8::Chip8Sys;
+
+/// This constant names the environment variable that blesses new snapshots.
+pub const BLESS_ENV_VAR: &str = "CHIP8_BLESS_SNAPSHOTS";
+
+const PIXEL_ON: char = '#';
+const PIXEL_OFF: char = '.';
+// used in the diff output for pixels that are lit but should not be
+const PIXEL_EXTRA: char = '+';
+// used in the diff output for pixels that should be lit but are not
+const PIXEL_MISSING: char = '-';
+// used in the diff output for pixels that only one of the renders has, when their sizes differ
+const PIXEL_OUTSIDE: char = '?';
+
+/// This function renders a packed framebuffer as ASCII art.
+/// Arguments:
+/// - frame_buffer: The packed framebuffer bytes, most significant bit first.
+/// - width: The display width in pixels.
+///
+/// Returns: One line per display row using `#` for lit and `.` for unlit pixels.
+pub fn render_ascii(frame_buffer: &[u8], width: usize) -> String {
+    let mut output = String::new();
+    for row in frame_buffer.chunks(width / 8) {
+        for byte in row {
+            for bit in 0..8 {
+                let mask = 0b1000_0000u8 >> bit;
+                output.push(if byte & mask == mask {
+                    PIXEL_ON
+                } else {
+                    PIXEL_OFF
+                });
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// This function builds a visual diff between two ASCII renders.
+/// Renders of different sizes never match: every pixel outside the smaller one is a mismatch.
+/// Arguments:
+/// - expected: The ASCII render stored in the snapshot.
+/// - actual: The ASCII render of the current framebuffer.
+///
+/// Returns: The diff grid and the number of mismatched pixels.
+pub fn diff_ascii(expected: &str, actual: &str) -> (String, usize) {
+    let mut output = String::new();
+    let mut mismatches = 0;
+    let expected_rows: Vec<&str> = expected.lines().collect();
+    let actual_rows: Vec<&str> = actual.lines().collect();
+    let row_count = expected_rows.len().max(actual_rows.len());
+    for row in 0..row_count {
+        let expected_row: Vec<char> = expected_rows.get(row).unwrap_or(&"").chars().collect();
+        let actual_row: Vec<char> = actual_rows.get(row).unwrap_or(&"").chars().collect();
+        let column_count = expected_row.len().max(actual_row.len());
+        let mut row_mismatches = 0;
+        for column in 0..column_count {
+            match (expected_row.get(column), actual_row.get(column)) {
+                (Some(expected_px), Some(actual_px)) if expected_px == actual_px => {
+                    output.push(*actual_px);
+                }
+                (Some(_), Some(actual_px)) => {
+                    row_mismatches += 1;
+                    output.push(if *actual_px == PIXEL_ON {
+                        PIXEL_EXTRA
+                    } else {
+                        PIXEL_MISSING
+                    });
+                }
+                _ => {
+                    row_mismatches += 1;
+                    output.push(PIXEL_OUTSIDE);
+                }
+            }
+        }
+        if row_mismatches > 0 {
+            let _ = write!(output, "  <- row {row}: {row_mismatches} px");
+        }
+        output.push('\n');
+        mismatches += row_mismatches;
+    }
+    (output, mismatches)
+}
+
+/// This function returns the path of a named snapshot file.
+/// Arguments:
+/// - name: The snapshot name without extension.
+///
+/// Returns: The snapshot file path.
+pub fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("snapshots")
+        .join(format!("{name}.txt"))
+}
+
+/// This function compares the Chip-8 framebuffer against a stored snapshot.
+/// Panics with a visual diff when the framebuffer does not match, or writes the snapshot when
+/// `CHIP8_BLESS_SNAPSHOTS` is set.
+/// Arguments:
+/// - chip8: The Chip-8 system whose framebuffer is checked.
+/// - name: The snapshot name without extension.
+///
+/// Returns: none.
+pub fn assert_frame_snapshot(chip8: &Chip8Sys, name: &str) {
+    assert_snapshot(
+        name,
+        &render_ascii(chip8.framebuffer_packed(), chip8.display_width()),
+    );
+}
+
+/// This function compares an ASCII render against a stored snapshot.
+/// Arguments:
+/// - name: The snapshot name without extension.
+/// - actual: The ASCII render to check.
+///
+/// Returns: none.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = snapshot_path(name);
+    if env::var_os(BLESS_ENV_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("should have been able to create the snapshot dir");
+        }
+        fs::write(&path, actual).expect("should have been able to write the snapshot");
+        return;
+    }
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(error) => panic!(
+            "Snapshot {} could not be read ({error}). Run with {BLESS_ENV_VAR}=1 to create it.\n{actual}",
+            path.display()
+        ),
+    };
+    let (diff, mismatches) = diff_ascii(&expected, actual);
+    assert!(
+        mismatches == 0,
+        "Framebuffer does not match snapshot {} ({mismatches} px differ; '{PIXEL_EXTRA}' = unexpected pixel, '{PIXEL_MISSING}' = missing pixel, '{PIXEL_OUTSIDE}' = outside the other size). Run with {BLESS_ENV_VAR}=1 to accept.\n{diff}",
+        path.display()
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    // Tests that packed bytes render most significant bit first, one line per row
+    fn test_render_ascii() {
+        let frame_buffer = [0b1000_0001, 0b0000_0000, 0b1111_0000, 0b0000_1111];
+        assert_eq!(
+            render_ascii(&frame_buffer, 16),
+            "#......#........\n####........####\n"
+        );
+    }
+
+    #[test]
+    // Tests that matching renders produce no mismatches
+    fn test_diff_ascii_match() {
+        let (diff, mismatches) = diff_ascii("#.\n.#\n", "#.\n.#\n");
+        assert_eq!(mismatches, 0);
+        assert_eq!(diff, "#.\n.#\n");
+    }
+
+    #[test]
+    // Tests that extra and missing pixels are highlighted in the diff
+    fn test_diff_ascii_mismatch() {
+        let (diff, mismatches) = diff_ascii("#.\n..\n", ".#\n..\n");
+        assert_eq!(mismatches, 2);
+        assert_eq!(diff, "-+  <- row 0: 2 px\n..\n");
+    }
+
+    #[test]
+    // Tests that a snapshot missing blank rows or columns does not match
+    fn test_diff_ascii_size_mismatch() {
+        let (diff, mismatches) = diff_ascii("#.\n", "#.\n..\n");
+        assert_eq!(mismatches, 2);
+        assert_eq!(diff, "#.\n??  <- row 1: 2 px\n");
+        let (diff, mismatches) = diff_ascii("#\n.\n", "#.\n..\n");
+        assert_eq!(mismatches, 2);
+        assert_eq!(diff, "#?  <- row 0: 1 px\n.?  <- row 1: 1 px\n");
+    }
+
+    #[test]
+    // Tests that a blank Chip-8 screen matches its snapshot
+    fn test_blank_screen_snapshot() {
+        let chip8 = Chip8Sys::new_chip_8();
+        assert_frame_snapshot(&chip8, "blank_screen");
+    }
+}
@@ -0,0 +1,382 @@
+//! COSMAC VIP machine-cycle timing.
+//!
+//! The VIP runs its 1802 at 1.76064 MHz and every machine cycle takes 8 clocks, so the CPU gets
+//! 220,080 machine cycles per second. The CDP1861 display raises an interrupt 60 times a second,
+//! and its DMA steals cycles from the interpreter on every frame.
+//!
+//! The instruction costs are the machine cycles the VIP interpreter's routine for each
+//! instruction takes, as worked out from its disassembly in Laurence Scotford's "Chip-8 on the
+//! COSMAC VIP" series. Most instructions have a fixed cost; the data-dependent ones are:
+//!
+//! | Instruction | Machine cycles |
+//! |---|---|
+//! | `3XNN` `4XNN` `5XY0` `9XY0` `EX9E` `EXA1` | listed cost, plus 4 when the skip is taken |
+//! | `DXYN` | 34, plus 14 per sprite row on a byte boundary, or 22 + 2 per bit of offset |
+//! | `FX33` | 84, plus 16 per unit of the three decimal digits (repeated subtraction) |
+//! | `FX55` `FX65` | 14, plus 14 per register |
+//!
+//! `DXYN` only pays for rows that are drawn, and it and `FX0A` idle until the next interrupt.
+//! The clock only runs under `TimingModel::CosmacVip`.
+
+use crate::chip8::{Chip8Sys, TimerMode};
+use crate::chip8error::Chip8Error;
+
+/// This constant defines the COSMAC VIP clock speed in Hz.
+pub const VIP_CLOCK_HZ: u64 = 1_760_640;
+/// This constant defines the number of clock pulses in one 1802 machine cycle.
+pub const VIP_CLOCKS_PER_MACHINE_CYCLE: u64 = 8;
+/// This constant defines the number of machine cycles executed per second.
+pub const VIP_MACHINE_CYCLES_PER_SECOND: u64 = VIP_CLOCK_HZ / VIP_CLOCKS_PER_MACHINE_CYCLE;
+/// This constant defines the number of machine cycles between two 60Hz display interrupts.
+pub const VIP_CYCLES_PER_FRAME: u32 = (VIP_MACHINE_CYCLES_PER_SECOND / 60) as u32;
+/// This constant defines the cycles spent on display DMA and the interrupt routine per frame.
+pub const VIP_INTERRUPT_CYCLES: u32 = 1024 + 46;
+
+const MICROS_PER_SECOND: u64 = 1_000_000;
+
+// extra cycles spent when a skip instruction skips
+const SKIP_TAKEN_CYCLES: u32 = 4;
+// DXYN setup plus the per-row cost of an aligned or unaligned sprite byte
+const DRAW_BASE_CYCLES: u32 = 34;
+const DRAW_ROW_ALIGNED_CYCLES: u32 = 14;
+const DRAW_ROW_UNALIGNED_CYCLES: u32 = 22;
+// each bit of misalignment costs a shift per row
+const DRAW_SHIFT_CYCLES: u32 = 2;
+
+/// This enum selects how instruction timing is modelled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimingModel {
+    /// This model treats every instruction as one `run` call and decrements the delay timer
+    /// every 6 cycles.
+    #[default]
+    Instruction,
+    /// This model charges each instruction its COSMAC VIP machine-cycle cost, drives the timers
+    /// from the 60Hz interrupt and makes DXYN wait for the display interrupt.
+    CosmacVip,
+}
+
+/// This struct tracks elapsed VIP machine cycles and the next display interrupt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VipClock {
+    /// This field stores the machine cycles executed since the clock was created.
+    pub total_cycles: u64,
+    /// This field stores the number of 60Hz interrupts raised so far.
+    pub interrupts: u64,
+    // cycles left until the next 60Hz interrupt
+    pub(crate) cycles_to_interrupt: u32,
+    // the machine cycle run_for_microseconds should reach before returning
+    pub(crate) target_cycles: u64,
+    // leftover microsecond fraction that did not add up to a whole machine cycle
+    pub(crate) micros_remainder: u64,
+}
+
+impl Default for VipClock {
+    /// This function returns a clock positioned at the start of a frame.
+    /// Arguments: none.
+    /// Returns: A new VIP clock.
+    fn default() -> Self {
+        Self {
+            total_cycles: 0,
+            interrupts: 0,
+            cycles_to_interrupt: VIP_CYCLES_PER_FRAME,
+            target_cycles: 0,
+            micros_remainder: 0,
+        }
+    }
+}
+
+/// This function returns the machine-cycle cost of an instruction before it executes, see the
+/// module docs for the table. Skip bonuses are applied after execution and the DXYN display wait
+/// before the draw.
+/// Arguments:
+/// - chip8: The Chip-8 system about to execute the instruction.
+/// - opcode: The 16-bit instruction word.
+///
+/// Returns: The number of machine cycles the instruction takes.
+pub fn instruction_cycles(chip8: &Chip8Sys, opcode: u16) -> u32 {
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let y = ((opcode & 0x00F0) >> 4) as usize;
+    let n = (opcode & 0x000F) as u32;
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => 24,
+            0x00EE => 10,
+            // machine code subroutines are not emulated
+            _ => 10,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 | 0x6000 => 10,
+        0x5000 | 0x9000 => 14,
+        0x7000 => 10,
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        0xD000 => {
+            let offset = (chip8.register[x] % 8) as u32;
+            let height = chip8.display_height();
+            let y_loc = (chip8.register[y] as usize) % height;
+            let rows = if chip8.is_wrap_draw() {
+                n
+            } else {
+                n.min((height - y_loc) as u32)
+            };
+            let row_cycles = if offset == 0 {
+                DRAW_ROW_ALIGNED_CYCLES
+            } else {
+                DRAW_ROW_UNALIGNED_CYCLES + DRAW_SHIFT_CYCLES * offset
+            };
+            DRAW_BASE_CYCLES + rows * row_cycles
+        }
+        0xE000 => 14,
+        _ => match opcode & 0x00FF {
+            0x1E => 18,
+            0x29 | 0x30 => 20,
+            0x33 => {
+                // the VIP converts to decimal by repeated subtraction
+                let value = chip8.register[x] as u32;
+                84 + 16 * (value / 100 + (value / 10) % 10 + value % 10)
+            }
+            0x55 | 0x65 => 14 + 14 * (x as u32 + 1),
+            _ => 10,
+        },
+    }
+}
+
+impl Chip8Sys {
+    /// This function returns the active timing model.
+    /// Arguments: none.
+    /// Returns: The active timing model.
+    pub fn timing_model(&self) -> TimingModel {
+        self.timing_model
+    }
+
+    /// This function sets the timing model.
+    /// Arguments:
+    /// - model: The desired timing model.
+    ///
+    /// Returns: The updated Chip-8 system.
+    pub fn set_timing_model(&mut self, model: TimingModel) -> &mut Self {
+        self.timing_model = model;
+        self
+    }
+
+    /// This function returns the VIP machine-cycle clock.
+    /// Arguments: none.
+    /// Returns: The VIP clock state.
+    pub fn vip_clock(&self) -> &VipClock {
+        &self.vip_clock
+    }
+
+    /// This function runs instructions until the given amount of VIP time has elapsed.
+    /// Time that an instruction overshoots is carried into the next call.
+    /// Arguments:
+    /// - microseconds: The amount of emulated time to run.
+    ///
+    /// Returns: The number of `run` calls made, the first execution error, or
+    /// InvalidTimingModel when the VIP timing model is off.
+    pub fn run_for_microseconds(&mut self, microseconds: u32) -> Result<u32, Chip8Error> {
+        if self.timing_model != TimingModel::CosmacVip {
+            return Err(Chip8Error::InvalidTimingModel);
+        }
+        let scaled =
+            microseconds as u64 * VIP_MACHINE_CYCLES_PER_SECOND + self.vip_clock.micros_remainder;
+        self.vip_clock.target_cycles += scaled / MICROS_PER_SECOND;
+        self.vip_clock.micros_remainder = scaled % MICROS_PER_SECOND;
+        let mut steps = 0;
+        while self.vip_clock.total_cycles < self.vip_clock.target_cycles {
+            self.run()?;
+            steps += 1;
+        }
+        Ok(steps)
+    }
+
+    // Returns the cycles an instruction took once it has executed, adding the taken skip bonus
+    pub(crate) fn executed_cycles(&self, opcode: u16, cycles: u32, pc_before: u16) -> u32 {
+        let is_skip = matches!(opcode & 0xF000, 0x3000 | 0x4000 | 0x5000 | 0x9000 | 0xE000);
+        if is_skip && self.program_counter == pc_before.wrapping_add(4) {
+            cycles + SKIP_TAKEN_CYCLES
+        } else {
+            cycles
+        }
+    }
+
+    // Burns the rest of the frame, used by the FX0A key wait and the DXYN display wait
+    pub(crate) fn idle_until_interrupt(&mut self) {
+        self.charge_cycles(self.vip_clock.cycles_to_interrupt);
+    }
+
+    // Adds machine cycles to the clock and raises any interrupts that are due; the clock stands
+    // still outside the VIP model
+    pub(crate) fn charge_cycles(&mut self, cycles: u32) {
+        if self.timing_model != TimingModel::CosmacVip {
+            return;
+        }
+        self.vip_clock.total_cycles += cycles as u64;
+        let mut remaining = cycles;
+        while remaining >= self.vip_clock.cycles_to_interrupt {
+            remaining -= self.vip_clock.cycles_to_interrupt;
+            self.vip_clock.cycles_to_interrupt = VIP_CYCLES_PER_FRAME;
+            self.interrupt();
+        }
+        self.vip_clock.cycles_to_interrupt -= remaining;
+    }
+
+    // The 60Hz display interrupt; DMA and the interrupt routine eat part of the next frame
+    fn interrupt(&mut self) {
+        self.end_frame();
+        self.vip_clock.interrupts += 1;
+        self.vip_clock.total_cycles += VIP_INTERRUPT_CYCLES as u64;
+        self.vip_clock.cycles_to_interrupt -= VIP_INTERRUPT_CYCLES;
+        if self.timer_mode() == TimerMode::Cycle {
+            self.tick_timers(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decode::test::single_instruction_chip_8;
+
+    #[test]
+    // Tests that aligned sprites cost less than unaligned ones
+    fn test_draw_cycles_alignment() {
+        let mut chip8 = single_instruction_chip_8(0xD015);
+        chip8.register[0] = 8;
+        let aligned = instruction_cycles(&chip8, 0xD015);
+        chip8.register[0] = 9;
+        let unaligned = instruction_cycles(&chip8, 0xD015);
+        assert_eq!(aligned, DRAW_BASE_CYCLES + 5 * DRAW_ROW_ALIGNED_CYCLES);
+        assert_eq!(
+            unaligned,
+            DRAW_BASE_CYCLES + 5 * (DRAW_ROW_UNALIGNED_CYCLES + DRAW_SHIFT_CYCLES)
+        );
+    }
+
+    #[test]
+    // Tests that clipped rows are not charged when wrapping is off
+    fn test_draw_cycles_clipped() {
+        let mut chip8 = single_instruction_chip_8(0xD01F);
+        chip8.register[1] = 30;
+        assert_eq!(
+            instruction_cycles(&chip8, 0xD01F),
+            DRAW_BASE_CYCLES + 2 * DRAW_ROW_ALIGNED_CYCLES
+        );
+    }
+
+    #[test]
+    // Tests that a taken skip costs more than one that falls through
+    fn test_skip_taken_cycles() {
+        let mut chip8 = single_instruction_chip_8(0x3000);
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        chip8.run().unwrap();
+        let taken = chip8.vip_clock().total_cycles;
+        let mut chip8 = single_instruction_chip_8(0x3001);
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        chip8.run().unwrap();
+        let not_taken = chip8.vip_clock().total_cycles;
+        assert_eq!(taken, not_taken + SKIP_TAKEN_CYCLES as u64);
+    }
+
+    #[test]
+    // Tests that one second of VIP time raises 60 interrupts and ticks the delay timer
+    fn test_run_for_one_second() {
+        // 0x200: jump to self
+        let mut chip8 = single_instruction_chip_8(0x1200);
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        chip8.delay_timer = 0xFF;
+        chip8.run_for_microseconds(1_000_000).unwrap();
+        assert_eq!(chip8.vip_clock().interrupts, 60);
+        assert_eq!(chip8.delay_timer(), 0xFF - 60);
+    }
+
+    #[test]
+    // Tests that the clock stands still under the instruction model
+    fn test_instruction_model_stops_clock() {
+        let mut chip8 = single_instruction_chip_8(0xD011);
+        for _ in 0..1_000 {
+            chip8.run().unwrap();
+        }
+        assert_eq!(*chip8.vip_clock(), VipClock::default());
+        assert!(matches!(
+            chip8.run_for_microseconds(1_000),
+            Err(Chip8Error::InvalidTimingModel)
+        ));
+    }
+
+    #[test]
+    // Tests that the cost of FX33 grows with the digits of VX
+    fn test_bcd_cycles() {
+        let mut chip8 = single_instruction_chip_8(0xF033);
+        assert_eq!(instruction_cycles(&chip8, 0xF033), 84);
+        chip8.register[0] = 255;
+        assert_eq!(instruction_cycles(&chip8, 0xF033), 84 + 16 * (2 + 5 + 5));
+    }
+
+    #[test]
+    // Tests that FX55 and FX65 pay for every register they move
+    fn test_store_load_cycles() {
+        let chip8 = single_instruction_chip_8(0xF055);
+        assert_eq!(instruction_cycles(&chip8, 0xF055), 14 + 14);
+        assert_eq!(instruction_cycles(&chip8, 0xFF65), 14 + 14 * 16);
+    }
+
+    #[test]
+    // Tests that DXYN draws at most once per frame in the VIP model
+    fn test_display_wait() {
+        let mut chip8 = single_instruction_chip_8(0xD011);
+        // 0x202: jump back to the draw
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        let steps = chip8.run_for_microseconds(1_000_000).unwrap();
+        // 60 draws, one per frame, with a jump between each of them
+        assert_eq!(steps, 119);
+    }
+
+    #[test]
+    // Tests that the display wait happens before the sprite is drawn
+    fn test_display_wait_before_draw() {
+        let mut chip8 = single_instruction_chip_8(0xD011);
+        chip8.register_i = 0x050;
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        chip8.run().unwrap();
+        assert_eq!(chip8.vip_clock().interrupts, 1);
+        assert!(chip8.frame_buffer.iter().any(|&chunk| chunk != 0));
+        // the instruction model draws straight away
+        let mut chip8 = single_instruction_chip_8(0xD011);
+        chip8.run().unwrap();
+        assert_eq!(chip8.vip_clock().interrupts, 0);
+    }
+
+    #[test]
+    // Tests that the VIP model idles for a frame while FX0A waits for a key
+    fn test_key_wait_cycles() {
+        let mut chip8 = single_instruction_chip_8(0xF00A);
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        chip8.run().unwrap();
+        let interrupts = chip8.vip_clock().interrupts;
+        chip8.run().unwrap();
+        assert_eq!(chip8.vip_clock().interrupts, interrupts + 1);
+        assert_eq!(
+            chip8.vip_clock().cycles_to_interrupt,
+            VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES
+        );
+    }
+
+    #[test]
+    // Tests that run_for_microseconds carries the fractional cycle into the next call
+    fn test_run_for_microseconds_remainder() {
+        let mut chip8 = single_instruction_chip_8(0x1200);
+        chip8.set_timing_model(TimingModel::CosmacVip);
+        for _ in 0..1_000 {
+            chip8.run_for_microseconds(1).unwrap();
+        }
+        assert!(
+            chip8.vip_clock().total_cycles >= VIP_MACHINE_CYCLES_PER_SECOND / 1_000,
+            "1ms of VIP time should have executed at least 220 machine cycles"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Typed errors for the exceptions chip8wasm throws.
+//
+// The wasm side throws a plain `Error` named after its `Chip8Error` variant, e.g.
+// `InvalidKeypadKey`. `toChip8Error` turns it into one of the classes below so callers can use
+// `instanceof` instead of matching strings.
+
+/** The names of the errors chip8wasm throws, one per `Chip8Error` variant. */
+export type Chip8ErrorKind =
+  | "InvalidFirstByte"
+  | "InvalidRegisterX"
+  | "Invalid0x8XYN"
+  | "Invalid0xENNN"
+  | "Invalid0xFNNN"
+  | "InvalidWaitRegister"
+  | "InvalidStackPointer"
+  | "IssueGeneratingRandomNum"
+  | "InvalidRomDatabase"
+  | "InvalidKeypadKey"
+  | "InvalidKeymapProfile"
+  | "InvalidMemorySize"
+  | "InvalidLoadAddress"
+  | "RomTooLarge"
+  | "InvalidFont"
+  | "InvalidFontAddress"
+  | "InvalidTimingModel"
+  | "StateBufferTooSmall"
+  | "InvalidSaveState"
+  | "Watchpoint"
+  | "ProtectedWrite"
+  | "InvalidSymbolFile"
+  | "InvalidCheat"
+  | "CoverageOff";
+
+/** The base class of every error chip8wasm throws. */
+export class Chip8Error extends Error {
+  /** The `Chip8Error` variant, also used as the error's `name`. */
+  readonly kind: Chip8ErrorKind;
+
+  constructor(kind: Chip8ErrorKind, message: string) {
+    super(message);
+    this.kind = kind;
+    this.name = kind;
+  }
+}
+
+/** The ROM ran an opcode the emulated platform does not define. */
+export class InvalidOpcodeError extends Chip8Error {}
+
+/** A register, key or stack slot number was out of range. */
+export class InvalidArgumentError extends Chip8Error {}
+
+/** A memory size, memory map, font or ROM did not fit the machine. */
+export class InvalidConfigurationError extends Chip8Error {}
+
+/** A save state was truncated, came from another build or does not fit. */
+export class SaveStateError extends Chip8Error {}
+
+/** A ROM database, keymap profile, symbol file or cheat list could not be parsed. */
+export class ParseError extends Chip8Error {}
+
+/** A watchpoint or the write guard stopped the CPU. */
+export class BreakError extends Chip8Error {}
+
+/** Anything else, e.g. the random number generator or coverage being off. */
+export class RuntimeError extends Chip8Error {}
+
+type Chip8ErrorClass = new (kind: Chip8ErrorKind, message: string) => Chip8Error;
+
+const ERROR_CLASSES: Record<Chip8ErrorKind, Chip8ErrorClass> = {
+  InvalidFirstByte: InvalidOpcodeError,
+  Invalid0x8XYN: InvalidOpcodeError,
+  Invalid0xENNN: InvalidOpcodeError,
+  Invalid0xFNNN: InvalidOpcodeError,
+  InvalidRegisterX: InvalidArgumentError,
+  InvalidWaitRegister: InvalidArgumentError,
+  InvalidStackPointer: InvalidArgumentError,
+  InvalidKeypadKey: InvalidArgumentError,
+  InvalidMemorySize: InvalidConfigurationError,
+  InvalidLoadAddress: InvalidConfigurationError,
+  RomTooLarge: InvalidConfigurationError,
+  InvalidFont: InvalidConfigurationError,
+  InvalidFontAddress: InvalidConfigurationError,
+  InvalidTimingModel: InvalidConfigurationError,
+  StateBufferTooSmall: SaveStateError,
+  InvalidSaveState: SaveStateError,
+  InvalidRomDatabase: ParseError,
+  InvalidKeymapProfile: ParseError,
+  InvalidSymbolFile: ParseError,
+  InvalidCheat: ParseError,
+  Watchpoint: BreakError,
+  ProtectedWrite: BreakError,
+  IssueGeneratingRandomNum: RuntimeError,
+  CoverageOff: RuntimeError,
+};
+
+/**
+ * Converts an exception thrown by chip8wasm into its typed error. Anything that is not a
+ * chip8wasm error, such as a `RangeError` from JS code, is returned unchanged.
+ */
+export function toChip8Error(error: unknown): unknown {
+  if (error instanceof Chip8Error || !(error instanceof Error)) {
+    return error;
+  }
+  const kind = error.name as Chip8ErrorKind;
+  const ErrorClass = Object.prototype.hasOwnProperty.call(ERROR_CLASSES, kind)
+    ? ERROR_CLASSES[kind]
+    : undefined;
+  return ErrorClass ? new ErrorClass(kind, error.message) : error;
+}
+
+/** Calls `action`, rethrowing chip8wasm exceptions as typed errors. */
+export function withTypedErrors<T>(action: () => T): T {
+  try {
+    return action();
+  } catch (error) {
+    throw toChip8Error(error);
+  }
+}
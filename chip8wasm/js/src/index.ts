@@ -0,0 +1,26 @@
+// The typed entry point of chip8wasm-ts: the wasm bindings under shorter names, typed errors,
+// keypad mask helpers and the requestAnimationFrame runner.
+
+export {
+  default as init,
+  Chip8Wasm,
+  Chip8Access as Access,
+  Chip8KeyLayout as KeyLayout,
+  Chip8MemoryMap as MemoryMap,
+  Chip8Platform as Platform,
+  Chip8QuirkPreset as QuirkPreset,
+  Chip8SearchCondition as SearchCondition,
+  Chip8Status as Status,
+  Chip8StopReason as StopReason,
+} from "chip8wasm";
+export type {
+  Chip8DisassemblyLine as DisassemblyLine,
+  Chip8QuirkFlags as QuirkFlags,
+  Chip8Snapshot as Snapshot,
+  Chip8StopInfo as StopInfo,
+  InitOutput,
+} from "chip8wasm";
+
+export * from "./errors.js";
+export * from "./keys.js";
+export * from "./runner.js";
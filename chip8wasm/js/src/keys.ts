@@ -0,0 +1,49 @@
+// Helpers for the 16-bit keypad masks `setKeys`, `keys` and `snapshot().keys` use.
+
+/** The 16 CHIP-8 keypad keys; bit N of a key mask is key N. */
+export enum Key {
+  Key0 = 0x0,
+  Key1 = 0x1,
+  Key2 = 0x2,
+  Key3 = 0x3,
+  Key4 = 0x4,
+  Key5 = 0x5,
+  Key6 = 0x6,
+  Key7 = 0x7,
+  Key8 = 0x8,
+  Key9 = 0x9,
+  KeyA = 0xa,
+  KeyB = 0xb,
+  KeyC = 0xc,
+  KeyD = 0xd,
+  KeyE = 0xe,
+  KeyF = 0xf,
+}
+
+/** A 16-bit mask with bit N set while key N is held. */
+export type KeyMask = number;
+
+/** Builds a key mask from the held keys. */
+export function keyMask(keys: Iterable<Key>): KeyMask {
+  let mask = 0;
+  for (const key of keys) {
+    mask |= 1 << key;
+  }
+  return mask;
+}
+
+/** Reports whether a key is held in a mask. */
+export function isKeyDown(mask: KeyMask, key: Key): boolean {
+  return (mask & (1 << key)) !== 0;
+}
+
+/** Lists the keys held in a mask, lowest first. */
+export function heldKeys(mask: KeyMask): Key[] {
+  const keys: Key[] = [];
+  for (let key = Key.Key0; key <= Key.KeyF; key++) {
+    if (isKeyDown(mask, key)) {
+      keys.push(key);
+    }
+  }
+  return keys;
+}
@@ -0,0 +1,107 @@
+// Tests of Chip8Runner's frame timing and stops, against a fake emulator and frame clock.
+
+import assert from "node:assert/strict";
+import { test } from "node:test";
+
+import { Chip8StopReason } from "chip8wasm";
+import type { Chip8StopInfo, Chip8Wasm } from "chip8wasm";
+
+import { InvalidArgumentError } from "./errors.js";
+import { Chip8Runner, FRAME_MS } from "./runner.js";
+
+// Stands in for Chip8Wasm, stopping or failing on the configured frame
+class FakeEmulator {
+  frames = 0;
+  timerTicks = 0;
+  stopOnFrame: number | undefined;
+  failOnFrame: number | undefined;
+
+  runUntilBreak(cycles: number): Chip8StopInfo {
+    this.frames += 1;
+    if (this.frames === this.failOnFrame) {
+      const error = new Error("InvalidKeypadKey(16)");
+      error.name = "InvalidKeypadKey";
+      throw error;
+    }
+    const reason =
+      this.frames === this.stopOnFrame ? Chip8StopReason.Watchpoint : Chip8StopReason.CycleLimit;
+    return { reason, cycles, free() {} } as unknown as Chip8StopInfo;
+  }
+
+  tickTimers(ticks: number): void {
+    this.timerTicks += ticks;
+  }
+
+  updateFramebuffer(): boolean {
+    return false;
+  }
+}
+
+// Creates a runner whose animation frames are fired by hand
+function setup(options: { maxCatchUpFrames?: number } = {}) {
+  const emulator = new FakeEmulator();
+  const callbacks: ((time: number) => void)[] = [];
+  const stops: Chip8StopInfo[] = [];
+  const errors: unknown[] = [];
+  const runner = new Chip8Runner(
+    emulator as unknown as Chip8Wasm,
+    new WebAssembly.Memory({ initial: 1 }),
+    {
+      ...options,
+      requestFrame: (callback) => callbacks.push(callback),
+      cancelFrame: () => {},
+      onStop: (info) => stops.push(info),
+      onError: (error) => errors.push(error),
+    },
+  );
+  // fires the latest scheduled frame, failing when none is scheduled
+  const fire = (time: number) => {
+    const callback = callbacks.pop();
+    assert.ok(callback, "no frame was scheduled");
+    callback(time);
+  };
+  return { emulator, runner, callbacks, stops, errors, fire };
+}
+
+test("runs one frame per 60Hz frame and catches up after a stall", () => {
+  const { emulator, runner, fire } = setup({ maxCatchUpFrames: 4 });
+  runner.start();
+  fire(1000);
+  assert.equal(emulator.frames, 1);
+  fire(1000 + FRAME_MS / 2);
+  assert.equal(emulator.frames, 1);
+  fire(1000 + FRAME_MS * 2.5);
+  assert.equal(emulator.frames, 3);
+  // a one second stall only runs the catch-up limit
+  fire(2000 + FRAME_MS * 2.5);
+  assert.equal(emulator.frames, 7);
+  assert.equal(emulator.timerTicks, 7);
+  assert.ok(runner.isRunning);
+});
+
+test("stops and reports a watchpoint without running further frames", () => {
+  const { emulator, runner, callbacks, stops, fire } = setup();
+  emulator.stopOnFrame = 2;
+  runner.start();
+  fire(0);
+  fire(FRAME_MS * 3);
+  assert.equal(emulator.frames, 2);
+  assert.equal(emulator.timerTicks, 1);
+  assert.equal(stops.length, 1);
+  assert.equal(stops[0].reason, Chip8StopReason.Watchpoint);
+  assert.ok(!runner.isRunning);
+  assert.equal(callbacks.length, 0);
+});
+
+test("stops and reports a failing instruction as a typed error", () => {
+  const { emulator, runner, callbacks, errors, fire } = setup();
+  emulator.failOnFrame = 1;
+  runner.start();
+  fire(0);
+  assert.equal(errors.length, 1);
+  assert.ok(errors[0] instanceof InvalidArgumentError);
+  assert.equal((errors[0] as InvalidArgumentError).kind, "InvalidKeypadKey");
+  assert.equal(emulator.timerTicks, 0);
+  assert.ok(!runner.isRunning);
+  assert.equal(callbacks.length, 0);
+});
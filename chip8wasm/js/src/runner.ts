@@ -0,0 +1,160 @@
+// Drives a Chip8Wasm from requestAnimationFrame at 60 frames per second.
+
+import { Chip8StopReason } from "chip8wasm";
+import type { Chip8StopInfo, Chip8Wasm } from "chip8wasm";
+
+import { toChip8Error } from "./errors.js";
+
+/** The length of one 60Hz CHIP-8 frame in milliseconds. */
+export const FRAME_MS = 1000 / 60;
+
+/** Settings for a `Chip8Runner`. */
+export interface Chip8RunnerOptions {
+  /** Instructions per 60Hz frame, 10 unless set. */
+  cyclesPerFrame?: number;
+  /** Frames run at most per animation frame when catching up after a stall, 4 unless set. */
+  maxCatchUpFrames?: number;
+  /** Called once per animation frame; `isDisplayChanged` is true when the views were redrawn. */
+  onFrame?: (runner: Chip8Runner, isDisplayChanged: boolean) => void;
+  /** Called when a breakpoint or watchpoint fires or the program halts; the runner has stopped. */
+  onStop?: (info: Chip8StopInfo) => void;
+  /** Called with the typed error when an instruction fails; the runner has stopped. */
+  onError?: (error: unknown) => void;
+  /** Schedules the next frame, `requestAnimationFrame` unless set (e.g. for tests). */
+  requestFrame?: (callback: (time: number) => void) => number;
+  /** Cancels a scheduled frame, `cancelAnimationFrame` unless set. */
+  cancelFrame?: (handle: number) => void;
+}
+
+/**
+ * Runs an emulator in real time: every animation frame it runs the 60Hz frames that are due,
+ * honouring breakpoints and watchpoints, ticks the timers and refreshes the framebuffer views.
+ */
+export class Chip8Runner {
+  readonly emulator: Chip8Wasm;
+  /** Instructions per 60Hz frame. */
+  cyclesPerFrame: number;
+
+  private readonly memory: WebAssembly.Memory;
+  private readonly options: Chip8RunnerOptions;
+  private readonly maxCatchUpFrames: number;
+  private handle: number | undefined;
+  private lastTime: number | undefined;
+  private dueFrames = 0;
+  private running = false;
+
+  /**
+   * @param emulator The emulator to drive; it must use external timers, as `new Chip8Wasm()` does.
+   * @param memory The wasm memory from `init()`, used for the zero-copy views.
+   */
+  constructor(emulator: Chip8Wasm, memory: WebAssembly.Memory, options: Chip8RunnerOptions = {}) {
+    this.emulator = emulator;
+    this.memory = memory;
+    this.options = options;
+    this.cyclesPerFrame = options.cyclesPerFrame ?? 10;
+    this.maxCatchUpFrames = options.maxCatchUpFrames ?? 4;
+  }
+
+  /** True between `start` and `stop`. */
+  get isRunning(): boolean {
+    return this.running;
+  }
+
+  /** Starts running on the next animation frame. */
+  start(): void {
+    if (this.running) {
+      return;
+    }
+    this.running = true;
+    this.lastTime = undefined;
+    this.dueFrames = 0;
+    this.handle = this.requestFrame(this.onAnimationFrame);
+  }
+
+  /** Stops after the current frame. */
+  stop(): void {
+    this.running = false;
+    if (this.handle !== undefined) {
+      this.cancelFrame(this.handle);
+      this.handle = undefined;
+    }
+  }
+
+  /**
+   * Runs one 60Hz frame now, e.g. to single-step frames while paused.
+   * @returns False when the frame stopped early on a break or an error.
+   */
+  runFrame(): boolean {
+    let info: Chip8StopInfo;
+    try {
+      info = this.emulator.runUntilBreak(this.cyclesPerFrame);
+    } catch (error) {
+      this.stop();
+      this.options.onError?.(toChip8Error(error));
+      return false;
+    }
+    if (info.reason !== Chip8StopReason.CycleLimit) {
+      this.stop();
+      if (this.options.onStop) {
+        this.options.onStop(info);
+      } else {
+        info.free();
+      }
+      return false;
+    }
+    info.free();
+    this.emulator.tickTimers(1);
+    return true;
+  }
+
+  /** The display as one 0/1 byte per pixel, current as of the last redraw. */
+  pixels(): Uint8Array {
+    return new Uint8Array(
+      this.memory.buffer,
+      this.emulator.framebufferPtr(),
+      this.emulator.framebufferLen(),
+    );
+  }
+
+  /** The display as RGBA, ready for `new ImageData(runner.rgba(), emulator.width())`. */
+  rgba(): Uint8ClampedArray {
+    return new Uint8ClampedArray(
+      this.memory.buffer,
+      this.emulator.rgbaPtr(),
+      this.emulator.rgbaLen(),
+    );
+  }
+
+  /** The emulated RAM. */
+  ram(): Uint8Array {
+    return new Uint8Array(this.memory.buffer, this.emulator.memoryPtr(), this.emulator.memoryLen());
+  }
+
+  /** The registers V0-VF. */
+  registers(): Uint8Array {
+    return new Uint8Array(this.memory.buffer, this.emulator.registersPtr(), 16);
+  }
+
+  private readonly onAnimationFrame = (time: number): void => {
+    this.handle = undefined;
+    const elapsed = this.lastTime === undefined ? FRAME_MS : time - this.lastTime;
+    this.lastTime = time;
+    this.dueFrames = Math.min(this.dueFrames + elapsed / FRAME_MS, this.maxCatchUpFrames);
+    while (this.running && this.dueFrames >= 1) {
+      this.dueFrames -= 1;
+      this.runFrame();
+    }
+    this.options.onFrame?.(this, this.emulator.updateFramebuffer());
+    if (this.running) {
+      this.handle = this.requestFrame(this.onAnimationFrame);
+    }
+  };
+
+  private requestFrame(callback: (time: number) => void): number {
+    return (this.options.requestFrame ?? requestAnimationFrame)(callback);
+  }
+
+  private cancelFrame(handle: number): void {
+    (this.options.cancelFrame ?? cancelAnimationFrame)(handle);
+  }
+}
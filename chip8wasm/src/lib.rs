@@ -7,15 +7,325 @@
 
 use wasm_bindgen::prelude::*;
 
+use chip8sys::bus::{Access, HookId, Watchpoint};
+use chip8sys::cheat::{Cheat, CheatList, CheatTarget, RamSearch, SearchCondition};
 use chip8sys::chip8::{
-    Chip8Quirks, Chip8Sys, DISPLAY_HEIGHT, DISPLAY_PIXELS, DISPLAY_WIDTH, TimerMode,
+    Chip8Quirks, Chip8Sys, DISPLAY_HEIGHT, DISPLAY_PIXELS, DISPLAY_WIDTH, FRAMEBUFFER_MAX_LEN,
+    TimerMode,
 };
 use chip8sys::chip8error::Chip8Error;
+use chip8sys::coverage::SymbolFile;
+use chip8sys::debug::{Stop, StopReason};
+use chip8sys::disasm::DisassemblyLine;
+use chip8sys::halt::RunStatus;
+use chip8sys::keymap::{KeyLayout, KeyboardState, KeymapProfile};
+use chip8sys::machine::Machine;
+use chip8sys::memory_map::MemoryMap;
+use chip8sys::platform::{
+    CHIP8X_BACKGROUND_PALETTE, CHIP8X_COLOR_COLUMNS, CHIP8X_FOREGROUND_PALETTE, Platform,
+};
+use chip8sys::rom_db::{self, RomDatabase, RomInfo};
+
+/// This enum lists the platforms JavaScript callers can select.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Platform {
+    /// The original 64x32 CHIP-8.
+    Chip8 = 0,
+    /// The two-page hi-res CHIP-8 with a 64x64 display.
+    HiresChip8 = 1,
+    /// CHIP-8X with the VP-590 colour board.
+    Chip8X = 2,
+}
+
+impl From<Chip8Platform> for Platform {
+    /// This function converts a JS platform selection into a core platform.
+    /// Arguments:
+    /// - platform: The JS platform value.
+    ///
+    /// Returns: The core platform.
+    fn from(platform: Chip8Platform) -> Self {
+        match platform {
+            Chip8Platform::Chip8 => Platform::Chip8,
+            Chip8Platform::HiresChip8 => Platform::HiresChip8,
+            Chip8Platform::Chip8X => Platform::Chip8X,
+        }
+    }
+}
+
+impl From<Platform> for Chip8Platform {
+    /// This function converts a core platform into its JS platform value.
+    /// Arguments:
+    /// - platform: The core platform.
+    ///
+    /// Returns: The JS platform value.
+    fn from(platform: Platform) -> Self {
+        match platform {
+            Platform::Chip8 => Chip8Platform::Chip8,
+            Platform::HiresChip8 => Chip8Platform::HiresChip8,
+            Platform::Chip8X => Chip8Platform::Chip8X,
+        }
+    }
+}
+
+/// This enum lists the memory layouts JavaScript callers can select.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8MemoryMap {
+    /// 4KB, the CHIP-48 font and programs at the platform's program start.
+    Default = 0,
+    /// 4KB, the COSMAC VIP font and programs at 0x200.
+    CosmacVip = 1,
+    /// 4KB, the ETI-660 font and programs at 0x600.
+    Eti660 = 2,
+    /// 4KB, the DREAM 6800 font and programs at 0x200.
+    Dream6800 = 3,
+}
+
+impl From<Chip8MemoryMap> for MemoryMap {
+    /// This function converts a JS memory layout selection into a core memory map.
+    /// Arguments:
+    /// - map: The JS memory layout value.
+    ///
+    /// Returns: The core memory map.
+    fn from(map: Chip8MemoryMap) -> Self {
+        match map {
+            Chip8MemoryMap::Default => MemoryMap::default(),
+            Chip8MemoryMap::CosmacVip => MemoryMap::cosmac_vip(),
+            Chip8MemoryMap::Eti660 => MemoryMap::eti_660(),
+            Chip8MemoryMap::Dream6800 => MemoryMap::dream_6800(),
+        }
+    }
+}
+
+/// This enum lists the quirk presets JavaScript callers can select, named after the
+/// interpreters in the CHIP-8 database.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8QuirkPreset {
+    /// The COSMAC VIP interpreter.
+    CosmacVip = 0,
+    /// Modern CHIP-8 interpreters.
+    ModernChip8 = 1,
+    /// CHIP-48 on the HP-48.
+    Chip48 = 2,
+    /// SUPER-CHIP.
+    SuperChip = 3,
+    /// XO-CHIP.
+    XoChip = 4,
+}
+
+impl From<Chip8QuirkPreset> for Chip8Quirks {
+    /// This function returns the quirks of a preset.
+    /// Arguments:
+    /// - preset: The JS preset value.
+    ///
+    /// Returns: The core quirks.
+    fn from(preset: Chip8QuirkPreset) -> Self {
+        let id = match preset {
+            Chip8QuirkPreset::CosmacVip => "originalChip8",
+            Chip8QuirkPreset::ModernChip8 => "modernChip8",
+            Chip8QuirkPreset::Chip48 => "chip48",
+            Chip8QuirkPreset::SuperChip => "superchip",
+            Chip8QuirkPreset::XoChip => "xochip",
+        };
+        rom_db::platform_quirks(id)
+            .expect("every preset is a database platform")
+            .to_quirks(id)
+    }
+}
+
+/// This enum lists the keyboard layouts JavaScript callers can select.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8KeyLayout {
+    /// `1234/QWER/ASDF/ZXCV` on a QWERTY keyboard.
+    Qwerty = 0,
+    /// `1234/AZER/QSDF/WXCV` on an AZERTY keyboard.
+    Azerty = 1,
+    /// `1234/',.P/AOEU/;QJK` on a Dvorak keyboard.
+    Dvorak = 2,
+    /// The number pad, with A-F on the operator keys.
+    Numpad = 3,
+}
+
+impl From<Chip8KeyLayout> for KeyLayout {
+    /// This function converts a JS layout selection into a core layout.
+    /// Arguments:
+    /// - layout: The JS layout value.
+    ///
+    /// Returns: The core layout.
+    fn from(layout: Chip8KeyLayout) -> Self {
+        match layout {
+            Chip8KeyLayout::Qwerty => KeyLayout::Qwerty,
+            Chip8KeyLayout::Azerty => KeyLayout::Azerty,
+            Chip8KeyLayout::Dvorak => KeyLayout::Dvorak,
+            Chip8KeyLayout::Numpad => KeyLayout::Numpad,
+        }
+    }
+}
+
+impl From<KeyLayout> for Chip8KeyLayout {
+    /// This function converts a core layout into its JS layout value.
+    /// Arguments:
+    /// - layout: The core layout.
+    ///
+    /// Returns: The JS layout value.
+    fn from(layout: KeyLayout) -> Self {
+        match layout {
+            KeyLayout::Qwerty => Chip8KeyLayout::Qwerty,
+            KeyLayout::Azerty => Chip8KeyLayout::Azerty,
+            KeyLayout::Dvorak => Chip8KeyLayout::Dvorak,
+            KeyLayout::Numpad => Chip8KeyLayout::Numpad,
+        }
+    }
+}
+
+/// This enum lists the run states reported to JavaScript callers.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Status {
+    /// The program is running.
+    Running = 0,
+    /// The program is waiting for input; ticking can pause until the next key event.
+    Idle = 1,
+    /// The program has halted; ticking can stop until a reset or a new ROM.
+    Halted = 2,
+}
+
+impl From<RunStatus> for Chip8Status {
+    /// This function converts a core run status into its JS value.
+    /// Arguments:
+    /// - status: The core run status.
+    ///
+    /// Returns: The JS status value.
+    fn from(status: RunStatus) -> Self {
+        match status {
+            RunStatus::Running => Chip8Status::Running,
+            RunStatus::Idle => Chip8Status::Idle,
+            RunStatus::Halted => Chip8Status::Halted,
+        }
+    }
+}
+
+/// This enum lists the RAM search comparisons JavaScript callers can filter by.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8SearchCondition {
+    /// The value equals the given byte.
+    Equal = 0,
+    /// The value differs from the given byte.
+    NotEqual = 1,
+    /// The value changed since the last filter.
+    Changed = 2,
+    /// The value stayed the same since the last filter.
+    Unchanged = 3,
+    /// The value grew since the last filter.
+    Increased = 4,
+    /// The value shrank since the last filter.
+    Decreased = 5,
+}
+
+impl Chip8SearchCondition {
+    // Pairs the JS condition with the byte Equal and NotEqual compare against
+    fn with_value(self, value: u8) -> SearchCondition {
+        match self {
+            Chip8SearchCondition::Equal => SearchCondition::Equal(value),
+            Chip8SearchCondition::NotEqual => SearchCondition::NotEqual(value),
+            Chip8SearchCondition::Changed => SearchCondition::Changed,
+            Chip8SearchCondition::Unchanged => SearchCondition::Unchanged,
+            Chip8SearchCondition::Increased => SearchCondition::Increased,
+            Chip8SearchCondition::Decreased => SearchCondition::Decreased,
+        }
+    }
+}
+
+/// This enum lists the memory accesses a watchpoint can stop on.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Access {
+    /// A data read, by DXYN or FX65.
+    Read = 0,
+    /// A data write, by FX33 or FX55.
+    Write = 1,
+    /// An instruction fetch.
+    Execute = 2,
+}
+
+impl From<Chip8Access> for Access {
+    /// This function converts a JS access kind into a core access kind.
+    /// Arguments:
+    /// - access: The JS access value.
+    ///
+    /// Returns: The core access kind.
+    fn from(access: Chip8Access) -> Self {
+        match access {
+            Chip8Access::Read => Access::Read,
+            Chip8Access::Write => Access::Write,
+            Chip8Access::Execute => Access::Execute,
+        }
+    }
+}
+
+impl From<Access> for Chip8Access {
+    /// This function converts a core access kind into its JS value.
+    /// Arguments:
+    /// - access: The core access kind.
+    ///
+    /// Returns: The JS access value.
+    fn from(access: Access) -> Self {
+        match access {
+            Access::Read => Chip8Access::Read,
+            Access::Write => Chip8Access::Write,
+            Access::Execute => Chip8Access::Execute,
+        }
+    }
+}
+
+/// This enum lists why a debugger run stopped.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8StopReason {
+    /// The program counter reached a breakpoint, which has not run yet.
+    Breakpoint = 0,
+    /// A watchpoint caught the instruction at the program counter accessing memory; it has not
+    /// run yet.
+    Watchpoint = 1,
+    /// The step over or step out finished.
+    Stepped = 2,
+    /// The program jumps to itself or loops without changing any state.
+    Halted = 3,
+    /// The cycle budget ran out first.
+    CycleLimit = 4,
+}
 
-/// This struct wraps the Chip-8 emulator for JavaScript callers.
+/// This struct wraps the Chip-8 emulator for JavaScript callers. It holds a `Chip8Sys` rather
+/// than a `Machine` because the debugger and cheat calls need the concrete core.
 #[wasm_bindgen]
 pub struct Chip8Wasm {
     emulator: Chip8Sys,
+    keymap_profile: KeymapProfile,
+    keyboard: KeyboardState,
+    rom_sha1: String,
+    ram_search: Option<RamSearch>,
+    // watchpoint hooks indexed by the id handed to JS, None once removed
+    watchpoints: Vec<Option<HookId>>,
+    // one 0/1 byte per pixel, refreshed by updateFramebuffer
+    pixels: Vec<u8>,
+    // four bytes per pixel, refreshed by updateFramebuffer
+    rgba: Vec<u8>,
+    // RGBA colours of lit and unlit pixels outside CHIP-8X
+    on_color: [u8; 4],
+    off_color: [u8; 4],
+}
+
+impl Default for Chip8Wasm {
+    /// This function returns a new Chip-8 WASM wrapper.
+    /// Arguments: none.
+    /// Returns: A new Chip-8 WASM wrapper.
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
@@ -25,9 +335,29 @@ impl Chip8Wasm {
     /// Returns: A new Chip-8 WASM wrapper.
     #[wasm_bindgen(constructor)]
     pub fn new() -> Chip8Wasm {
-        let mut emulator = Chip8Sys::new_chip_8();
-        emulator.set_timer_mode(TimerMode::External);
-        Chip8Wasm { emulator }
+        let emulator = Chip8Sys::builder()
+            .timer_mode(TimerMode::External)
+            .build()
+            .expect("the default configuration is valid");
+        let keymap_profile = KeymapProfile::default();
+        let rom_sha1 = rom_db::sha1_hex(&[]);
+        let keyboard = KeyboardState::new(
+            keymap_profile
+                .keymap_for_rom(&rom_sha1)
+                .expect("the default profile should only have valid keys"),
+        );
+        Chip8Wasm {
+            emulator,
+            keymap_profile,
+            keyboard,
+            rom_sha1,
+            ram_search: None,
+            watchpoints: Vec::new(),
+            pixels: vec![0; FRAMEBUFFER_MAX_LEN * 8],
+            rgba: vec![0; FRAMEBUFFER_MAX_LEN * 8 * 4],
+            on_color: [0xFF, 0xFF, 0xFF, 0xFF],
+            off_color: [0x00, 0x00, 0x00, 0xFF],
+        }
     }
 
     /// This function resets the emulator to its initial state.
@@ -41,27 +371,184 @@ impl Chip8Wasm {
     /// This function loads a ROM byte buffer into memory.
     /// Arguments:
     /// - rom_bytes: The ROM bytes to load.
+    ///
     /// Returns: none.
     #[wasm_bindgen(js_name = "loadRom")]
     pub fn load_rom(&mut self, rom_bytes: &[u8]) {
         self.emulator.load_rom_bytes(rom_bytes);
+        self.rom_sha1 = rom_db::sha1_hex(rom_bytes);
+        // profiles only hold validated bindings, so resolving cannot fail here
+        if let Ok(keymap) = self.keymap_profile.keymap_for_rom(&self.rom_sha1) {
+            self.keyboard.set_keymap(keymap);
+            self.sync_keyboard();
+        }
+    }
+
+    /// This function returns the SHA-1 of the loaded ROM, used to key ROM specific bindings.
+    /// Arguments: none.
+    /// Returns: The lowercase hex SHA-1.
+    #[wasm_bindgen(js_name = "romSha1")]
+    pub fn rom_sha1(&self) -> String {
+        self.rom_sha1.clone()
     }
 
     /// This function updates the keypad state using a 16-bit bitmask.
     /// Arguments:
     /// - mask: The bitmask of pressed keys.
+    ///
     /// Returns: none.
     #[wasm_bindgen(js_name = "setKeys")]
     pub fn set_keys(&mut self, mask: u16) {
         self.emulator.set_keys_mask(mask);
     }
 
+    /// This function presses the keypad key bound to a DOM key code.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string, e.g. `KeyQ`.
+    ///
+    /// Returns: True when the code is bound, so the caller can `preventDefault()`.
+    #[wasm_bindgen(js_name = "keyDown")]
+    pub fn key_down(&mut self, code: &str) -> bool {
+        let is_bound = self.keyboard.key_down(code);
+        self.sync_keyboard();
+        is_bound
+    }
+
+    /// This function releases the keypad key bound to a DOM key code.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string, e.g. `KeyQ`.
+    ///
+    /// Returns: True when the code is bound.
+    #[wasm_bindgen(js_name = "keyUp")]
+    pub fn key_up(&mut self, code: &str) -> bool {
+        let is_bound = self.keyboard.key_up(code);
+        self.sync_keyboard();
+        is_bound
+    }
+
+    /// This function releases every held key, e.g. on window blur.
+    /// Arguments: none.
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "releaseKeys")]
+    pub fn release_keys(&mut self) {
+        self.keyboard.release_all();
+        self.sync_keyboard();
+    }
+
+    /// This function presses a keypad key directly.
+    /// Arguments:
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "pressKey")]
+    pub fn press_key(&mut self, key: u8) -> Result<(), JsValue> {
+        self.emulator.key_down(key).map_err(to_js_error)?;
+        Ok(())
+    }
+
+    /// This function releases a keypad key directly.
+    /// Arguments:
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "releaseKey")]
+    pub fn release_key(&mut self, key: u8) -> Result<(), JsValue> {
+        self.emulator.key_up(key).map_err(to_js_error)?;
+        Ok(())
+    }
+
+    /// This function reports whether the program is waiting for a key (FX0A).
+    /// Arguments: none.
+    /// Returns: True while the CPU is stopped on FX0A.
+    #[wasm_bindgen(js_name = "isWaitingForKey")]
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.emulator.is_waiting_for_key()
+    }
+
+    /// This function selects the keyboard layout of the keymap profile.
+    /// Arguments:
+    /// - layout: The keyboard layout.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "setKeyLayout")]
+    pub fn set_key_layout(&mut self, layout: Chip8KeyLayout) -> Result<(), JsValue> {
+        self.keymap_profile.layout = layout.into();
+        self.refresh_keymap()
+    }
+
+    /// This function returns the keyboard layout of the keymap profile.
+    /// Arguments: none.
+    /// Returns: The keyboard layout.
+    #[wasm_bindgen(js_name = "keyLayout")]
+    pub fn key_layout(&self) -> Chip8KeyLayout {
+        self.keymap_profile.layout.into()
+    }
+
+    /// This function returns the key cap labels of the selected layout.
+    /// Arguments: none.
+    /// Returns: The labels, indexed by keypad key 0x0-0xF.
+    #[wasm_bindgen(js_name = "keyLabels")]
+    pub fn key_labels(&self) -> Vec<String> {
+        self.keymap_profile
+            .layout
+            .labels()
+            .iter()
+            .map(|label| label.to_string())
+            .collect()
+    }
+
+    /// This function binds a DOM key code to a keypad key for every ROM.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "bindKey")]
+    pub fn bind_key(&mut self, code: &str, key: u8) -> Result<(), JsValue> {
+        self.keymap_profile.bind(code, key).map_err(to_js_error)?;
+        self.refresh_keymap()
+    }
+
+    /// This function binds a DOM key code to a keypad key for the loaded ROM only.
+    /// Arguments:
+    /// - code: The `KeyboardEvent.code` string.
+    /// - key: The keypad key 0x0-0xF.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "bindRomKey")]
+    pub fn bind_rom_key(&mut self, code: &str, key: u8) -> Result<(), JsValue> {
+        self.keymap_profile
+            .bind_for_rom(&self.rom_sha1, code, key)
+            .map_err(to_js_error)?;
+        self.refresh_keymap()
+    }
+
+    /// This function returns the keymap profile as JSON for persistence.
+    /// Arguments: none.
+    /// Returns: The profile JSON, otherwise a JS error.
+    #[wasm_bindgen(js_name = "keymapProfile")]
+    pub fn keymap_profile(&self) -> Result<String, JsValue> {
+        self.keymap_profile.to_json().map_err(to_js_error)
+    }
+
+    /// This function replaces the keymap profile with one saved by `keymapProfile`.
+    /// Arguments:
+    /// - json: The profile JSON.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "loadKeymapProfile")]
+    pub fn load_keymap_profile(&mut self, json: &str) -> Result<(), JsValue> {
+        self.keymap_profile = KeymapProfile::from_json(json).map_err(to_js_error)?;
+        self.refresh_keymap()
+    }
+
     /// This function updates the emulator quirk settings.
     /// Arguments:
     /// - increment_i_on_store: Whether FX55/FX65 increment I.
     /// - reset_vf_on_logic: Whether logic ops reset VF.
     /// - wrap_draw: Whether sprites wrap at screen edges.
     /// - shift_uses_vx: Whether shifts use VX instead of VY.
+    ///
     /// Returns: none.
     #[wasm_bindgen(js_name = "setQuirks")]
     pub fn set_quirks(
@@ -76,33 +563,321 @@ impl Chip8Wasm {
             reset_vf_on_logic,
             wrap_draw,
             shift_uses_vx,
+            ..self.emulator.quirks()
+        });
+    }
+
+    /// This function applies a quirk preset.
+    /// Arguments:
+    /// - preset: The interpreter whose quirks to copy.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setQuirkPreset")]
+    pub fn set_quirk_preset(&mut self, preset: Chip8QuirkPreset) {
+        self.emulator.set_quirks(preset.into());
+    }
+
+    /// This function selects whether FX0A waits for the key to be released or only pressed.
+    /// Arguments:
+    /// - wait_for_key_release: True for the COSMAC VIP press-and-release behaviour.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setWaitForKeyRelease")]
+    pub fn set_wait_for_key_release(&mut self, wait_for_key_release: bool) {
+        self.emulator.set_quirks(Chip8Quirks {
+            wait_for_key_release,
+            ..self.emulator.quirks()
         });
     }
 
+    /// This function selects the emulated platform.
+    /// Loading a ROM that starts with `1260` switches to hi-res CHIP-8 automatically.
+    /// Arguments:
+    /// - platform: The platform to emulate.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setPlatform")]
+    pub fn set_platform(&mut self, platform: Chip8Platform) {
+        self.emulator.set_platform(platform.into());
+    }
+
+    /// This function selects where the font and programs live, e.g. ETI-660 ROMs at 0x600.
+    /// The layout survives `reset`; load the ROM after selecting it.
+    /// Arguments:
+    /// - map: The memory layout.
+    ///
+    /// Returns: An error when the layout does not fit the active platform.
+    #[wasm_bindgen(js_name = "setMemoryMap")]
+    pub fn set_memory_map(&mut self, map: Chip8MemoryMap) -> Result<(), JsValue> {
+        self.emulator
+            .set_memory_map(map.into())
+            .map(|_| ())
+            .map_err(to_js_error)
+    }
+
+    /// This function starts execution somewhere other than the load address after a reset.
+    /// Arguments:
+    /// - address: The entry address, or undefined for the load address.
+    ///
+    /// Returns: An error when the address is inside the font or past the end of memory.
+    #[wasm_bindgen(js_name = "setEntryAddress")]
+    pub fn set_entry_address(&mut self, address: Option<u16>) -> Result<(), JsValue> {
+        let map = MemoryMap {
+            entry_address: address,
+            ..self.emulator.memory_map()
+        };
+        self.emulator
+            .set_memory_map(map)
+            .map(|_| ())
+            .map_err(to_js_error)
+    }
+
+    /// This function returns the emulated platform.
+    /// Arguments: none.
+    /// Returns: The active platform.
+    #[wasm_bindgen(js_name = "platform")]
+    pub fn platform(&self) -> Chip8Platform {
+        self.emulator.platform().into()
+    }
+
+    /// This function returns the display width of the active platform.
+    /// Arguments: none.
+    /// Returns: The display width in pixels.
+    #[wasm_bindgen(js_name = "width")]
+    pub fn width(&self) -> u32 {
+        self.emulator.display_width() as u32
+    }
+
+    /// This function returns the display height of the active platform.
+    /// Arguments: none.
+    /// Returns: The display height in pixels.
+    #[wasm_bindgen(js_name = "height")]
+    pub fn height(&self) -> u32 {
+        self.emulator.display_height() as u32
+    }
+
+    /// This function returns the CHIP-8X foreground colour map.
+    /// Arguments: none.
+    /// Returns: One palette index per 8-pixel column for each of the 32 rows.
+    #[wasm_bindgen(js_name = "colorMap")]
+    pub fn color_map(&self) -> Vec<u8> {
+        self.emulator.color_map().to_vec()
+    }
+
+    /// This function returns the CHIP-8X background colour index.
+    /// Arguments: none.
+    /// Returns: The background colour index (blue, black, green, red).
+    #[wasm_bindgen(js_name = "backgroundColor")]
+    pub fn background_color(&self) -> u8 {
+        self.emulator.background_color()
+    }
+
+    /// This function configures the start address, platform and quirks from a ROM database
+    /// entry. Load the ROM afterwards so it lands at the start address.
+    /// Arguments:
+    /// - info: The ROM database entry.
+    ///
+    /// Returns: Ok on success, otherwise a JS error when the start address does not fit.
+    #[wasm_bindgen(js_name = "applyRomInfo")]
+    pub fn apply_rom_info(&mut self, info: &Chip8RomInfo) -> Result<(), JsValue> {
+        info.info.configure(&mut self.emulator).map_err(to_js_error)
+    }
+
     /// This function advances the emulator by a number of CPU cycles.
     /// Arguments:
     /// - cycles: The number of cycles to execute.
+    ///
     /// Returns: Ok on success, otherwise a JS error.
     #[wasm_bindgen(js_name = "tick")]
     pub fn tick(&mut self, cycles: u32) -> Result<(), JsValue> {
         self.emulator.tick(cycles).map_err(to_js_error)
     }
 
+    /// This function disassembles memory for the active platform.
+    /// Arguments:
+    /// - start: The first address.
+    /// - end: The address to stop before.
+    ///
+    /// Returns: One line per 2-byte instruction word.
+    #[wasm_bindgen(js_name = "disassemble")]
+    pub fn disassemble(&self, start: u16, end: u16) -> Vec<Chip8DisassemblyLine> {
+        self.emulator
+            .disassemble(start..end)
+            .into_iter()
+            .map(Chip8DisassemblyLine::from)
+            .collect()
+    }
+
+    /// This function adds a breakpoint; breakpoints survive `reset`.
+    /// Arguments:
+    /// - address: The address of the instruction to stop at.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "addBreakpoint")]
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.emulator.add_breakpoint(address);
+    }
+
+    /// This function removes a breakpoint.
+    /// Arguments:
+    /// - address: The address of the breakpoint.
+    ///
+    /// Returns: True if there was a breakpoint at the address.
+    #[wasm_bindgen(js_name = "removeBreakpoint")]
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.emulator.remove_breakpoint(address)
+    }
+
+    /// This function removes every breakpoint.
+    /// Arguments: none.
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "clearBreakpoints")]
+    pub fn clear_breakpoints(&mut self) {
+        self.emulator.clear_breakpoints();
+    }
+
+    /// This function returns the breakpoints.
+    /// Arguments: none.
+    /// Returns: The breakpoint addresses in ascending order.
+    #[wasm_bindgen(js_name = "breakpoints")]
+    pub fn breakpoints(&self) -> Vec<u16> {
+        self.emulator.breakpoints().to_vec()
+    }
+
+    /// This function adds a watchpoint; watchpoints survive `reset`.
+    /// Arguments:
+    /// - start: The first watched address.
+    /// - end: The last watched address, inclusive.
+    /// - access: The kind of access to stop on.
+    ///
+    /// Returns: An id for `removeWatchpoint`.
+    #[wasm_bindgen(js_name = "addWatchpoint")]
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, access: Chip8Access) -> u32 {
+        let hook = Watchpoint::new(start..=end, access.into());
+        self.watchpoints
+            .push(Some(self.emulator.memory.add_hook(hook)));
+        self.watchpoints.len() as u32 - 1
+    }
+
+    /// This function removes a watchpoint.
+    /// Arguments:
+    /// - id: The id `addWatchpoint` returned.
+    ///
+    /// Returns: True if the watchpoint was still set.
+    #[wasm_bindgen(js_name = "removeWatchpoint")]
+    pub fn remove_watchpoint(&mut self, id: u32) -> bool {
+        let hook = self.watchpoints.get_mut(id as usize).and_then(Option::take);
+        hook.is_some_and(|hook| self.emulator.memory.remove_hook(hook).is_some())
+    }
+
+    /// This function removes every watchpoint, leaving other memory hooks in place.
+    /// Arguments: none.
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "clearWatchpoints")]
+    pub fn clear_watchpoints(&mut self) {
+        for hook in self.watchpoints.drain(..).flatten() {
+            self.emulator.memory.remove_hook(hook);
+        }
+    }
+
+    /// This function runs until a breakpoint or watchpoint fires or the program halts. Neither
+    /// fires on the first instruction, so calling it again continues from where it stopped.
+    /// Arguments:
+    /// - max_cycles: The most cycles to run.
+    ///
+    /// Returns: Why the run stopped, or a JS error raised by an instruction.
+    #[wasm_bindgen(js_name = "runUntilBreak")]
+    pub fn run_until_break(&mut self, max_cycles: u32) -> Result<Chip8StopInfo, JsValue> {
+        self.emulator
+            .run_until_break(max_cycles)
+            .map(Chip8StopInfo::from)
+            .map_err(to_js_error)
+    }
+
+    /// This function runs one instruction, or a whole subroutine when it is a 2NNN call.
+    /// Arguments:
+    /// - max_cycles: The most cycles to run.
+    ///
+    /// Returns: Why the run stopped, or a JS error raised by an instruction.
+    #[wasm_bindgen(js_name = "stepOver")]
+    pub fn step_over(&mut self, max_cycles: u32) -> Result<Chip8StopInfo, JsValue> {
+        self.emulator
+            .step_over(max_cycles)
+            .map(Chip8StopInfo::from)
+            .map_err(to_js_error)
+    }
+
+    /// This function runs until the current subroutine returns.
+    /// Arguments:
+    /// - max_cycles: The most cycles to run.
+    ///
+    /// Returns: Why the run stopped, or a JS error raised by an instruction.
+    #[wasm_bindgen(js_name = "stepOut")]
+    pub fn step_out(&mut self, max_cycles: u32) -> Result<Chip8StopInfo, JsValue> {
+        self.emulator
+            .step_out(max_cycles)
+            .map(Chip8StopInfo::from)
+            .map_err(to_js_error)
+    }
+
+    /// This function reports whether the program is running, idle or halted.
+    /// Arguments: none.
+    /// Returns: The run status.
+    #[wasm_bindgen(js_name = "status")]
+    pub fn status(&self) -> Chip8Status {
+        self.emulator.status().into()
+    }
+
     /// This function advances the delay and sound timers by a number of ticks.
     /// Arguments:
     /// - ticks: The number of 60Hz timer ticks to apply.
+    ///
     /// Returns: none.
     #[wasm_bindgen(js_name = "tickTimers")]
     pub fn tick_timers(&mut self, ticks: u32) {
         self.emulator.tick_timers(ticks);
     }
 
+    /// This function runs one 60Hz frame: a number of CPU cycles followed by a timer tick.
+    /// Arguments:
+    /// - cycles: The number of cycles to execute in the frame.
+    ///
+    /// Returns: Ok on success, otherwise a JS error.
+    #[wasm_bindgen(js_name = "runFrame")]
+    pub fn run_frame(&mut self, cycles: u32) -> Result<(), JsValue> {
+        self.emulator.run_frame(cycles).map_err(to_js_error)
+    }
+
+    /// This function saves the machine state.
+    /// Arguments: none.
+    /// Returns: The save state bytes.
+    #[wasm_bindgen(js_name = "saveState")]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![0; self.emulator.state_len()];
+        self.emulator
+            .save_state(&mut state)
+            .expect("the buffer is sized by state_len");
+        state
+    }
+
+    /// This function restores a state returned by `saveState`.
+    /// Arguments:
+    /// - state: The save state bytes.
+    ///
+    /// Returns: Ok on success, otherwise a JS error and the machine is unchanged.
+    #[wasm_bindgen(js_name = "loadState")]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsValue> {
+        self.emulator.load_state(state).map_err(to_js_error)
+    }
+
     /// This function returns the framebuffer as a 0/1 byte array.
     /// Arguments: none.
-    /// Returns: A framebuffer array with one byte per pixel.
+    /// Returns: A framebuffer array with one byte per pixel, `width() * height()` long.
     #[wasm_bindgen(js_name = "framebuffer")]
     pub fn framebuffer(&self) -> Vec<u8> {
-        unpack_framebuffer(self.emulator.framebuffer_packed())
+        let mut pixels = vec![0; self.framebuffer_len()];
+        unpack_framebuffer(self.emulator.framebuffer_packed(), &mut pixels);
+        pixels
     }
 
     /// This function returns the packed framebuffer bytes.
@@ -113,61 +888,1012 @@ impl Chip8Wasm {
         self.emulator.framebuffer_packed().to_vec()
     }
 
-    /// This function returns the current program counter address.
+    /// This function reports whether the display changed since the last `updateFramebuffer`.
     /// Arguments: none.
-    /// Returns: The program counter value.
-    #[wasm_bindgen(js_name = "programCounter")]
-    pub fn program_counter(&self) -> u16 {
-        self.emulator.program_counter
+    /// Returns: True when `DXYN` or `00E0` changed pixels, or the platform, colours or state
+    /// changed.
+    #[wasm_bindgen(js_name = "isDisplayDirty")]
+    pub fn is_display_dirty(&self) -> bool {
+        self.emulator.is_display_dirty()
     }
 
-    /// This function returns the current instruction word at the program counter.
+    /// This function refreshes the buffers behind `framebufferPtr` and `rgbaPtr` when the
+    /// display is dirty, and marks it clean.
     /// Arguments: none.
-    /// Returns: The 16-bit instruction word.
-    #[wasm_bindgen(js_name = "currentInstruction")]
-    pub fn current_instruction(&self) -> u16 {
-        let pc = self.emulator.program_counter as usize;
-        let memory = &self.emulator.memory;
-        if pc + 1 >= memory.len() {
-            return 0;
+    /// Returns: True when the buffers changed and textures should be re-uploaded.
+    #[wasm_bindgen(js_name = "updateFramebuffer")]
+    pub fn update_framebuffer(&mut self) -> bool {
+        if !self.emulator.is_display_dirty() {
+            return false;
         }
-        ((memory[pc] as u16) << 8) | memory[pc + 1] as u16
+        self.render();
+        self.emulator.clear_display_dirty();
+        true
     }
 
-    /// This function returns the current delay timer value.
+    /// This function sets the colours `rgbaPtr` uses outside CHIP-8X and redraws.
+    /// Arguments:
+    /// - on: The lit pixel colour as 0xRRGGBBAA.
+    /// - off: The unlit pixel colour as 0xRRGGBBAA.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setColors")]
+    pub fn set_colors(&mut self, on: u32, off: u32) {
+        self.on_color = on.to_be_bytes();
+        self.off_color = off.to_be_bytes();
+        self.render();
+    }
+
+    /// This function returns where the 0/1 pixels live in wasm memory, one byte per pixel.
+    /// Wrap it with `new Uint8Array(memory.buffer, ptr, framebufferLen())`; the view stays
+    /// valid until wasm memory grows.
     /// Arguments: none.
-    /// Returns: The delay timer value.
-    #[wasm_bindgen(js_name = "delayTimer")]
-    pub fn delay_timer(&self) -> u8 {
-        self.emulator.delay_timer()
+    /// Returns: A pointer into wasm linear memory.
+    #[wasm_bindgen(js_name = "framebufferPtr")]
+    pub fn framebuffer_ptr(&self) -> *const u8 {
+        self.pixels.as_ptr()
     }
 
-    /// This function returns the current sound timer value.
+    /// This function returns the number of pixels behind `framebufferPtr`.
     /// Arguments: none.
-    /// Returns: The sound timer value.
-    #[wasm_bindgen(js_name = "soundTimer")]
-    pub fn sound_timer(&self) -> u8 {
-        self.emulator.sound_timer()
+    /// Returns: `width() * height()`.
+    #[wasm_bindgen(js_name = "framebufferLen")]
+    pub fn framebuffer_len(&self) -> usize {
+        self.emulator.display_width() * self.emulator.display_height()
     }
 
-    /// This function reports whether the sound timer is active.
+    /// This function returns where the packed framebuffer lives in wasm memory. It is the
+    /// emulator's own buffer, so it is current without `updateFramebuffer`.
     /// Arguments: none.
-    /// Returns: True if sound is playing.
-    #[wasm_bindgen(js_name = "isSoundPlaying")]
-    pub fn is_sound_playing(&self) -> bool {
-        self.emulator.is_sound_playing()
+    /// Returns: A pointer to `framebufferPackedLen()` bytes, most significant bit first.
+    #[wasm_bindgen(js_name = "framebufferPackedPtr")]
+    pub fn framebuffer_packed_ptr(&self) -> *const u8 {
+        self.emulator.frame_buffer.as_ptr()
     }
-}
 
-/// This function returns the Chip-8 display width in pixels.
-/// Arguments: none.
-/// Returns: The display width.
-#[wasm_bindgen(js_name = "displayWidth")]
-pub fn display_width() -> u32 {
-    DISPLAY_WIDTH as u32
+    /// This function returns the number of packed framebuffer bytes.
+    /// Arguments: none.
+    /// Returns: The packed length for the active display size.
+    #[wasm_bindgen(js_name = "framebufferPackedLen")]
+    pub fn framebuffer_packed_len(&self) -> usize {
+        self.emulator.framebuffer_packed().len()
+    }
+
+    /// This function returns where the RGBA pixels live in wasm memory, ready for
+    /// `new ImageData(new Uint8ClampedArray(memory.buffer, ptr, rgbaLen()), width())`.
+    /// Arguments: none.
+    /// Returns: A pointer into wasm linear memory.
+    #[wasm_bindgen(js_name = "rgbaPtr")]
+    pub fn rgba_ptr(&self) -> *const u8 {
+        self.rgba.as_ptr()
+    }
+
+    /// This function returns the number of RGBA bytes behind `rgbaPtr`.
+    /// Arguments: none.
+    /// Returns: Four bytes per pixel.
+    #[wasm_bindgen(js_name = "rgbaLen")]
+    pub fn rgba_len(&self) -> usize {
+        self.framebuffer_len() * 4
+    }
+
+    /// This function returns where the emulated RAM lives in wasm memory. Reads through the
+    /// view bypass memory hooks and coverage.
+    /// Arguments: none.
+    /// Returns: A pointer to `memoryLen()` bytes.
+    #[wasm_bindgen(js_name = "memoryPtr")]
+    pub fn memory_ptr(&self) -> *const u8 {
+        self.emulator.memory.as_ptr()
+    }
+
+    /// This function returns the installed memory size.
+    /// Arguments: none.
+    /// Returns: The size in bytes.
+    #[wasm_bindgen(js_name = "memoryLen")]
+    pub fn memory_len(&self) -> usize {
+        self.emulator.memory.size()
+    }
+
+    /// This function returns where V0-VF live in wasm memory.
+    /// Arguments: none.
+    /// Returns: A pointer to 16 bytes.
+    #[wasm_bindgen(js_name = "registersPtr")]
+    pub fn registers_ptr(&self) -> *const u8 {
+        self.emulator.register.as_ptr()
+    }
+
+    /// This function returns the current program counter address.
+    /// Arguments: none.
+    /// Returns: The program counter value.
+    #[wasm_bindgen(js_name = "programCounter")]
+    pub fn program_counter(&self) -> u16 {
+        self.emulator.program_counter
+    }
+
+    /// This function moves the program counter, e.g. while paused in a debugger.
+    /// Arguments:
+    /// - address: The new program counter.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setProgramCounter")]
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.emulator.program_counter = address;
+    }
+
+    /// This function returns one of the registers V0-VF.
+    /// Arguments:
+    /// - x: The register number.
+    ///
+    /// Returns: The register value, or an error when x > 0xF.
+    #[wasm_bindgen(js_name = "register")]
+    pub fn register(&self, x: u8) -> Result<u8, JsValue> {
+        self.try_register(x).map_err(to_js_error)
+    }
+
+    /// This function sets one of the registers V0-VF.
+    /// Arguments:
+    /// - x: The register number.
+    /// - value: The new value.
+    ///
+    /// Returns: Ok, or an error when x > 0xF.
+    #[wasm_bindgen(js_name = "setRegister")]
+    pub fn set_register(&mut self, x: u8, value: u8) -> Result<(), JsValue> {
+        self.try_set_register(x, value).map_err(to_js_error)
+    }
+
+    /// This function returns the registers V0-VF.
+    /// Arguments: none.
+    /// Returns: 16 register values.
+    #[wasm_bindgen(js_name = "registers")]
+    pub fn registers(&self) -> Vec<u8> {
+        self.emulator.register.to_vec()
+    }
+
+    /// This function returns the index register I.
+    /// Arguments: none.
+    /// Returns: The I register value.
+    #[wasm_bindgen(js_name = "indexRegister")]
+    pub fn index_register(&self) -> u16 {
+        self.emulator.register_i
+    }
+
+    /// This function sets the index register I.
+    /// Arguments:
+    /// - value: The new I register value.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setIndexRegister")]
+    pub fn set_index_register(&mut self, value: u16) {
+        self.emulator.register_i = value;
+    }
+
+    /// This function returns the 16 stack slots, including unused ones.
+    /// Arguments: none.
+    /// Returns: The stack, slots 1 to `stackPointer()` hold return addresses.
+    #[wasm_bindgen(js_name = "stack")]
+    pub fn stack(&self) -> Vec<u16> {
+        self.emulator.stack.to_vec()
+    }
+
+    /// This function sets one stack slot.
+    /// Arguments:
+    /// - slot: The stack slot.
+    /// - address: The return address to store.
+    ///
+    /// Returns: Ok, or an error when slot > 0xF.
+    #[wasm_bindgen(js_name = "setStackSlot")]
+    pub fn set_stack_slot(&mut self, slot: u8, address: u16) -> Result<(), JsValue> {
+        self.try_set_stack_slot(slot, address).map_err(to_js_error)
+    }
+
+    /// This function returns the stack pointer.
+    /// Arguments: none.
+    /// Returns: The slot of the latest return address, 0 when the stack is empty.
+    #[wasm_bindgen(js_name = "stackPointer")]
+    pub fn stack_pointer(&self) -> u8 {
+        self.emulator.stack_pointer
+    }
+
+    /// This function sets the stack pointer.
+    /// Arguments:
+    /// - pointer: The new stack pointer.
+    ///
+    /// Returns: Ok, or an error when pointer > 0xF.
+    #[wasm_bindgen(js_name = "setStackPointer")]
+    pub fn set_stack_pointer(&mut self, pointer: u8) -> Result<(), JsValue> {
+        self.try_set_stack_pointer(pointer).map_err(to_js_error)
+    }
+
+    /// This function returns the keypad state.
+    /// Arguments: none.
+    /// Returns: A bitmask with bit N set while key N is held.
+    #[wasm_bindgen(js_name = "keys")]
+    pub fn keys(&self) -> u16 {
+        self.emulator.keys_mask()
+    }
+
+    /// This function returns the register FX0A will store the next key in.
+    /// Arguments: none.
+    /// Returns: The register X, or undefined when the CPU is not waiting for a key.
+    #[wasm_bindgen(js_name = "waitingRegister")]
+    pub fn waiting_register(&self) -> Option<u8> {
+        self.emulator.waiting_register()
+    }
+
+    /// This function returns the active quirks.
+    /// Arguments: none.
+    /// Returns: The quirk flags.
+    #[wasm_bindgen(js_name = "quirks")]
+    pub fn quirks(&self) -> Chip8QuirkFlags {
+        self.emulator.quirks().into()
+    }
+
+    /// This function returns the whole CPU state at once, for drawing a debugger pane.
+    /// Arguments: none.
+    /// Returns: The CPU snapshot.
+    #[wasm_bindgen(js_name = "snapshot")]
+    pub fn snapshot(&self) -> Chip8Snapshot {
+        Chip8Snapshot {
+            program_counter: self.emulator.program_counter,
+            current_instruction: self.current_instruction(),
+            index_register: self.emulator.register_i,
+            registers: self.emulator.register,
+            stack: self.emulator.stack,
+            stack_pointer: self.emulator.stack_pointer,
+            delay_timer: self.emulator.delay_timer(),
+            sound_timer: self.emulator.sound_timer(),
+            is_sound_playing: self.emulator.is_sound_playing(),
+            keys: self.emulator.keys_mask(),
+            waiting_register: self.emulator.waiting_register(),
+            quirks: self.emulator.quirks().into(),
+        }
+    }
+
+    /// This function turns coverage counting on or off; turning it off discards the counters.
+    /// Arguments:
+    /// - enabled: True to count executions, reads and writes per address.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setCoverage")]
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.emulator.set_coverage(enabled);
+    }
+
+    /// This function sets every coverage counter back to zero.
+    /// Arguments: none.
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "clearCoverage")]
+    pub fn clear_coverage(&mut self) {
+        self.emulator.clear_coverage();
+    }
+
+    /// This function returns how often each address started an instruction.
+    /// Arguments: none.
+    /// Returns: A `Uint32Array` of 4096 counters, empty while coverage is off.
+    #[wasm_bindgen(js_name = "coverageExecutions")]
+    pub fn coverage_executions(&self) -> Vec<u32> {
+        self.emulator
+            .coverage()
+            .map_or_else(Vec::new, |coverage| coverage.executions().to_vec())
+    }
+
+    /// This function returns how often each address was read.
+    /// Arguments: none.
+    /// Returns: A `Uint32Array` of 4096 counters, empty while coverage is off.
+    #[wasm_bindgen(js_name = "coverageReads")]
+    pub fn coverage_reads(&self) -> Vec<u32> {
+        self.emulator
+            .coverage()
+            .map_or_else(Vec::new, |coverage| coverage.reads().to_vec())
+    }
+
+    /// This function returns how often each address was written.
+    /// Arguments: none.
+    /// Returns: A `Uint32Array` of 4096 counters, empty while coverage is off.
+    #[wasm_bindgen(js_name = "coverageWrites")]
+    pub fn coverage_writes(&self) -> Vec<u32> {
+        self.emulator
+            .coverage()
+            .map_or_else(Vec::new, |coverage| coverage.writes().to_vec())
+    }
+
+    /// This function draws the coverage counters as a 64x64 heatmap.
+    /// Arguments: none.
+    /// Returns: RGBA bytes for `new ImageData(..., 64, 64)`, empty while coverage is off.
+    #[wasm_bindgen(js_name = "coverageHeatmap")]
+    pub fn coverage_heatmap(&self) -> Vec<u8> {
+        self.emulator
+            .coverage()
+            .map_or_else(Vec::new, |coverage| coverage.heatmap_rgba())
+    }
+
+    /// This function writes the execution counters as an lcov report.
+    /// Arguments:
+    /// - symbols: The assembler's symbol file text.
+    /// - rom_name: The file name to report unmapped addresses against.
+    ///
+    /// Returns: The lcov text, or a JS error if coverage is off or the symbols do not parse.
+    #[wasm_bindgen(js_name = "coverageLcov")]
+    pub fn coverage_lcov(&self, symbols: &str, rom_name: &str) -> Result<String, JsValue> {
+        let symbols = SymbolFile::parse(symbols).map_err(to_js_error)?;
+        let coverage = self
+            .emulator
+            .coverage()
+            .ok_or_else(|| js_error("CoverageOff", "coverage is off"))?;
+        Ok(coverage.to_lcov(&symbols, rom_name))
+    }
+
+    /// This function starts a RAM search with every address and register as a candidate.
+    /// Arguments: none.
+    /// Returns: The number of candidates.
+    #[wasm_bindgen(js_name = "searchStart")]
+    pub fn search_start(&mut self) -> u32 {
+        let search = RamSearch::new(&self.emulator);
+        let count = search.candidates().len() as u32;
+        self.ram_search = Some(search);
+        count
+    }
+
+    /// This function keeps the search candidates that match a condition.
+    /// Arguments:
+    /// - condition: The comparison to filter by.
+    /// - value: The byte Equal and NotEqual compare against, ignored otherwise.
+    ///
+    /// Returns: The number of candidates left, starting a search first if none is running.
+    #[wasm_bindgen(js_name = "searchFilter")]
+    pub fn search_filter(&mut self, condition: Chip8SearchCondition, value: u8) -> u32 {
+        let search = self
+            .ram_search
+            .get_or_insert_with(|| RamSearch::new(&self.emulator));
+        search.filter(&self.emulator, condition.with_value(value)) as u32
+    }
+
+    /// This function returns the remaining search candidates.
+    /// Arguments: none.
+    /// Returns: Targets as written in cheat lists, `3F0` or `V5`.
+    #[wasm_bindgen(js_name = "searchResults")]
+    pub fn search_results(&self) -> Vec<String> {
+        self.ram_search.as_ref().map_or_else(Vec::new, |search| {
+            search
+                .candidates()
+                .iter()
+                .map(|(target, _)| target.to_string())
+                .collect()
+        })
+    }
+
+    /// This function returns the candidates' values at the last filter.
+    /// Arguments: none.
+    /// Returns: A `Uint8Array` in the same order as `searchResults`.
+    #[wasm_bindgen(js_name = "searchValues")]
+    pub fn search_values(&self) -> Vec<u8> {
+        self.ram_search.as_ref().map_or_else(Vec::new, |search| {
+            search
+                .candidates()
+                .iter()
+                .map(|(_, value)| *value)
+                .collect()
+        })
+    }
+
+    /// This function replaces the cheats with a cheat list.
+    /// Arguments:
+    /// - text: The cheat list text.
+    ///
+    /// Returns: A JS error if the list does not parse.
+    #[wasm_bindgen(js_name = "loadCheats")]
+    pub fn load_cheats(&mut self, text: &str) -> Result<(), JsValue> {
+        let list = CheatList::parse(text).map_err(to_js_error)?;
+        self.emulator.set_cheats(list);
+        Ok(())
+    }
+
+    /// This function writes the cheats as a cheat list for saving.
+    /// Arguments: none.
+    /// Returns: The cheat list text.
+    #[wasm_bindgen(js_name = "cheatsText")]
+    pub fn cheats_text(&self) -> String {
+        self.emulator.cheat_list().to_text()
+    }
+
+    /// This function returns the cheat names for a cheat panel.
+    /// Arguments: none.
+    /// Returns: The names in list order.
+    #[wasm_bindgen(js_name = "cheatNames")]
+    pub fn cheat_names(&self) -> Vec<String> {
+        self.emulator
+            .cheats()
+            .iter()
+            .map(|cheat| cheat.name.clone())
+            .collect()
+    }
+
+    /// This function reports whether a cheat is switched on.
+    /// Arguments:
+    /// - name: The cheat's name.
+    ///
+    /// Returns: True if the cheat exists and is enabled.
+    #[wasm_bindgen(js_name = "isCheatEnabled")]
+    pub fn is_cheat_enabled(&self, name: &str) -> bool {
+        self.emulator
+            .cheats()
+            .iter()
+            .any(|cheat| cheat.name == name && cheat.is_enabled)
+    }
+
+    /// This function switches a cheat on or off.
+    /// Arguments:
+    /// - name: The cheat's name.
+    /// - enabled: True to apply the cheat.
+    ///
+    /// Returns: True if a cheat by that name exists.
+    #[wasm_bindgen(js_name = "setCheatEnabled")]
+    pub fn set_cheat_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        self.emulator.set_cheat_enabled(name, enabled)
+    }
+
+    /// This function adds a cheat that holds an address or register at a value.
+    /// Arguments:
+    /// - name: The cheat's name, replacing any cheat with the same name.
+    /// - target: The address or register, `3F0` or `V5`.
+    /// - value: The value to hold it at.
+    ///
+    /// Returns: A JS error if the target does not parse.
+    #[wasm_bindgen(js_name = "addFreeze")]
+    pub fn add_freeze(&mut self, name: &str, target: &str, value: u8) -> Result<(), JsValue> {
+        let target: CheatTarget = target.parse().map_err(to_js_error)?;
+        self.emulator.add_cheat(Cheat::freeze(name, target, value));
+        Ok(())
+    }
+
+    /// This function removes a cheat.
+    /// Arguments:
+    /// - name: The cheat's name.
+    ///
+    /// Returns: True if a cheat by that name existed.
+    #[wasm_bindgen(js_name = "removeCheat")]
+    pub fn remove_cheat(&mut self, name: &str) -> bool {
+        self.emulator.remove_cheat(name).is_some()
+    }
+
+    /// This function returns the current instruction word at the program counter.
+    /// Arguments: none.
+    /// Returns: The 16-bit instruction word.
+    #[wasm_bindgen(js_name = "currentInstruction")]
+    pub fn current_instruction(&self) -> u16 {
+        let pc = self.emulator.program_counter as usize;
+        let memory = &self.emulator.memory;
+        if pc + 1 >= memory.len() {
+            return 0;
+        }
+        ((memory[pc] as u16) << 8) | memory[pc + 1] as u16
+    }
+
+    /// This function returns the current delay timer value.
+    /// Arguments: none.
+    /// Returns: The delay timer value.
+    #[wasm_bindgen(js_name = "delayTimer")]
+    pub fn delay_timer(&self) -> u8 {
+        self.emulator.delay_timer()
+    }
+
+    /// This function sets the delay timer.
+    /// Arguments:
+    /// - value: The new delay timer value.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setDelayTimer")]
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.emulator.delay_timer = value;
+    }
+
+    /// This function returns the current sound timer value.
+    /// Arguments: none.
+    /// Returns: The sound timer value.
+    #[wasm_bindgen(js_name = "soundTimer")]
+    pub fn sound_timer(&self) -> u8 {
+        self.emulator.sound_timer()
+    }
+
+    /// This function sets the sound timer, starting or stopping the tone to match.
+    /// Arguments:
+    /// - value: The new sound timer value.
+    ///
+    /// Returns: none.
+    #[wasm_bindgen(js_name = "setSoundTimer")]
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.emulator.sound_timer = value;
+        self.emulator.is_playing_sound = value > 0;
+    }
+
+    /// This function reports whether the sound timer is active.
+    /// Arguments: none.
+    /// Returns: True if sound is playing.
+    #[wasm_bindgen(js_name = "isSoundPlaying")]
+    pub fn is_sound_playing(&self) -> bool {
+        self.emulator.is_sound_playing()
+    }
+}
+
+impl Chip8Wasm {
+    // Re-resolves the keymap for the loaded ROM after the profile changed
+    fn refresh_keymap(&mut self) -> Result<(), JsValue> {
+        let keymap = self
+            .keymap_profile
+            .keymap_for_rom(&self.rom_sha1)
+            .map_err(to_js_error)?;
+        self.keyboard.set_keymap(keymap);
+        self.sync_keyboard();
+        Ok(())
+    }
+
+    // Forwards keypad changes as key events so taps between ticks still reach FX0A
+    fn sync_keyboard(&mut self) {
+        let mask = self.keyboard.mask();
+        let changed = mask ^ self.emulator.keys_mask();
+        for key in (0..16u8).filter(|key| changed & (1 << key) != 0) {
+            let result = if mask & (1 << key) != 0 {
+                self.emulator.key_down(key)
+            } else {
+                self.emulator.key_up(key)
+            };
+            result.expect("keypad keys from the mask are always valid");
+        }
+    }
+}
+
+/// This struct exposes a ROM metadata database to JavaScript callers.
+#[wasm_bindgen]
+pub struct Chip8RomDatabase {
+    database: RomDatabase,
+}
+
+#[wasm_bindgen]
+impl Chip8RomDatabase {
+    /// This function returns the database embedded in the emulator.
+    /// Arguments: none.
+    /// Returns: The embedded ROM database.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Chip8RomDatabase {
+        Chip8RomDatabase {
+            database: RomDatabase::embedded().clone(),
+        }
+    }
+
+    /// This function parses a database in the community `programs.json` format.
+    /// Arguments:
+    /// - json: The contents of `programs.json`.
+    ///
+    /// Returns: The parsed database, otherwise a JS error.
+    #[wasm_bindgen(js_name = "fromJson")]
+    pub fn from_json(json: &str) -> Result<Chip8RomDatabase, JsValue> {
+        RomDatabase::from_json(json)
+            .map(|database| Chip8RomDatabase { database })
+            .map_err(to_js_error)
+    }
+
+    /// This function returns the number of ROMs in the database.
+    /// Arguments: none.
+    /// Returns: The ROM count.
+    #[wasm_bindgen(js_name = "length")]
+    pub fn length(&self) -> u32 {
+        self.database.len() as u32
+    }
+
+    /// This function looks up a ROM by its bytes.
+    /// Arguments:
+    /// - rom_bytes: The ROM bytes.
+    ///
+    /// Returns: The ROM's metadata, or undefined when unknown.
+    #[wasm_bindgen(js_name = "lookup")]
+    pub fn lookup(&self, rom_bytes: &[u8]) -> Option<Chip8RomInfo> {
+        self.database
+            .lookup(rom_bytes)
+            .map(|info| Chip8RomInfo { info })
+    }
+}
+
+impl Default for Chip8RomDatabase {
+    /// This function returns the embedded ROM database.
+    /// Arguments: none.
+    /// Returns: The embedded ROM database.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// This struct holds the quirk flags for JavaScript callers.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chip8QuirkFlags {
+    /// This field stores whether FX55/FX65 increment I.
+    #[wasm_bindgen(js_name = "incrementIOnStore")]
+    pub increment_i_on_store: bool,
+    /// This field stores whether logic ops reset VF.
+    #[wasm_bindgen(js_name = "resetVfOnLogic")]
+    pub reset_vf_on_logic: bool,
+    /// This field stores whether sprites wrap at screen edges.
+    #[wasm_bindgen(js_name = "wrapDraw")]
+    pub wrap_draw: bool,
+    /// This field stores whether shifts use VX instead of VY.
+    #[wasm_bindgen(js_name = "shiftUsesVx")]
+    pub shift_uses_vx: bool,
+    /// This field stores whether FX0A waits for the key to be released.
+    #[wasm_bindgen(js_name = "waitForKeyRelease")]
+    pub wait_for_key_release: bool,
+}
+
+impl From<Chip8Quirks> for Chip8QuirkFlags {
+    /// This function converts core quirks into JS quirk flags.
+    /// Arguments:
+    /// - quirks: The core quirks.
+    ///
+    /// Returns: The JS quirk flags.
+    fn from(quirks: Chip8Quirks) -> Self {
+        Chip8QuirkFlags {
+            increment_i_on_store: quirks.increment_i_on_store,
+            reset_vf_on_logic: quirks.reset_vf_on_logic,
+            wrap_draw: quirks.wrap_draw,
+            shift_uses_vx: quirks.shift_uses_vx,
+            wait_for_key_release: quirks.wait_for_key_release,
+        }
+    }
+}
+
+/// This struct holds a copy of the CPU state for JavaScript callers.
+#[wasm_bindgen]
+pub struct Chip8Snapshot {
+    program_counter: u16,
+    current_instruction: u16,
+    index_register: u16,
+    registers: [u8; 16],
+    stack: [u16; 16],
+    stack_pointer: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+    is_sound_playing: bool,
+    keys: u16,
+    waiting_register: Option<u8>,
+    quirks: Chip8QuirkFlags,
+}
+
+#[wasm_bindgen]
+impl Chip8Snapshot {
+    /// This function returns the program counter.
+    /// Arguments: none.
+    /// Returns: The program counter.
+    #[wasm_bindgen(getter, js_name = "programCounter")]
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    /// This function returns the instruction at the program counter.
+    /// Arguments: none.
+    /// Returns: The 16-bit instruction word.
+    #[wasm_bindgen(getter, js_name = "currentInstruction")]
+    pub fn current_instruction(&self) -> u16 {
+        self.current_instruction
+    }
+
+    /// This function returns the index register I.
+    /// Arguments: none.
+    /// Returns: The I register value.
+    #[wasm_bindgen(getter, js_name = "indexRegister")]
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    /// This function returns the registers V0-VF.
+    /// Arguments: none.
+    /// Returns: 16 register values.
+    #[wasm_bindgen(getter)]
+    pub fn registers(&self) -> Vec<u8> {
+        self.registers.to_vec()
+    }
+
+    /// This function returns the 16 stack slots.
+    /// Arguments: none.
+    /// Returns: The stack, slots 1 to `stackPointer` hold return addresses.
+    #[wasm_bindgen(getter)]
+    pub fn stack(&self) -> Vec<u16> {
+        self.stack.to_vec()
+    }
+
+    /// This function returns the stack pointer.
+    /// Arguments: none.
+    /// Returns: The slot of the latest return address, 0 when the stack is empty.
+    #[wasm_bindgen(getter, js_name = "stackPointer")]
+    pub fn stack_pointer(&self) -> u8 {
+        self.stack_pointer
+    }
+
+    /// This function returns the delay timer.
+    /// Arguments: none.
+    /// Returns: The delay timer value.
+    #[wasm_bindgen(getter, js_name = "delayTimer")]
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// This function returns the sound timer.
+    /// Arguments: none.
+    /// Returns: The sound timer value.
+    #[wasm_bindgen(getter, js_name = "soundTimer")]
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// This function reports whether the tone is playing.
+    /// Arguments: none.
+    /// Returns: True while the sound timer runs.
+    #[wasm_bindgen(getter, js_name = "isSoundPlaying")]
+    pub fn is_sound_playing(&self) -> bool {
+        self.is_sound_playing
+    }
+
+    /// This function returns the keypad state.
+    /// Arguments: none.
+    /// Returns: A bitmask with bit N set while key N is held.
+    #[wasm_bindgen(getter)]
+    pub fn keys(&self) -> u16 {
+        self.keys
+    }
+
+    /// This function reports whether FX0A is waiting for a key.
+    /// Arguments: none.
+    /// Returns: True while the CPU is stopped on FX0A.
+    #[wasm_bindgen(getter, js_name = "isWaitingForKey")]
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_register.is_some()
+    }
+
+    /// This function returns the register FX0A will store the next key in.
+    /// Arguments: none.
+    /// Returns: The register X, or undefined when the CPU is not waiting.
+    #[wasm_bindgen(getter, js_name = "waitingRegister")]
+    pub fn waiting_register(&self) -> Option<u8> {
+        self.waiting_register
+    }
+
+    /// This function returns the active quirks.
+    /// Arguments: none.
+    /// Returns: The quirk flags.
+    #[wasm_bindgen(getter)]
+    pub fn quirks(&self) -> Chip8QuirkFlags {
+        self.quirks
+    }
+}
+
+/// This struct is one line of a disassembly listing for JavaScript callers.
+#[wasm_bindgen]
+pub struct Chip8DisassemblyLine {
+    line: DisassemblyLine,
+}
+
+impl From<DisassemblyLine> for Chip8DisassemblyLine {
+    /// This function wraps a core disassembly line.
+    /// Arguments:
+    /// - line: The core line.
+    ///
+    /// Returns: The JS line.
+    fn from(line: DisassemblyLine) -> Self {
+        Chip8DisassemblyLine { line }
+    }
+}
+
+#[wasm_bindgen]
+impl Chip8DisassemblyLine {
+    /// This function returns the address of the instruction word.
+    /// Arguments: none.
+    /// Returns: The address.
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> u16 {
+        self.line.address
+    }
+
+    /// This function returns the instruction word.
+    /// Arguments: none.
+    /// Returns: The 16-bit opcode.
+    #[wasm_bindgen(getter)]
+    pub fn opcode(&self) -> u16 {
+        self.line.opcode
+    }
+
+    /// This function returns the instruction as assembly.
+    /// Arguments: none.
+    /// Returns: Text such as `DRW V1, V2, 15`.
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.line.instruction.to_string()
+    }
+}
+
+/// This struct reports the end of a debugger run to JavaScript callers.
+#[wasm_bindgen]
+pub struct Chip8StopInfo {
+    stop: Stop,
+}
+
+impl From<Stop> for Chip8StopInfo {
+    /// This function wraps a core debugger stop.
+    /// Arguments:
+    /// - stop: The core stop.
+    ///
+    /// Returns: The JS stop info.
+    fn from(stop: Stop) -> Self {
+        Chip8StopInfo { stop }
+    }
+}
+
+#[wasm_bindgen]
+impl Chip8StopInfo {
+    /// This function returns why the run stopped.
+    /// Arguments: none.
+    /// Returns: The stop reason.
+    #[wasm_bindgen(getter)]
+    pub fn reason(&self) -> Chip8StopReason {
+        match self.stop.reason {
+            StopReason::Breakpoint(_) => Chip8StopReason::Breakpoint,
+            StopReason::Watchpoint(..) => Chip8StopReason::Watchpoint,
+            StopReason::Stepped => Chip8StopReason::Stepped,
+            StopReason::Halted => Chip8StopReason::Halted,
+            StopReason::CycleLimit => Chip8StopReason::CycleLimit,
+        }
+    }
+
+    /// This function returns the breakpoint or watched address that stopped the run.
+    /// Arguments: none.
+    /// Returns: The address, or undefined for other reasons.
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> Option<u16> {
+        match self.stop.reason {
+            StopReason::Breakpoint(address) | StopReason::Watchpoint(_, address) => Some(address),
+            _ => None,
+        }
+    }
+
+    /// This function returns the access a watchpoint caught.
+    /// Arguments: none.
+    /// Returns: The access, or undefined when no watchpoint fired.
+    #[wasm_bindgen(getter)]
+    pub fn access(&self) -> Option<Chip8Access> {
+        match self.stop.reason {
+            StopReason::Watchpoint(access, _) => Some(access.into()),
+            _ => None,
+        }
+    }
+
+    /// This function returns how many cycles ran.
+    /// Arguments: none.
+    /// Returns: The cycle count.
+    #[wasm_bindgen(getter)]
+    pub fn cycles(&self) -> u32 {
+        self.stop.cycles
+    }
+}
+
+/// This struct exposes one ROM database entry to JavaScript callers.
+#[wasm_bindgen]
+pub struct Chip8RomInfo {
+    info: RomInfo,
+}
+
+#[wasm_bindgen]
+impl Chip8RomInfo {
+    /// This function returns the hex SHA-1 of the ROM.
+    /// Arguments: none.
+    /// Returns: The SHA-1.
+    #[wasm_bindgen(getter)]
+    pub fn sha1(&self) -> String {
+        self.info.sha1.clone()
+    }
+
+    /// This function returns the program title.
+    /// Arguments: none.
+    /// Returns: The title.
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> String {
+        self.info.title.clone()
+    }
+
+    /// This function returns the program authors.
+    /// Arguments: none.
+    /// Returns: The authors.
+    #[wasm_bindgen(getter)]
+    pub fn authors(&self) -> Vec<String> {
+        self.info.authors.clone()
+    }
+
+    /// This function returns the database platform ids, most preferred first.
+    /// Arguments: none.
+    /// Returns: The platform ids.
+    #[wasm_bindgen(getter, js_name = "platformIds")]
+    pub fn platform_ids(&self) -> Vec<String> {
+        self.info.platform_ids.clone()
+    }
+
+    /// This function returns the emulated platform for the ROM.
+    /// Arguments: none.
+    /// Returns: The platform, or undefined when none of its platforms is emulated.
+    #[wasm_bindgen(getter)]
+    pub fn platform(&self) -> Option<Chip8Platform> {
+        self.info.platform.map(Chip8Platform::from)
+    }
+
+    /// This function returns the database names of the quirks the ROM needs but the emulator
+    /// does not implement.
+    /// Arguments: none.
+    /// Returns: The quirk names, e.g. `memoryIncrementByX`, empty when every quirk is applied.
+    #[wasm_bindgen(getter, js_name = "unsupportedQuirks")]
+    pub fn unsupported_quirks(&self) -> Vec<String> {
+        self.info
+            .unsupported_quirks
+            .iter()
+            .map(|&name| name.to_owned())
+            .collect()
+    }
+
+    /// This function returns the address the ROM should be loaded and started at.
+    /// Arguments: none.
+    /// Returns: The start address, or undefined for the platform's default.
+    #[wasm_bindgen(getter, js_name = "startAddress")]
+    pub fn start_address(&self) -> Option<u16> {
+        self.info.start_address
+    }
+
+    /// This function returns the recommended instructions per frame.
+    /// Arguments: none.
+    /// Returns: The tick rate, or undefined.
+    #[wasm_bindgen(getter, js_name = "tickRate")]
+    pub fn tick_rate(&self) -> Option<u32> {
+        self.info.tick_rate
+    }
+
+    /// This function reports whether DXYN should wait for the display interrupt.
+    /// Arguments: none.
+    /// Returns: True when the ROM expects the display wait.
+    #[wasm_bindgen(getter, js_name = "displayWait")]
+    pub fn display_wait(&self) -> bool {
+        self.info.display_wait
+    }
+
+    /// This function returns the key binding action names.
+    /// Arguments: none.
+    /// Returns: The action names, in the same order as `keyValues`.
+    #[wasm_bindgen(getter, js_name = "keyNames")]
+    pub fn key_names(&self) -> Vec<String> {
+        self.info.keys.keys().cloned().collect()
+    }
+
+    /// This function returns the keypad keys bound to the actions.
+    /// Arguments: none.
+    /// Returns: The keypad keys, in the same order as `keyNames`.
+    #[wasm_bindgen(getter, js_name = "keyValues")]
+    pub fn key_values(&self) -> Vec<u8> {
+        self.info.keys.values().copied().collect()
+    }
+
+    /// This function returns the recommended pixel colours, background first.
+    /// Arguments: none.
+    /// Returns: CSS hex colours, empty when the database has none.
+    #[wasm_bindgen(getter, js_name = "pixelColors")]
+    pub fn pixel_colors(&self) -> Vec<String> {
+        self.info
+            .colors
+            .as_ref()
+            .map(|colors| colors.pixels.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// This function returns the default Chip-8 display width in pixels.
+/// Arguments: none.
+/// Returns: The display width.
+#[wasm_bindgen(js_name = "displayWidth")]
+pub fn display_width() -> u32 {
+    DISPLAY_WIDTH as u32
 }
 
-/// This function returns the Chip-8 display height in pixels.
+/// This function returns the default Chip-8 display height in pixels.
 /// Arguments: none.
 /// Returns: The display height.
 #[wasm_bindgen(js_name = "displayHeight")]
@@ -175,7 +1901,7 @@ pub fn display_height() -> u32 {
     DISPLAY_HEIGHT as u32
 }
 
-/// This function returns the Chip-8 display pixel count.
+/// This function returns the default Chip-8 display pixel count.
 /// Arguments: none.
 /// Returns: The display pixel count.
 #[wasm_bindgen(js_name = "displayPixels")]
@@ -183,33 +1909,359 @@ pub fn display_pixels() -> u32 {
     DISPLAY_PIXELS as u32
 }
 
+// The register and stack editors, checked without building JS values so they run natively
+impl Chip8Wasm {
+    fn try_register(&self, x: u8) -> Result<u8, Chip8Error> {
+        self.emulator
+            .register
+            .get(x as usize)
+            .copied()
+            .ok_or(Chip8Error::InvalidRegisterX(x))
+    }
+
+    fn try_set_register(&mut self, x: u8, value: u8) -> Result<(), Chip8Error> {
+        let register = self
+            .emulator
+            .register
+            .get_mut(x as usize)
+            .ok_or(Chip8Error::InvalidRegisterX(x))?;
+        *register = value;
+        Ok(())
+    }
+
+    fn try_set_stack_slot(&mut self, slot: u8, address: u16) -> Result<(), Chip8Error> {
+        let entry = self
+            .emulator
+            .stack
+            .get_mut(slot as usize)
+            .ok_or(Chip8Error::InvalidStackPointer(slot))?;
+        *entry = address;
+        Ok(())
+    }
+
+    fn try_set_stack_pointer(&mut self, pointer: u8) -> Result<(), Chip8Error> {
+        if pointer as usize >= self.emulator.stack.len() {
+            return Err(Chip8Error::InvalidStackPointer(pointer));
+        }
+        self.emulator.stack_pointer = pointer;
+        Ok(())
+    }
+}
+
+impl Chip8Wasm {
+    // Redraws the pixel and RGBA buffers, CHIP-8X in its VP-590 colours
+    fn render(&mut self) {
+        unpack_framebuffer(self.emulator.framebuffer_packed(), &mut self.pixels);
+        let width = self.emulator.display_width();
+        let is_chip8x = self.emulator.platform() == Platform::Chip8X;
+        let background = CHIP8X_BACKGROUND_PALETTE[self.emulator.background_color() as usize];
+        for index in 0..width * self.emulator.display_height() {
+            let is_lit = self.pixels[index] == 1;
+            let color = match (is_chip8x, is_lit) {
+                (false, true) => self.on_color,
+                (false, false) => self.off_color,
+                (true, true) => {
+                    let (row, column) = (index / width, index % width / 8);
+                    let color = self.emulator.color_map()[row * CHIP8X_COLOR_COLUMNS + column];
+                    let [red, green, blue] = CHIP8X_FOREGROUND_PALETTE[color as usize];
+                    [red, green, blue, 0xFF]
+                }
+                (true, false) => [background[0], background[1], background[2], 0xFF],
+            };
+            self.rgba[index * 4..index * 4 + 4].copy_from_slice(&color);
+        }
+    }
+}
+
 /// This function converts packed framebuffer bytes into a 0/1 pixel array.
 /// Arguments:
 /// - packed: The packed framebuffer bytes.
-/// Returns: The unpacked framebuffer.
-fn unpack_framebuffer(packed: &[u8]) -> Vec<u8> {
-    let mut output = vec![0u8; DISPLAY_PIXELS];
+/// - output: The pixel array, at least eight bytes per packed byte.
+///
+/// Returns: none.
+fn unpack_framebuffer(packed: &[u8], output: &mut [u8]) {
     for (byte_index, byte) in packed.iter().enumerate() {
         let base = byte_index * 8;
-        if base >= DISPLAY_PIXELS {
-            break;
-        }
         for bit in 0..8u32 {
             let pixel_index = base + bit as usize;
-            if pixel_index >= DISPLAY_PIXELS {
-                break;
-            }
             let mask = 0b1000_0000u8 >> bit;
             output[pixel_index] = if byte & mask == mask { 1 } else { 0 };
         }
     }
-    output
 }
 
-/// This function converts a Chip-8 error into a JS error value.
+/// This function converts a Chip-8 error into a JS `Error` named after its variant, e.g.
+/// `InvalidKeypadKey`, so the TypeScript layer can map it to an error class.
 /// Arguments:
 /// - error: The chip-8 error to convert.
+///
 /// Returns: The JS error value.
 fn to_js_error(error: Chip8Error) -> JsValue {
-    JsValue::from_str(&format!("{error:?}"))
+    js_error(&error_name(&error), &format!("{error:?}"))
+}
+
+/// This function returns the name a Chip-8 error is thrown under.
+/// Arguments:
+/// - error: The chip-8 error.
+///
+/// Returns: The variant name, e.g. `InvalidKeypadKey`.
+fn error_name(error: &Chip8Error) -> String {
+    let message = format!("{error:?}");
+    let name = message.split('(').next().unwrap_or(&message);
+    name.to_owned()
+}
+
+/// This function builds a named JS `Error`.
+/// Arguments:
+/// - name: The error name.
+/// - message: The error message.
+///
+/// Returns: The JS error value.
+fn js_error(name: &str, message: &str) -> JsValue {
+    let error = js_sys::Error::new(message);
+    error.set_name(name);
+    error.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chip8sys::guard::{ProtectedRegion, WriteWarning};
+
+    // 00E0 A20A D015 220A 1208 00EE: clear, draw a sprite, call an empty subroutine, then halt
+    const ROM: [u8; 12] = [
+        0x00, 0xE0, 0xA2, 0x0A, 0xD0, 0x15, 0x22, 0x0A, 0x12, 0x08, 0x00, 0xEE,
+    ];
+
+    // Reads the bytes behind one of the zero-copy views
+    fn view(ptr: *const u8, len: usize) -> &'static [u8] {
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+
+    #[test]
+    // Tests that the display only reports a change after DXYN or 00E0 changed pixels
+    fn test_display_dirty() {
+        let mut emulator = Chip8Wasm::new();
+        emulator.load_rom(&ROM);
+        assert!(emulator.update_framebuffer());
+        assert!(!emulator.is_display_dirty());
+        assert!(!emulator.update_framebuffer());
+        emulator.tick(2).unwrap();
+        assert!(!emulator.update_framebuffer());
+        emulator.tick(1).unwrap();
+        assert!(emulator.is_display_dirty());
+        assert!(emulator.update_framebuffer());
+        assert!(!emulator.is_display_dirty());
+    }
+
+    #[test]
+    // Tests that the pixel and RGBA views hold the drawn sprite in the configured colours
+    fn test_framebuffer_views() {
+        let mut emulator = Chip8Wasm::new();
+        emulator.set_colors(0x11223344, 0x55667788);
+        emulator.load_rom(&ROM);
+        emulator.tick(3).unwrap();
+        emulator.update_framebuffer();
+        let pixels = view(emulator.framebuffer_ptr(), emulator.framebuffer_len());
+        assert_eq!(pixels.len(), 64 * 32);
+        assert_eq!(pixels, emulator.framebuffer().as_slice());
+        // the sprite at 0x20A is 00 EE 00 00 00, so only the second row is lit
+        assert_eq!(pixels[..8], [0; 8]);
+        assert_eq!(pixels[64..72], [1, 1, 1, 0, 1, 1, 1, 0]);
+        let rgba = view(emulator.rgba_ptr(), emulator.rgba_len());
+        assert_eq!(rgba[..4], [0x55, 0x66, 0x77, 0x88]);
+        assert_eq!(rgba[64 * 4..65 * 4], [0x11, 0x22, 0x33, 0x44]);
+    }
+
+    #[test]
+    // Tests that the memory, register and packed views point at the emulator's own buffers
+    fn test_memory_views() {
+        let mut emulator = Chip8Wasm::new();
+        emulator.load_rom(&ROM);
+        emulator.tick(3).unwrap();
+        let memory = view(emulator.memory_ptr(), emulator.memory_len());
+        assert_eq!(memory.len(), 4096);
+        assert_eq!(memory[0x200..0x20C], ROM);
+        emulator.set_register(3, 0x42).unwrap();
+        assert_eq!(view(emulator.registers_ptr(), 16)[3], 0x42);
+        let packed = view(
+            emulator.framebuffer_packed_ptr(),
+            emulator.framebuffer_packed_len(),
+        );
+        assert_eq!(packed, emulator.framebuffer_packed().as_slice());
+        assert_eq!(packed[8], 0xEE);
+    }
+
+    #[test]
+    // Tests that the snapshot copies the CPU state after a call and a key wait
+    fn test_snapshot() {
+        let mut emulator = Chip8Wasm::new();
+        // 6A07 A300 2206 00E0 F30A: set VA and I, call 0x206 and wait for a key into V3
+        emulator.load_rom(&[0x6A, 0x07, 0xA3, 0x00, 0x22, 0x06, 0x00, 0xE0, 0xF3, 0x0A]);
+        emulator.set_delay_timer(9);
+        emulator.press_key(0x5).unwrap();
+        emulator.tick(5).unwrap();
+        let snapshot = emulator.snapshot();
+        // FX0A has run and holds the CPU until a key goes down
+        assert_eq!(snapshot.program_counter(), 0x20A);
+        assert_eq!(
+            snapshot.current_instruction(),
+            emulator.current_instruction()
+        );
+        assert_eq!(snapshot.index_register(), 0x300);
+        assert_eq!(snapshot.registers()[0xA], 7);
+        assert_eq!(snapshot.registers(), emulator.registers());
+        assert_eq!(snapshot.stack_pointer(), 1);
+        assert_eq!(snapshot.stack()[1], 0x206);
+        assert_eq!(snapshot.stack(), emulator.stack());
+        assert_eq!(snapshot.delay_timer(), 9);
+        assert_eq!(snapshot.keys(), 1 << 5);
+        assert!(snapshot.is_waiting_for_key());
+        assert_eq!(snapshot.waiting_register(), Some(3));
+        assert_eq!(snapshot.quirks(), emulator.quirks());
+    }
+
+    #[test]
+    // Tests that registers and the stack can be edited within range
+    fn test_setters() {
+        let mut emulator = Chip8Wasm::new();
+        emulator.try_set_register(0xF, 0x12).unwrap();
+        assert_eq!(emulator.try_register(0xF).unwrap(), 0x12);
+        emulator.set_index_register(0x345);
+        emulator.set_program_counter(0x246);
+        emulator.try_set_stack_slot(0xF, 0x208).unwrap();
+        emulator.try_set_stack_pointer(0xF).unwrap();
+        let snapshot = emulator.snapshot();
+        assert_eq!(snapshot.index_register(), 0x345);
+        assert_eq!(snapshot.program_counter(), 0x246);
+        assert_eq!(snapshot.stack()[0xF], 0x208);
+        assert_eq!(snapshot.stack_pointer(), 0xF);
+    }
+
+    #[test]
+    // Tests that out-of-range registers, stack slots and keys are rejected without changes
+    fn test_setters_out_of_range() {
+        let mut emulator = Chip8Wasm::new();
+        assert!(matches!(
+            emulator.try_register(0x10),
+            Err(Chip8Error::InvalidRegisterX(0x10))
+        ));
+        assert!(matches!(
+            emulator.try_set_register(0x10, 1),
+            Err(Chip8Error::InvalidRegisterX(0x10))
+        ));
+        assert!(matches!(
+            emulator.try_set_stack_slot(0x10, 0x208),
+            Err(Chip8Error::InvalidStackPointer(0x10))
+        ));
+        assert!(matches!(
+            emulator.try_set_stack_pointer(0x10),
+            Err(Chip8Error::InvalidStackPointer(0x10))
+        ));
+        // pressKey and releaseKey pass the core's error on
+        assert!(matches!(
+            emulator.emulator.key_down(0x10),
+            Err(Chip8Error::InvalidKeypadKey(0x10))
+        ));
+        assert!(matches!(
+            emulator.emulator.key_up(0xFF),
+            Err(Chip8Error::InvalidKeypadKey(0xFF))
+        ));
+        assert_eq!(emulator.registers(), vec![0; 16]);
+        assert_eq!(emulator.stack(), vec![0; 16]);
+        assert_eq!(emulator.stack_pointer(), 0);
+        assert_eq!(emulator.keys(), 0);
+    }
+
+    #[test]
+    // Tests that errors are named after their variant and that every name has a TS error class
+    fn test_error_names() {
+        let warning = WriteWarning {
+            program_counter: 0x200,
+            address: 0x200,
+            value: 0,
+            region: ProtectedRegion::Code,
+        };
+        let errors = [
+            Chip8Error::InvalidFirstByte(0x1),
+            Chip8Error::InvalidRegisterX(0x10),
+            Chip8Error::Invalid0x8XYN(0x8),
+            Chip8Error::Invalid0xENNN(0x1, 0x2),
+            Chip8Error::Invalid0xFNNN(0x1, 0x2),
+            Chip8Error::InvalidWaitRegister(0x10),
+            Chip8Error::InvalidStackPointer(0x10),
+            Chip8Error::IssueGeneratingRandomNum(getrandom::Error::UNSUPPORTED),
+            Chip8Error::InvalidRomDatabase("line 1".to_owned()),
+            Chip8Error::InvalidKeypadKey(0x10),
+            Chip8Error::InvalidKeymapProfile("line 1".to_owned()),
+            Chip8Error::InvalidMemorySize(3000),
+            Chip8Error::InvalidLoadAddress(0x50),
+            Chip8Error::RomTooLarge(5000),
+            Chip8Error::InvalidFont(11),
+            Chip8Error::InvalidFontAddress(0xFFF),
+            Chip8Error::InvalidTimingModel,
+            Chip8Error::StateBufferTooSmall(10),
+            Chip8Error::InvalidSaveState(4),
+            Chip8Error::Watchpoint(Access::Write, 0x300),
+            Chip8Error::ProtectedWrite(warning),
+            Chip8Error::InvalidSymbolFile("line 1".to_owned()),
+            Chip8Error::InvalidCheat("line 1".to_owned()),
+        ];
+        assert_eq!(error_name(&errors[9]), "InvalidKeypadKey");
+        assert_eq!(error_name(&errors[16]), "InvalidTimingModel");
+        let errors_ts = include_str!("../js/src/errors.ts");
+        let names = errors.iter().map(error_name);
+        for name in names.chain(["CoverageOff".to_owned()]) {
+            assert!(
+                errors_ts.contains(&format!("| \"{name}\"")),
+                "{name} has no kind"
+            );
+            assert!(
+                errors_ts.contains(&format!("  {name}: ")),
+                "{name} has no class"
+            );
+        }
+    }
+
+    #[test]
+    // Tests that every core stop reason maps to its JS reason, address and access
+    fn test_stop_reasons() {
+        let info = |reason| Chip8StopInfo::from(Stop { reason, cycles: 7 });
+        let breakpoint = info(StopReason::Breakpoint(0x204));
+        assert_eq!(breakpoint.reason(), Chip8StopReason::Breakpoint);
+        assert_eq!(breakpoint.address(), Some(0x204));
+        assert_eq!(breakpoint.access(), None);
+        assert_eq!(breakpoint.cycles(), 7);
+        let watchpoint = info(StopReason::Watchpoint(Access::Write, 0x300));
+        assert_eq!(watchpoint.reason(), Chip8StopReason::Watchpoint);
+        assert_eq!(watchpoint.address(), Some(0x300));
+        assert_eq!(watchpoint.access(), Some(Chip8Access::Write));
+        for (reason, expected) in [
+            (StopReason::Stepped, Chip8StopReason::Stepped),
+            (StopReason::Halted, Chip8StopReason::Halted),
+            (StopReason::CycleLimit, Chip8StopReason::CycleLimit),
+        ] {
+            let stop = info(reason);
+            assert_eq!(stop.reason(), expected);
+            assert_eq!(stop.address(), None);
+            assert_eq!(stop.access(), None);
+        }
+    }
+
+    #[test]
+    // Tests that a watchpoint stops runUntilBreak before the access and can be removed
+    fn test_watchpoint_stop() {
+        let mut emulator = Chip8Wasm::new();
+        emulator.load_rom(&ROM);
+        let id = emulator.add_watchpoint(0x20A, 0x20A, Chip8Access::Read);
+        let stop = emulator.run_until_break(100).unwrap();
+        assert_eq!(stop.reason(), Chip8StopReason::Watchpoint);
+        assert_eq!(stop.address(), Some(0x20A));
+        assert_eq!(stop.access(), Some(Chip8Access::Read));
+        assert_eq!(emulator.program_counter(), 0x204);
+        assert!(emulator.remove_watchpoint(id));
+        assert!(!emulator.remove_watchpoint(id));
+        let stop = emulator.run_until_break(100).unwrap();
+        assert_eq!(stop.reason(), Chip8StopReason::Halted);
+    }
 }
@@ -0,0 +1,68 @@
+//! Tests of the JavaScript API, run under Node with `wasm-pack test --node chip8wasm`.
+#![cfg(target_arch = "wasm32")]
+
+use chip8wasm::{Chip8QuirkPreset, Chip8StopReason, Chip8Wasm};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// 00E0 A20A D015 220A 1208 00EE: clear, draw a sprite, call an empty subroutine, then halt
+const ROM: [u8; 12] = [
+    0x00, 0xE0, 0xA2, 0x0A, 0xD0, 0x15, 0x22, 0x0A, 0x12, 0x08, 0x00, 0xEE,
+];
+
+#[wasm_bindgen_test]
+// Tests that errors reach JS as Error objects named after their Chip8Error variant
+fn test_error_names() {
+    let mut emulator = Chip8Wasm::new();
+    let error = emulator.press_key(16).unwrap_err();
+    let error = error.dyn_into::<js_sys::Error>().unwrap();
+    assert_eq!(String::from(error.name()), "InvalidKeypadKey");
+}
+
+#[wasm_bindgen_test]
+// Tests that runUntilBreak stops at a breakpoint and that stepping continues from it
+fn test_breakpoints() {
+    let mut emulator = Chip8Wasm::new();
+    emulator.load_rom(&ROM);
+    emulator.add_breakpoint(0x206);
+    let stop = emulator.run_until_break(100).unwrap();
+    assert_eq!(stop.reason(), Chip8StopReason::Breakpoint);
+    assert_eq!(stop.address(), Some(0x206));
+    assert_eq!(stop.cycles(), 3);
+    let stop = emulator.step_over(100).unwrap();
+    assert_eq!(stop.reason(), Chip8StopReason::Stepped);
+    assert_eq!(emulator.program_counter(), 0x208);
+    assert_eq!(
+        emulator.run_until_break(100).unwrap().reason(),
+        Chip8StopReason::Halted
+    );
+    assert_eq!(
+        emulator.disassemble(0x204, 0x206)[0].text(),
+        "DRW V0, V1, 5"
+    );
+}
+
+#[wasm_bindgen_test]
+// Tests that the framebuffer only reports changes once, and the views cover the display
+fn test_framebuffer_updates() {
+    let mut emulator = Chip8Wasm::new();
+    emulator.load_rom(&ROM);
+    assert!(emulator.update_framebuffer());
+    assert!(!emulator.update_framebuffer());
+    emulator.tick(3).unwrap();
+    assert!(emulator.update_framebuffer());
+    assert_eq!(emulator.framebuffer_len(), 64 * 32);
+    assert_eq!(emulator.rgba_len(), 64 * 32 * 4);
+    assert_eq!(emulator.framebuffer().len(), 64 * 32);
+}
+
+#[wasm_bindgen_test]
+// Tests that quirk presets and the snapshot agree
+fn test_quirk_preset() {
+    let mut emulator = Chip8Wasm::new();
+    emulator.set_quirk_preset(Chip8QuirkPreset::SuperChip);
+    let snapshot = emulator.snapshot();
+    assert!(snapshot.quirks().shift_uses_vx);
+    assert!(!snapshot.quirks().increment_i_on_store);
+    assert_eq!(snapshot.program_counter(), 0x200);
+}
@@ -0,0 +1,15 @@
+#!/usr/bin/env sh
+# Lints and tests chip8sys with no features, each feature on its own and all features.
+set -eu
+
+run() {
+    echo "== chip8sys $*"
+    cargo clippy -p chip8sys --all-targets "$@" -- -D warnings
+    cargo test -p chip8sys "$@"
+}
+
+run --no-default-features
+for feature in std serde os-rng debugger disasm audio; do
+    run --no-default-features --features "$feature"
+done
+run --all-features
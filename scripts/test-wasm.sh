@@ -0,0 +1,7 @@
+#!/usr/bin/env sh
+# Runs the chip8wasm JavaScript API tests under Node. Needs wasm-pack, Node and the
+# wasm32-unknown-unknown target (rustup target add wasm32-unknown-unknown).
+set -eu
+
+cd "$(dirname "$0")/.."
+wasm-pack test --node chip8wasm "$@"
@@ -0,0 +1,12 @@
+#!/usr/bin/env sh
+# Vendors the community CHIP-8 database into chip8sys/data, together with its licence.
+# Usage: ./scripts/update-rom-db.sh [git ref of chip-8/chip-8-database, default master]
+set -eu
+
+ref="${1:-master}"
+base="https://raw.githubusercontent.com/chip-8/chip-8-database/$ref"
+data="$(dirname "$0")/../chip8sys/data"
+
+curl -fsSL "$base/database/programs.json" -o "$data/programs.json"
+curl -fsSL "$base/LICENSE" -o "$data/LICENSE-chip-8-database"
+echo "Vendored chip-8-database $ref into $data"